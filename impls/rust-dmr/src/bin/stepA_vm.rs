@@ -0,0 +1,18 @@
+// As stepA_mal, but evaluating with the bytecode compiler and VM rather than
+// the tree-walker. Handy for performance comparisons, e.g.
+//     STEP=stepA_vm ./run ../tests/perf3.mal
+
+use rust_dmr_mal::interpreter::Engine;
+use rust_dmr_mal::{cmdline, environment};
+use std::rc::Rc;
+
+fn main() {
+    let env = Rc::new(environment::Environment::default());
+    environment::add_eval_using(&env, Engine::Bytecode);
+    let args = std::env::args().collect();
+    std::process::exit(cmdline::report(cmdline::launch_using(
+        args,
+        &env,
+        Engine::Bytecode,
+    )))
+}
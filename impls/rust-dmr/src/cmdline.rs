@@ -1,10 +1,12 @@
 use crate::environment::Environment;
-use crate::evaluator::EVAL;
+use crate::interpreter::Engine;
+use crate::printer::PrintMode;
 use crate::types::{MalObject, MalSymbol};
-use crate::{interpreter, printer};
+use crate::{environment, evaluator, interpreter, limits, namespaces, printer, random, reader};
 use ansi_term::Style;
 use linefeed::{DefaultTerminal, Interface, ReadResult, Terminal};
-use std::cmp::min;
+use std::fmt;
+use std::io::Read;
 use std::path::PathBuf;
 use std::rc::Rc;
 
@@ -15,8 +17,8 @@ where
     let interface = setup()?;
     let processor = |line: &str| rep(line);
     rep("(println (str \"Mal [\" *host-language* \"]\"))").unwrap();
+    limits::interrupt_on_sigint()?;
     repl(&interface, processor);
-    save_history(&interface)?;
     Ok(())
 }
 
@@ -89,6 +91,9 @@ where
                     continue;
                 }
                 interface.add_history_unique(line.clone());
+                if let Err(e) = save_history(interface) {
+                    log::warn!("Failed to save history: {}", e);
+                }
                 match processor(&line) {
                     Ok(Outcome::String(s)) => writeln!(interface, "{}", s).ok(),
                     Ok(Outcome::Empty) => continue,
@@ -103,48 +108,268 @@ where
     }
 }
 
-enum Mode {
-    Repl,
-    Batch(String),
+const USAGE: &str = "\
+usage: mal [OPTION]... [-e EXPR]... [FILE | -] [ARG]...
+
+Run FILE as a mal script, or read the script from stdin if FILE is -. A first
+line starting with #! is ignored. Any ARGs are available to the script as
+*ARGV*. With no FILE or EXPR, start a REPL.
+
+If MAL_RANDOM_SEED is set to a number, random numbers in FILE and EXPRs come
+from that seed, so are the same every run.
+
+Namespaces loaded with require are looked for in the directories given with
+-I, then in those listed in MAL_PATH, then in the working directory.
+
+options:
+  -e EXPR            evaluate the forms in EXPR and print the last one's value,
+                     unless it's nil
+  -i                 start a REPL after running FILE and EXPRs
+  -I DIR             add DIR to the load path
+  --no-prelude       don't define the functions and macros from the prelude
+  --log-level LEVEL  one of off, error, warn, info, debug or trace
+  -h, --help         show this message
+";
+
+enum Source {
+    File(String),
+    Stdin,
+}
+
+// What the command line asked for. Expressions are evaluated before the script.
+struct Options {
+    expressions: Vec<String>,
+    script: Option<Source>,
+    script_args: Vec<String>,
+    interactive: bool,
+    load_path: Vec<PathBuf>,
+    prelude: bool,
+    log_level: Option<log::LevelFilter>,
+    help: bool,
 }
 
 #[derive(Debug)]
 pub enum Error {
     IO(std::io::Error),
-    BadArguments,
-    RepError(String),
+    BadArguments(String),
+    /// The script couldn't be parsed, or called `read-string` on bad input.
+    Read(String),
+    /// The script threw a value which nothing caught.
+    Uncaught(String),
+    /// Anything else which went wrong while evaluating the script.
+    Eval(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::IO(e) => write!(f, "io error: {}", e),
+            Error::BadArguments(e) => write!(f, "{}\n\n{}", e, USAGE),
+            Error::Read(e) | Error::Uncaught(e) | Error::Eval(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error {
+    /// The status a binary should exit with after this error. Scripts can exit
+    /// with a status of their choosing using `(exit n)`.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Uncaught(_) => 1,
+            Error::Eval(_) => 2,
+            Error::Read(_) => 3,
+            Error::BadArguments(_) => 64,
+            Error::IO(_) => 74,
+        }
+    }
+}
+
+impl From<evaluator::Error> for Error {
+    fn from(e: evaluator::Error) -> Self {
+        match e {
+            evaluator::Error::ReadError(_) => Error::Read(e.to_string()),
+            evaluator::Error::UserException(_) => Error::Uncaught(e.to_string()),
+            _ => Error::Eval(e.to_string()),
+        }
+    }
 }
 
-fn process_argv(args: &[String]) -> Mode {
+/// Report the outcome of `launch` on stderr, and return the status to exit with.
+pub fn report(result: Result<(), Error>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            e.exit_code()
+        }
+    }
+}
+
+fn process_argv(args: &[String]) -> Result<Options, Error> {
     log::debug!("command line args={:?}", args);
-    match args {
-        [] | [_] => Mode::Repl,
-        [_program_name, file_path, ..] => Mode::Batch(file_path.clone()),
-    }
-}
-
-pub fn launch(mut args: Vec<String>, env: &Rc<Environment>) -> Result<(), Error> {
-    let mode = process_argv(&args);
-
-    // Remove two args (rust binary name and mal file name) to get what Mal considers its argv
-    let script_args = args.split_off(min(args.len(), 2));
-    log::debug!("Invoked with arguments {:?}", script_args);
-    let script_args =
-        MalObject::wrap_list(script_args.into_iter().map(MalObject::String).collect());
-    env.set(MalSymbol("*ARGV*".into()), script_args);
-
-    match mode {
-        Mode::Repl => run(|line| interpreter::rep(line, &env)).map_err(Error::IO),
-        Mode::Batch(path) => {
-            let cmd = MalObject::wrap_list(vec![
-                MalObject::new_symbol("load-file"),
-                MalObject::String(path),
-            ]);
-            log::debug!("Batch mode, run cmd {}", cmd);
-            match EVAL(&cmd, &env) {
-                Ok(_) => Ok(()),
-                Err(e) => Err(Error::RepError(e.to_string())),
+    let mut options = Options {
+        expressions: Vec::new(),
+        script: None,
+        script_args: Vec::new(),
+        interactive: false,
+        load_path: Vec::new(),
+        prelude: true,
+        log_level: None,
+        help: false,
+    };
+    // Skip the program name.
+    let mut args = args.iter().skip(1);
+    let missing = |option: &str| Error::BadArguments(format!("{} needs an argument", option));
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-e" => options
+                .expressions
+                .push(args.next().ok_or_else(|| missing("-e"))?.clone()),
+            "-i" => options.interactive = true,
+            "-I" => options
+                .load_path
+                .push(args.next().ok_or_else(|| missing("-I"))?.into()),
+            "--no-prelude" => options.prelude = false,
+            "--log-level" => {
+                let level = args.next().ok_or_else(|| missing("--log-level"))?;
+                let level = level
+                    .parse()
+                    .map_err(|_| Error::BadArguments(format!("unknown log level {}", level)))?;
+                options.log_level = Some(level);
+            }
+            "-h" | "--help" => options.help = true,
+            "-" => {
+                options.script = Some(Source::Stdin);
+                break;
+            }
+            "--" => {
+                options.script = args.next().cloned().map(Source::File);
+                break;
+            }
+            option if option.starts_with('-') => {
+                return Err(Error::BadArguments(format!("unknown option {}", option)))
+            }
+            path => {
+                options.script = Some(Source::File(path.into()));
+                break;
+            }
+        }
+    }
+    options.script_args = args.cloned().collect();
+    Ok(options)
+}
+
+// Log to stderr, at the level from the command line if given, otherwise as
+// configured by RUST_LOG. Does nothing if a logger is already set up.
+fn setup_logging(level: Option<log::LevelFilter>) {
+    let mut builder = pretty_env_logger::formatted_builder();
+    match (level, std::env::var("RUST_LOG")) {
+        (Some(level), _) => builder.filter_level(level),
+        (None, Ok(filters)) => builder.parse_filters(&filters),
+        (None, Err(_)) => &mut builder,
+    };
+    if builder.try_init().is_ok() {
+        if let Some(level) = level {
+            log::set_max_level(level);
+        }
+    }
+}
+
+fn read_script(source: &Source) -> Result<String, Error> {
+    let text = match source {
+        Source::File(path) => std::fs::read_to_string(path),
+        Source::Stdin => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text).map(|_| text)
+        }
+    };
+    // The reader skips any shebang line.
+    text.map_err(Error::IO)
+}
+
+fn run_script(text: &str, env: &Rc<Environment>, engine: Engine) -> Result<(), Error> {
+    let forms = reader::read_all(text).map_err(|e| Error::Read(e.to_string()))?;
+    for form in forms {
+        engine.eval(&form, env)?;
+    }
+    Ok(())
+}
+
+// Make a script's random numbers reproducible, if asked to.
+fn seed_from_environment() -> Result<(), Error> {
+    if let Ok(value) = std::env::var(random::SEED_VARIABLE) {
+        let seed = value.trim().parse().map_err(|_| {
+            Error::BadArguments(format!(
+                "{} must be a number, not {:?}",
+                random::SEED_VARIABLE,
+                value
+            ))
+        })?;
+        random::seed(seed);
+    }
+    Ok(())
+}
+
+/// Run mal as the command line in `args` asks: see `USAGE`. The environment
+/// should have the primitives and `eval`. The prelude is added unless the
+/// command line says not to.
+pub fn launch(args: Vec<String>, env: &Rc<Environment>) -> Result<(), Error> {
+    launch_using(args, env, Engine::TreeWalker)
+}
+
+pub fn launch_using(args: Vec<String>, env: &Rc<Environment>, engine: Engine) -> Result<(), Error> {
+    let options = process_argv(&args)?;
+    if options.help {
+        print!("{}", USAGE);
+        return Ok(());
+    }
+    setup_logging(options.log_level);
+    for dir in &options.load_path {
+        namespaces::add_to_load_path(env, dir);
+    }
+    if let Some(dirs) = std::env::var_os(namespaces::LOAD_PATH_VARIABLE) {
+        for dir in std::env::split_paths(&dirs) {
+            namespaces::add_to_load_path(env, dir);
+        }
+    }
+    if options.prelude {
+        environment::read_prelude_using(env, engine).map_err(Error::Eval)?;
+    }
+
+    log::debug!("Invoked with arguments {:?}", options.script_args);
+    let script_args = options.script_args.into_iter().map(MalObject::String);
+    env.set(
+        MalSymbol("*ARGV*".into()),
+        MalObject::wrap_list(script_args.collect()),
+    );
+
+    let batch = !options.expressions.is_empty() || options.script.is_some();
+    if batch {
+        seed_from_environment()?;
+    }
+    for expression in &options.expressions {
+        let forms = reader::read_all(expression).map_err(|e| Error::Read(e.to_string()))?;
+        let mut last = MalObject::Nil;
+        for form in forms {
+            last = engine.eval(&form, env)?;
+        }
+        match last {
+            MalObject::Nil => {}
+            value => {
+                printer::realise(&value)?;
+                println!(
+                    "{}",
+                    printer::pr_str(&value, PrintMode::ReadableRepresentation)
+                )
             }
         }
     }
+    if let Some(source) = &options.script {
+        log::debug!("Batch mode, running script");
+        run_script(&read_script(source)?, env, engine)?;
+    }
+    if options.interactive || !batch {
+        run(|line| interpreter::rep_using(line, env, engine)).map_err(Error::IO)?;
+    }
+    Ok(())
 }
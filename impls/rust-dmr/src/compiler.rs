@@ -0,0 +1,420 @@
+// The tree-walking evaluator re-dispatches on every node of the AST each time
+// it is evaluated. Here we do that work once: a macroexpanded form is
+// translated into a flat sequence of instructions for the stack machine in
+// `vm`.
+//
+// Function bodies are compiled when the enclosing `fn*` is compiled, but
+// whether a form is a macro call can only be decided when it's run: its head
+// may be defined (or redefined) as a macro later on. So a call whose head is
+// a symbol is preceded by a check which expands it at runtime if so, as the
+// tree-walker would. The exception is a symbol bound by an enclosing `let*`,
+// `fn*` or `catch*`, which the compiler can see is a local and so never a
+// macro. Anything we can't make sense of (e.g. a malformed special form) is
+// left to the tree-walker at runtime, so that it reports the same errors as it
+// would have done without compilation.
+
+use crate::special_forms;
+use crate::types::{Arity, ClosureParameters, MalObject, MalSymbol};
+use itertools::Itertools;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Op {
+    /// Push a copy of the given constant.
+    Constant(usize),
+    /// Push the value bound to the given symbol constant.
+    Lookup(usize),
+    /// Bind the given symbol constant to the top of the stack, leaving it in
+    /// place.
+    Define(usize),
+    DefineMacro(usize),
+    Pop,
+    Jump(usize),
+    /// Pop the top of the stack and jump if it is falsey.
+    JumpIfFalse(usize),
+    /// Push a closure over the current environment built from the given lambda.
+    MakeClosure(usize),
+    /// Replace the closure on top of the stack with a lazy sequence which calls
+    /// it when realised.
+    MakeLazySeq,
+    /// Call the function sitting beneath the given number of arguments.
+    Call(usize),
+    /// As `Call`, but reuse the current frame.
+    TailCall(usize),
+    /// If the given form is a call to a macro, expand it and run the expansion,
+    /// then jump to the given instruction. Otherwise carry on with the compiled
+    /// call which follows.
+    MacroCall(usize, usize),
+    /// As `MacroCall`, but run the expansion in place of the current frame.
+    TailMacroCall(usize),
+    Return,
+    /// Enter a fresh child of the current environment.
+    PushEnv,
+    /// Return to the parent of the current environment.
+    PopEnv,
+    /// Collect the given number of values into a vector.
+    MakeVector(usize),
+    /// Collect the given number of alternating keys and values into a map.
+    MakeMap(usize),
+    /// Run the given `try*` block.
+    Try(usize),
+    /// Run the given `binding` block, with its variables' new values taken from
+    /// the top of the stack.
+    Binding(usize),
+    /// Push the macroexpansion of the given constant.
+    Macroexpand(usize),
+    /// Hand the given constant to the tree-walking evaluator.
+    Interpret(usize),
+}
+
+#[derive(Debug)]
+pub struct Lambda {
+    pub(crate) parameters: ClosureParameters,
+    pub(crate) body: MalObject,
+    pub(crate) chunk: Rc<Chunk>,
+}
+
+#[derive(Debug)]
+pub struct TryBlock {
+    pub(crate) body: Rc<Chunk>,
+    pub(crate) catch: Option<(MalSymbol, Rc<Chunk>)>,
+}
+
+#[derive(Debug)]
+pub struct BindingBlock {
+    pub(crate) names: Vec<MalSymbol>,
+    pub(crate) body: Rc<Chunk>,
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub(crate) code: Vec<Op>,
+    pub(crate) constants: Vec<MalObject>,
+    pub(crate) lambdas: Vec<Lambda>,
+    pub(crate) try_blocks: Vec<TryBlock>,
+    pub(crate) binding_blocks: Vec<BindingBlock>,
+}
+
+/// Compile `ast` to a chunk which evaluates it and returns the result.
+pub fn compile(ast: &MalObject) -> Chunk {
+    compile_in_scope(ast, Vec::new())
+}
+
+fn compile_in_scope(ast: &MalObject, locals: Vec<MalSymbol>) -> Chunk {
+    let mut compiler = Compiler {
+        chunk: Chunk::default(),
+        locals,
+    };
+    compiler.compile_form(ast, true);
+    compiler.emit(Op::Return);
+    compiler.chunk
+}
+
+struct Compiler {
+    chunk: Chunk,
+    // Symbols bound by the enclosing `let*`, `fn*` and `catch*` forms.
+    locals: Vec<MalSymbol>,
+}
+
+impl Compiler {
+    fn emit(&mut self, op: Op) -> usize {
+        self.chunk.code.push(op);
+        self.chunk.code.len() - 1
+    }
+
+    fn constant(&mut self, obj: MalObject) -> usize {
+        self.chunk.constants.push(obj);
+        self.chunk.constants.len() - 1
+    }
+
+    fn patch_jump(&mut self, at: usize) {
+        let target = self.chunk.code.len();
+        match &mut self.chunk.code[at] {
+            Op::Jump(dest) | Op::JumpIfFalse(dest) | Op::MacroCall(_, dest) => *dest = target,
+            op => unreachable!("tried to patch {:?}", op),
+        }
+    }
+
+    fn interpret(&mut self, ast: &MalObject) {
+        let index = self.constant(ast.clone());
+        self.emit(Op::Interpret(index));
+    }
+
+    // Compile `ast` to a separate chunk which also sees `locals`.
+    fn compile_nested(&self, ast: &MalObject, locals: &[MalSymbol]) -> Rc<Chunk> {
+        let mut scope = self.locals.clone();
+        scope.extend_from_slice(locals);
+        Rc::new(compile_in_scope(ast, scope))
+    }
+
+    // `tail` is true if the value of `ast` is the value of the entire chunk.
+    fn compile_form(&mut self, ast: &MalObject, tail: bool) {
+        match ast {
+            MalObject::List(list) if !list.payload.is_empty() => {
+                let (head, args) = list.payload.split_first().unwrap();
+                let special = match head {
+                    MalObject::Symbol(name) => self.compile_special(name, args, ast, tail),
+                    _ => false,
+                };
+                if !special {
+                    self.compile_call_or_macro(ast, tail);
+                }
+            }
+            MalObject::Symbol(_) => {
+                let index = self.constant(ast.clone());
+                self.emit(Op::Lookup(index));
+            }
+            MalObject::Vector(vec) => {
+                for element in vec.payload.iter() {
+                    self.compile_form(element, false);
+                }
+                self.emit(Op::MakeVector(vec.payload.len()));
+            }
+            MalObject::Map(map) => {
+                for (key, value) in map.payload.iter() {
+                    let index = self.constant(key.into_mal_object());
+                    self.emit(Op::Constant(index));
+                    self.compile_form(value, false);
+                }
+                self.emit(Op::MakeMap(2 * map.payload.len()));
+            }
+            _ => {
+                let index = self.constant(ast.clone());
+                self.emit(Op::Constant(index));
+            }
+        };
+    }
+
+    // A call to something which may turn out to be a macro at runtime.
+    fn compile_call_or_macro(&mut self, ast: &MalObject, tail: bool) {
+        let forms = &ast.as_list().unwrap().payload;
+        let maybe_macro = match &forms[0] {
+            MalObject::Symbol(name) => !self.locals.contains(name),
+            _ => false,
+        };
+        if !maybe_macro {
+            return self.compile_call(forms, tail);
+        }
+        let index = self.constant(ast.clone());
+        match tail {
+            true => {
+                self.emit(Op::TailMacroCall(index));
+                self.compile_call(forms, tail);
+            }
+            false => {
+                let check = self.emit(Op::MacroCall(index, 0));
+                self.compile_call(forms, tail);
+                self.patch_jump(check);
+            }
+        }
+    }
+
+    fn compile_call(&mut self, forms: &[MalObject], tail: bool) {
+        for form in forms {
+            self.compile_form(form, false);
+        }
+        let argc = forms.len() - 1;
+        self.emit(match tail {
+            true => Op::TailCall(argc),
+            false => Op::Call(argc),
+        });
+    }
+
+    // Returns false if `name` isn't a special form.
+    fn compile_special(
+        &mut self,
+        name: &MalSymbol,
+        args: &[MalObject],
+        ast: &MalObject,
+        tail: bool,
+    ) -> bool {
+        match name.as_str() {
+            "def!" => self.compile_def(args, ast, false),
+            "defmacro!" => self.compile_def(args, ast, true),
+            "let*" => self.compile_let(args, ast, tail),
+            "do" => self.compile_do(args, ast, tail),
+            "if" => self.compile_if(args, ast, tail),
+            "fn*" => self.compile_fn(args, ast),
+            "lazy-seq" => {
+                let body = special_forms::lazy_seq_body(args);
+                self.compile_fn(&[MalObject::new_list(), body], ast);
+                self.emit(Op::MakeLazySeq);
+            }
+            "quote" => match args {
+                [quoted] => {
+                    let index = self.constant(quoted.clone());
+                    self.emit(Op::Constant(index));
+                }
+                _ => self.interpret(ast),
+            },
+            "quasiquote" => match args {
+                [quasiquoted] => match special_forms::apply_quasiquote(quasiquoted) {
+                    Ok(expanded) => self.compile_form(&expanded, tail),
+                    Err(_) => self.interpret(ast),
+                },
+                _ => self.interpret(ast),
+            },
+            "macroexpand" => match args {
+                [form] => {
+                    let index = self.constant(form.clone());
+                    self.emit(Op::Macroexpand(index));
+                }
+                _ => self.interpret(ast),
+            },
+            "try*" => self.compile_try(args, ast),
+            "binding" => self.compile_binding(args, ast),
+            _ => return false,
+        };
+        true
+    }
+
+    fn compile_def(&mut self, args: &[MalObject], ast: &MalObject, make_macro: bool) {
+        match args {
+            [MalObject::Symbol(_), value] => {
+                self.compile_form(value, false);
+                let index = self.constant(args[0].clone());
+                self.emit(match make_macro {
+                    true => Op::DefineMacro(index),
+                    false => Op::Define(index),
+                });
+            }
+            _ => self.interpret(ast),
+        };
+    }
+
+    fn compile_let(&mut self, args: &[MalObject], ast: &MalObject, tail: bool) {
+        let (bindings, body) = match args {
+            [MalObject::List(_), body] | [MalObject::Vector(_), body] => {
+                (args[0].as_seq().unwrap(), body)
+            }
+            _ => return self.interpret(ast),
+        };
+        if bindings.len() % 2 == 1 || !bindings.iter().step_by(2).all(MalObject::is_symbol) {
+            return self.interpret(ast);
+        }
+        let outer_scope = self.locals.len();
+        self.emit(Op::PushEnv);
+        for (key, value) in bindings.iter().tuples() {
+            self.compile_form(value, false);
+            let index = self.constant(key.clone());
+            self.emit(Op::Define(index));
+            self.emit(Op::Pop);
+            self.locals.push(key.as_symbol().unwrap().clone());
+        }
+        self.compile_form(body, tail);
+        self.locals.truncate(outer_scope);
+        // In tail position the frame, and with it the environment, is about to be discarded.
+        if !tail {
+            self.emit(Op::PopEnv);
+        }
+    }
+
+    fn compile_do(&mut self, args: &[MalObject], ast: &MalObject, tail: bool) {
+        match args.split_last() {
+            None => self.interpret(ast),
+            Some((last, init)) => {
+                for form in init {
+                    self.compile_form(form, false);
+                    self.emit(Op::Pop);
+                }
+                self.compile_form(last, tail);
+            }
+        };
+    }
+
+    fn compile_if(&mut self, args: &[MalObject], ast: &MalObject, tail: bool) {
+        if !Arity::Between(2..=3).contains(args.len()) {
+            return self.interpret(ast);
+        }
+        self.compile_form(&args[0], false);
+        let to_else = self.emit(Op::JumpIfFalse(0));
+        self.compile_form(&args[1], tail);
+        let to_end = self.emit(Op::Jump(0));
+        self.patch_jump(to_else);
+        match args.get(2) {
+            Some(otherwise) => self.compile_form(otherwise, tail),
+            None => {
+                let index = self.constant(MalObject::Nil);
+                self.emit(Op::Constant(index));
+            }
+        };
+        self.patch_jump(to_end);
+    }
+
+    fn compile_fn(&mut self, args: &[MalObject], ast: &MalObject) {
+        let symbols: Option<Vec<MalSymbol>> = match args {
+            [parameters, _body] => parameters
+                .as_seq()
+                .ok()
+                .and_then(|seq| seq.iter().map(|p| p.as_symbol().ok().cloned()).collect()),
+            _ => None,
+        };
+        let (symbols, parameters) = match symbols {
+            Some(symbols) => match ClosureParameters::new(symbols.clone()) {
+                Ok(parameters) => (symbols, parameters),
+                Err(_) => return self.interpret(ast),
+            },
+            None => return self.interpret(ast),
+        };
+        let body = args[1].clone();
+        let chunk = self.compile_nested(&body, &symbols);
+        self.chunk.lambdas.push(Lambda {
+            parameters,
+            body,
+            chunk,
+        });
+        self.emit(Op::MakeClosure(self.chunk.lambdas.len() - 1));
+    }
+
+    fn compile_try(&mut self, args: &[MalObject], ast: &MalObject) {
+        let catch_sym = MalObject::new_symbol("catch*");
+        let catch = match args {
+            [_body] => Some(None),
+            [_body, catch] => match catch.as_list().map(|list| list.payload.as_slice()) {
+                Ok([head, MalObject::Symbol(name), handler]) if *head == catch_sym => {
+                    Some(Some((name.clone(), handler)))
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        let catch = match catch {
+            Some(catch) => catch,
+            None => return self.interpret(ast),
+        };
+        let body = self.compile_nested(&args[0], &[]);
+        let catch = catch.map(|(name, handler)| {
+            let handler = self.compile_nested(handler, std::slice::from_ref(&name));
+            (name, handler)
+        });
+        self.chunk.try_blocks.push(TryBlock { body, catch });
+        self.emit(Op::Try(self.chunk.try_blocks.len() - 1));
+    }
+
+    fn compile_binding(&mut self, args: &[MalObject], ast: &MalObject) {
+        let bindings = match args {
+            [MalObject::List(_), _body] | [MalObject::Vector(_), _body] => {
+                args[0].as_seq().unwrap()
+            }
+            _ => return self.interpret(ast),
+        };
+        let names: Option<Vec<MalSymbol>> = match bindings.len() % 2 {
+            0 => bindings
+                .iter()
+                .step_by(2)
+                .map(|name| name.as_symbol().ok().cloned())
+                .collect(),
+            _ => None,
+        };
+        let names = match names {
+            Some(names) => names,
+            None => return self.interpret(ast),
+        };
+        for value in bindings.iter().skip(1).step_by(2) {
+            self.compile_form(value, false);
+        }
+        let body = self.compile_nested(&args[1], &[]);
+        self.chunk.binding_blocks.push(BindingBlock { names, body });
+        self.emit(Op::Binding(self.chunk.binding_blocks.len() - 1));
+    }
+}
@@ -0,0 +1,177 @@
+// Where `prn`, `println` and `readline` send and take their text. By default
+// that's stdout and the terminal, but embedders and tests can plug in their own
+// handles, and `with-out-str` captures output into a string.
+//
+// Each root environment has a console of its own, so interpreters on the same
+// thread don't share handles. Primitives use the console of the environment
+// being evaluated in.
+
+use crate::environment::{self, Environment};
+use crate::limits;
+use linefeed::{DefaultTerminal, Interface, ReadResult};
+use std::cell::RefCell;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+use std::time::Duration;
+
+/// A source of lines for `readline`.
+pub trait LineSource {
+    /// Show `prompt` and read a line without its terminator. None at end of input.
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>>;
+}
+
+// Created on first use, so that programs which never call `readline` don't
+// touch the terminal.
+struct Terminal(Option<Interface<DefaultTerminal>>);
+
+impl LineSource for Terminal {
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        if self.0.is_none() {
+            self.0 = Some(Interface::new("mal_user")?);
+        }
+        let interface = self.0.as_ref().unwrap();
+        interface.set_prompt(prompt)?;
+        // Wait a little at a time, giving up if the evaluation is interrupted or
+        // times out. The caller finds out which from `limits::check`.
+        loop {
+            if limits::check().is_err() {
+                interface.cancel_read_line()?;
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            match interface.read_line_step(Some(Duration::from_millis(10)))? {
+                Some(ReadResult::Input(line)) => return Ok(Some(line)),
+                Some(ReadResult::Eof) | Some(ReadResult::Signal(_)) => return Ok(None),
+                None => {}
+            }
+        }
+    }
+}
+
+// Reads from any `BufRead`, writing prompts to the current output.
+struct Lines<R>(R);
+
+impl<R: BufRead> LineSource for Lines<R> {
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        write(prompt)?;
+        let mut line = String::new();
+        if self.0.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+}
+
+pub(crate) struct Console {
+    output: Box<dyn Write>,
+    input: Box<dyn LineSource>,
+    // Innermost last.
+    captures: Vec<String>,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Console {
+            output: Box::new(io::stdout()),
+            input: Box::new(Terminal(None)),
+            captures: Vec::new(),
+        }
+    }
+}
+
+thread_local! {
+    // For when mal code runs without having been started in an environment.
+    static DETACHED: Rc<RefCell<Console>> = Rc::default();
+}
+
+fn current() -> Rc<RefCell<Console>> {
+    match environment::active_root() {
+        Some(root) => root.console().clone(),
+        None => DETACHED.with(Rc::clone),
+    }
+}
+
+/// Send everything `prn` and `println` print in `env` to `output`.
+pub fn set_output<W: Write + 'static>(env: &Environment, output: W) {
+    env.console().borrow_mut().output = Box::new(output);
+}
+
+/// Have `readline` in `env` read lines from `input`.
+pub fn set_input<R: BufRead + 'static>(env: &Environment, input: R) {
+    set_line_source(env, Lines(input));
+}
+
+pub fn set_line_source<S: LineSource + 'static>(env: &Environment, source: S) {
+    env.console().borrow_mut().input = Box::new(source);
+}
+
+/// Run `f`, returning whatever it printed to `env`'s console alongside its result.
+///
+/// ```
+/// use rust_dmr_mal::{console, environment::Environment, interpreter};
+/// let env = std::rc::Rc::new(Environment::default());
+/// let (_, out) = console::capture(&env, || interpreter::rep("(prn \"hi\" :there)", &env));
+/// assert_eq!(out, "\"hi\" :there\n");
+/// ```
+pub fn capture<T, F: FnOnce() -> T>(env: &Environment, f: F) -> (T, String) {
+    capture_in(env.console().clone(), f)
+}
+
+// Like `capture`, in the environment being evaluated in.
+pub(crate) fn capture_current<T, F: FnOnce() -> T>(f: F) -> (T, String) {
+    capture_in(current(), f)
+}
+
+fn capture_in<T, F: FnOnce() -> T>(console: Rc<RefCell<Console>>, f: F) -> (T, String) {
+    console.borrow_mut().captures.push(String::new());
+    // Pop our buffer even if `f` panics, so the caller's output goes to the right place.
+    struct Guard(Rc<RefCell<Console>>);
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            self.0.borrow_mut().captures.pop();
+        }
+    }
+    let guard = Guard(console);
+    let result = f();
+    let text = guard.0.borrow_mut().captures.last_mut().map(std::mem::take);
+    (result, text.unwrap_or_default())
+}
+
+pub(crate) fn write(text: &str) -> io::Result<()> {
+    let console = current();
+    let mut console = console.borrow_mut();
+    match console.captures.last_mut() {
+        Some(buffer) => {
+            buffer.push_str(text);
+            Ok(())
+        }
+        None => {
+            console.output.write_all(text.as_bytes())?;
+            console.output.flush()
+        }
+    }
+}
+
+pub(crate) fn read_line(prompt: &str) -> io::Result<Option<String>> {
+    let console = current();
+    // Take the source out while reading: it may write its prompt to the console.
+    let mut input: Box<dyn LineSource> =
+        std::mem::replace(&mut console.borrow_mut().input, Box::new(Eof));
+    let result = input.read_line(prompt);
+    console.borrow_mut().input = input;
+    result
+}
+
+// Stands in for the real input while it's in use.
+struct Eof;
+
+impl LineSource for Eof {
+    fn read_line(&mut self, _prompt: &str) -> io::Result<Option<String>> {
+        Ok(None)
+    }
+}
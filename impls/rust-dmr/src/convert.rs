@@ -0,0 +1,258 @@
+//! Conversions between Rust values and `MalObject`s, for embedding.
+//!
+//! ```
+//! use rust_dmr_mal::convert::{FromMal, IntoMal};
+//! use std::collections::HashMap;
+//!
+//! let obj = vec![(1, "one"), (2, "two")].into_mal();
+//! assert_eq!(obj.to_string(), r#"[[1 "one"] [2 "two"]]"#);
+//! let back: Vec<(i32, String)> = FromMal::from_mal(&obj).unwrap();
+//! assert_eq!(back[1], (2, "two".to_string()));
+//!
+//! let mut scores = HashMap::new();
+//! scores.insert("alice".to_string(), Some(3u8));
+//! let round_trip: HashMap<String, Option<u8>> = FromMal::from_mal(&scores.clone().into_mal()).unwrap();
+//! assert_eq!(round_trip, scores);
+//! ```
+
+use crate::evaluator;
+use crate::types::{HashKey, MalInt, MalObject, TypeMismatch};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::Hash;
+
+pub trait IntoMal {
+    fn into_mal(self) -> MalObject;
+}
+
+pub trait FromMal: Sized {
+    fn from_mal(obj: &MalObject) -> Result<Self, TypeMismatch>;
+}
+
+/// A Rust stand-in for a mal keyword, e.g. for use as a map key.
+///
+/// ```
+/// use rust_dmr_mal::convert::{IntoMal, Keyword};
+/// assert_eq!(Keyword("port".into()).into_mal().to_string(), ":port");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Keyword(pub String);
+
+impl IntoMal for MalObject {
+    fn into_mal(self) -> MalObject {
+        self
+    }
+}
+
+impl FromMal for MalObject {
+    fn from_mal(obj: &MalObject) -> Result<Self, TypeMismatch> {
+        Ok(obj.clone())
+    }
+}
+
+impl IntoMal for () {
+    fn into_mal(self) -> MalObject {
+        MalObject::Nil
+    }
+}
+
+impl IntoMal for bool {
+    fn into_mal(self) -> MalObject {
+        MalObject::Bool(self)
+    }
+}
+
+impl FromMal for bool {
+    fn from_mal(obj: &MalObject) -> Result<Self, TypeMismatch> {
+        obj.as_bool()
+    }
+}
+
+macro_rules! integer_conversions {
+    ($($t:ty),*) => {
+        $(
+            impl IntoMal for $t {
+                // Saturates if out of range, e.g. for a u64 above MalInt::MAX,
+                // rather than wrapping around to a negative number.
+                fn into_mal(self) -> MalObject {
+                    let saturated = if self > 0 { MalInt::MAX } else { MalInt::MIN };
+                    MalObject::Integer(MalInt::try_from(self).unwrap_or(saturated))
+                }
+            }
+
+            impl FromMal for $t {
+                fn from_mal(obj: &MalObject) -> Result<Self, TypeMismatch> {
+                    <$t>::try_from(obj.as_int()?).map_err(|_| TypeMismatch::OutOfRange)
+                }
+            }
+        )*
+    };
+}
+
+integer_conversions!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl IntoMal for String {
+    fn into_mal(self) -> MalObject {
+        MalObject::String(self)
+    }
+}
+
+impl IntoMal for &str {
+    fn into_mal(self) -> MalObject {
+        MalObject::String(self.into())
+    }
+}
+
+impl FromMal for String {
+    fn from_mal(obj: &MalObject) -> Result<Self, TypeMismatch> {
+        obj.as_string().map(String::from)
+    }
+}
+
+impl IntoMal for char {
+    fn into_mal(self) -> MalObject {
+        MalObject::Char(self)
+    }
+}
+
+impl FromMal for char {
+    fn from_mal(obj: &MalObject) -> Result<Self, TypeMismatch> {
+        obj.as_char()
+    }
+}
+
+impl IntoMal for Keyword {
+    fn into_mal(self) -> MalObject {
+        MalObject::Keyword(self.0)
+    }
+}
+
+impl FromMal for Keyword {
+    fn from_mal(obj: &MalObject) -> Result<Self, TypeMismatch> {
+        match obj {
+            MalObject::Keyword(k) => Ok(Keyword(k.clone())),
+            _ => Err(TypeMismatch::NotIntoKeyword),
+        }
+    }
+}
+
+impl<T: IntoMal> IntoMal for Option<T> {
+    fn into_mal(self) -> MalObject {
+        match self {
+            Some(x) => x.into_mal(),
+            None => MalObject::Nil,
+        }
+    }
+}
+
+impl<T: FromMal> FromMal for Option<T> {
+    fn from_mal(obj: &MalObject) -> Result<Self, TypeMismatch> {
+        match obj {
+            MalObject::Nil => Ok(None),
+            _ => T::from_mal(obj).map(Some),
+        }
+    }
+}
+
+impl<T: IntoMal> IntoMal for Vec<T> {
+    fn into_mal(self) -> MalObject {
+        MalObject::wrap_vector(self.into_iter().map(IntoMal::into_mal).collect())
+    }
+}
+
+// Accepts lists, vectors and lazy sequences alike.
+impl<T: FromMal> FromMal for Vec<T> {
+    fn from_mal(obj: &MalObject) -> Result<Self, TypeMismatch> {
+        elements(obj)?.iter().map(T::from_mal).collect()
+    }
+}
+
+// The elements of a sequence, realising it if it's lazy. Realising can fail
+// in any way, but a conversion can only report that it wasn't a sequence.
+fn elements(obj: &MalObject) -> Result<Cow<'_, [MalObject]>, TypeMismatch> {
+    obj.to_seq().map_err(|e| match e {
+        evaluator::Error::TypeMismatch(e) => e,
+        _ => TypeMismatch::NotASequence,
+    })
+}
+
+/// Types which can be the keys of a mal map.
+pub trait MapKey: Sized {
+    fn into_key(self) -> HashKey;
+    fn from_key(key: &HashKey) -> Result<Self, TypeMismatch>;
+}
+
+// Strings are converted to and from string keys, but will also accept keywords.
+impl MapKey for String {
+    fn into_key(self) -> HashKey {
+        HashKey::String(self)
+    }
+
+    fn from_key(key: &HashKey) -> Result<Self, TypeMismatch> {
+        match key {
+            HashKey::String(s) | HashKey::Keyword(s) => Ok(s.clone()),
+        }
+    }
+}
+
+impl MapKey for Keyword {
+    fn into_key(self) -> HashKey {
+        HashKey::Keyword(self.0)
+    }
+
+    fn from_key(key: &HashKey) -> Result<Self, TypeMismatch> {
+        match key {
+            HashKey::Keyword(s) => Ok(Keyword(s.clone())),
+            HashKey::String(_) => Err(TypeMismatch::NotIntoKeyword),
+        }
+    }
+}
+
+impl<K: MapKey, V: IntoMal> IntoMal for HashMap<K, V> {
+    fn into_mal(self) -> MalObject {
+        MalObject::wrap_map(
+            self.into_iter()
+                .map(|(k, v)| (k.into_key(), v.into_mal()))
+                .collect(),
+        )
+    }
+}
+
+impl<K: MapKey + Eq + Hash, V: FromMal> FromMal for HashMap<K, V> {
+    fn from_mal(obj: &MalObject) -> Result<Self, TypeMismatch> {
+        obj.as_map()?
+            .iter()
+            .map(|(k, v)| Ok((K::from_key(k)?, V::from_mal(v)?)))
+            .collect()
+    }
+}
+
+// Tuples are converted to and from vectors of the same length.
+macro_rules! tuple_conversions {
+    ($len:expr => $($t:ident $index:tt),*) => {
+        impl<$($t: IntoMal),*> IntoMal for ($($t,)*) {
+            fn into_mal(self) -> MalObject {
+                MalObject::wrap_vector(vec![$(self.$index.into_mal()),*])
+            }
+        }
+
+        impl<$($t: FromMal),*> FromMal for ($($t,)*) {
+            fn from_mal(obj: &MalObject) -> Result<Self, TypeMismatch> {
+                let seq = elements(obj)?;
+                if seq.len() != $len {
+                    return Err(TypeMismatch::WrongLength {
+                        expected: $len,
+                        got: seq.len(),
+                    });
+                }
+                Ok(($($t::from_mal(&seq[$index])?,)*))
+            }
+        }
+    };
+}
+
+tuple_conversions!(1 => A 0);
+tuple_conversions!(2 => A 0, B 1);
+tuple_conversions!(3 => A 0, B 1, C 2);
+tuple_conversions!(4 => A 0, B 1, C 2, D 3);
@@ -1,15 +1,17 @@
+use crate::environment::Capability;
 use crate::types::{
     callable, Arity, Atom, HashKey, MalInt, MalObject, MapError, PrimitiveFn, TypeMismatch,
 };
-use crate::{environment, evaluator, printer, reader, types};
+use crate::{
+    console, edn, environment, evaluator, fs, gc, json, lazy, limits, printer, process, random,
+    reader, reducers, sequences, text, time, types,
+};
 use itertools::Itertools;
-use linefeed::{DefaultTerminal, Interface, ReadResult};
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::fs::read_to_string;
 use std::ops::Deref;
 use std::rc::Rc;
-use std::time::SystemTime;
 
 fn grab_ints(args: &[MalObject]) -> evaluator::Result<Vec<MalInt>> {
     let type_check: Result<Vec<_>, _> = args.iter().map(|o| o.as_int()).collect();
@@ -152,6 +154,9 @@ const EMPTY_TEST: PrimitiveFn = PrimitiveFn {
 };
 
 fn empty_test_(args: &[MalObject]) -> evaluator::Result {
+    if let MalObject::LazySeq(seq) = &args[0] {
+        return Ok(MalObject::Bool(seq.realise()?.is_none()));
+    }
     args[0]
         .as_seq()
         .map(|slice| slice.is_empty())
@@ -170,6 +175,7 @@ fn count_(args: &[MalObject]) -> evaluator::Result {
         MalObject::List(list) => Ok(list.payload.len() as MalInt),
         MalObject::Vector(vec) => Ok(vec.payload.len() as MalInt),
         MalObject::Nil => Ok(0 as MalInt),
+        MalObject::LazySeq(_) => Ok(lazy::realise_all(&args[0])?.len() as MalInt),
         _ => Err(evaluator::Error::TypeMismatch(
             // TODO better error here!
             types::TypeMismatch::NotASequence,
@@ -185,6 +191,12 @@ const EQUAL: PrimitiveFn = PrimitiveFn {
 };
 
 fn equal(args: &[MalObject]) -> evaluator::Result {
+    // Lazy sequences are only equal to anything once they're realised.
+    for arg in args {
+        if lazy::is_lazy(arg) {
+            lazy::realise_all(arg)?;
+        }
+    }
     Ok(MalObject::Bool(args[0] == args[1]))
 }
 
@@ -194,10 +206,13 @@ fn print_string_internal(
     sep: &'static str,
     to_screen: bool,
 ) -> evaluator::Result {
+    for arg in args {
+        printer::realise(arg)?;
+    }
     let text = args.iter().map(|arg| printer::pr_str(arg, mode)).join(sep);
     if to_screen {
-        // TODO bypassing the "interface" in cmdline.rs. Maybe that's fine?
-        println!("{}", text);
+        console::write(&text)?;
+        console::write("\n")?;
         Ok(MalObject::Nil)
     } else {
         Ok(MalObject::String(text))
@@ -263,11 +278,96 @@ fn slurp_(args: &[MalObject]) -> evaluator::Result {
     let s = args[0]
         .as_string()
         .map_err(evaluator::Error::TypeMismatch)?;
-    read_to_string(s)
-        .map_err(evaluator::Error::IOError)
+    fs::attempt("read", s.as_ref(), read_to_string(s)).map(MalObject::String)
+}
+
+// Look up a boolean flag in an optional map of keyword options.
+pub(crate) fn flag(options: Option<&MalObject>, name: &str) -> evaluator::Result<bool> {
+    match options {
+        None | Some(MalObject::Nil) => Ok(false),
+        Some(options) => match options.as_map()?.get(&HashKey::Keyword(name.into())) {
+            Some(value) => Ok(types::truthy(value)),
+            None => Ok(false),
+        },
+    }
+}
+
+const JSON_PARSE: PrimitiveFn = PrimitiveFn {
+    name: "json-parse",
+    fn_ptr: json_parse,
+    arity: Arity::Between(1..=2),
+};
+
+fn json_parse(args: &[MalObject]) -> evaluator::Result {
+    let keys = match flag(args.get(1), "keywordize-keys")? {
+        true => json::Keys::Keywords,
+        false => json::Keys::Strings,
+    };
+    json::parse(args[0].as_string()?, keys).map_err(evaluator::Error::Json)
+}
+
+const JSON_STRINGIFY: PrimitiveFn = PrimitiveFn {
+    name: "json-stringify",
+    fn_ptr: json_stringify,
+    arity: Arity::Between(1..=2),
+};
+
+fn json_stringify(args: &[MalObject]) -> evaluator::Result {
+    let layout = match flag(args.get(1), "pretty")? {
+        true => json::Layout::Pretty,
+        false => json::Layout::Compact,
+    };
+    lazy::realise_deeply(&args[0], None)?;
+    json::stringify(&args[0], layout)
         .map(MalObject::String)
+        .map_err(evaluator::Error::Json)
+}
+
+const READ_EDN: PrimitiveFn = PrimitiveFn {
+    name: "read-edn",
+    fn_ptr: read_edn,
+    arity: Arity::Between(1..=2),
+};
+
+// Options are a map which may contain
+//   :readers, a map from tag names to functions of the tagged value,
+//   :default, a function of the tag (as a symbol) and value for other tags.
+fn read_edn(args: &[MalObject]) -> evaluator::Result {
+    let mut readers = edn::Readers::default();
+    if let Some(options) = args.get(1).filter(|options| !options.is_nil()) {
+        let options = options.as_map()?;
+        if let Some(tags) = options.get(&HashKey::Keyword("readers".into())) {
+            for (tag, func) in tags.as_map()?.iter() {
+                let tag = match tag {
+                    HashKey::String(s) | HashKey::Keyword(s) => s.clone(),
+                };
+                let func = func.clone();
+                let handler = move |value| evaluator::apply_fully(&func, &[value]);
+                readers.tags.insert(tag, Rc::new(handler));
+            }
+        }
+        if let Some(func) = options.get(&HashKey::Keyword("default".into())) {
+            let func = func.clone();
+            let handler = move |tag: &str, value| {
+                evaluator::apply_fully(&func, &[MalObject::new_symbol(tag), value])
+            };
+            readers.default = Some(Rc::new(handler));
+        }
+    }
+    edn::read_str(args[0].as_string()?, &readers)
 }
 
+const WRITE_EDN: PrimitiveFn = PrimitiveFn {
+    name: "write-edn",
+    fn_ptr: |args| {
+        lazy::realise_deeply(&args[0], None)?;
+        edn::write_str(&args[0])
+            .map(MalObject::String)
+            .map_err(evaluator::Error::Edn)
+    },
+    arity: Arity::exactly(1),
+};
+
 const ATOM: PrimitiveFn = PrimitiveFn {
     name: "atom",
     fn_ptr: atom_,
@@ -295,6 +395,9 @@ const DEREF: PrimitiveFn = PrimitiveFn {
 };
 
 fn deref_(args: &[MalObject]) -> evaluator::Result {
+    if let MalObject::Reduced(x) = &args[0] {
+        return Ok((**x).clone());
+    }
     args[0]
         .as_atom()
         .map_err(evaluator::Error::TypeMismatch)
@@ -346,6 +449,9 @@ const CONS: PrimitiveFn = PrimitiveFn {
 };
 
 fn cons_(args: &[MalObject]) -> evaluator::Result {
+    if lazy::is_lazy(&args[1]) {
+        return Ok(lazy::cons(args[0].clone(), args[1].clone()));
+    }
     let head = &args[0];
     let tail = args[1].as_seq().map_err(evaluator::Error::TypeMismatch)?;
 
@@ -362,6 +468,10 @@ const CONCAT: PrimitiveFn = PrimitiveFn {
 };
 
 fn concat_(args: &[MalObject]) -> evaluator::Result {
+    if args.iter().any(lazy::is_lazy) {
+        let parts = lazy::Cursor::new(&MalObject::wrap_list(args.to_vec()))?;
+        return Ok(sequences::lazy_concat(parts));
+    }
     let mut output = Vec::new();
     let mut extend = |obj: &MalObject| {
         obj.as_seq()
@@ -382,6 +492,9 @@ const NTH: PrimitiveFn = PrimitiveFn {
 };
 
 fn nth_(args: &[MalObject]) -> evaluator::Result {
+    if lazy::is_lazy(&args[0]) {
+        return lazy_nth(&args[0], args[1].as_int()?);
+    }
     let seq = args[0].as_seq().map_err(evaluator::Error::TypeMismatch)?;
     let orig_index = args[1].as_int().map_err(evaluator::Error::TypeMismatch)?;
     nth_internal(seq, orig_index)
@@ -397,6 +510,21 @@ fn nth_internal(seq: &[MalObject], orig_index: isize) -> evaluator::Result {
     value.ok_or_else(|| evaluator::Error::BadIndex(orig_index, 0..seq.len()))
 }
 
+// Realises only as far as the element it returns.
+fn lazy_nth(seq: &MalObject, orig_index: isize) -> evaluator::Result {
+    let mut cursor = lazy::Cursor::new(seq)?;
+    let mut seen = 0;
+    if orig_index >= 0 {
+        while let Some(x) = cursor.next()? {
+            if seen == orig_index as usize {
+                return Ok(x);
+            }
+            seen += 1;
+        }
+    }
+    Err(evaluator::Error::BadIndex(orig_index, 0..seen))
+}
+
 const FIRST: PrimitiveFn = PrimitiveFn {
     name: "first",
     fn_ptr: first_,
@@ -407,6 +535,9 @@ fn first_(args: &[MalObject]) -> evaluator::Result {
     if args[0].is_nil() {
         return Ok(MalObject::Nil);
     }
+    if let MalObject::LazySeq(seq) = &args[0] {
+        return Ok(seq.realise()?.map_or(MalObject::Nil, |(first, _)| first));
+    }
     let seq = args[0].as_seq().map_err(evaluator::Error::TypeMismatch)?;
     match seq.is_empty() {
         true => Ok(MalObject::Nil),
@@ -424,6 +555,11 @@ fn rest_(args: &[MalObject]) -> evaluator::Result {
     if args[0].is_nil() {
         return Ok(MalObject::new_list());
     }
+    if let MalObject::LazySeq(seq) = &args[0] {
+        return Ok(seq
+            .realise()?
+            .map_or(MalObject::new_list(), |(_, rest)| rest));
+    }
     let seq = args[0].as_seq().map_err(evaluator::Error::TypeMismatch)?;
     if seq.is_empty() {
         return Ok(MalObject::new_list());
@@ -526,25 +662,13 @@ const APPLY: PrimitiveFn = PrimitiveFn {
 };
 fn apply_(args: &[MalObject]) -> evaluator::Result {
     let mut concatenated = args[1..args.len() - 1].to_vec();
-    let last = args[args.len() - 1].as_seq()?;
-    concatenated.extend_from_slice(last);
+    match &args[args.len() - 1] {
+        last @ MalObject::LazySeq(_) => concatenated.extend(lazy::realise_all(last)?),
+        last => concatenated.extend_from_slice(last.as_seq()?),
+    }
     evaluator::apply_fully(&args[0], &concatenated)
 }
 
-const MAP: PrimitiveFn = PrimitiveFn {
-    name: "map",
-    fn_ptr: map_,
-    arity: Arity::exactly(2),
-};
-fn map_(args: &[MalObject]) -> evaluator::Result {
-    let result: Result<Vec<_>, _> = args[1]
-        .as_seq()?
-        .chunks_exact(1)
-        .map(|obj| evaluator::apply_fully(&args[0], obj))
-        .collect();
-    Ok(MalObject::wrap_list(result?))
-}
-
 const SEQ: PrimitiveFn = PrimitiveFn {
     name: "seq",
     fn_ptr: seq_,
@@ -561,6 +685,7 @@ fn seq_(args: &[MalObject]) -> evaluator::Result {
 
     match &args[0] {
         Nil => Ok(Nil),
+        // Mal's own tests expect one-character strings rather than chars here.
         String(s) => Ok(MalObject::wrap_list(
             s.chars()
                 .map(|substr| MalObject::String(substr.to_string()))
@@ -568,6 +693,10 @@ fn seq_(args: &[MalObject]) -> evaluator::Result {
         )),
         List(_) => Ok(args[0].clone()),
         Vector(x) => Ok(MalObject::wrap_list(x.payload.clone())),
+        LazySeq(seq) => match seq.realise()? {
+            Some(_) => Ok(args[0].clone()),
+            None => Ok(Nil),
+        },
         _ => Err(evaluator::Error::TypeMismatch(TypeMismatch::NotASequence)),
     }
 }
@@ -575,9 +704,21 @@ fn seq_(args: &[MalObject]) -> evaluator::Result {
 const CONJ: PrimitiveFn = PrimitiveFn {
     name: "conj",
     fn_ptr: conj_,
-    arity: Arity::at_least(2),
+    arity: Arity::at_least(0),
 };
+// (conj) and (conj coll) are there for `transduce`, which calls its reducing
+// function with no arguments to start and with just the result to finish.
 fn conj_(args: &[MalObject]) -> evaluator::Result {
+    match args {
+        [] => return Ok(MalObject::wrap_vector(Vec::new())),
+        [coll] => return Ok(coll.clone()),
+        [MalObject::Nil, ..] => return conj_(&[&[MalObject::new_list()], &args[1..]].concat()),
+        _ => {}
+    }
+    if lazy::is_lazy(&args[0]) {
+        let prepend = |seq, x: &MalObject| lazy::cons(x.clone(), seq);
+        return Ok(args[1..].iter().fold(args[0].clone(), prepend));
+    }
     let old = args[0].as_seq()?;
     let new = &args[1..];
     match &args[0] {
@@ -713,6 +854,44 @@ fn number_test(args: &[MalObject]) -> evaluator::Result {
     Ok(MalObject::Bool(args[0].is_number()))
 }
 
+const CHAR_TEST: PrimitiveFn = PrimitiveFn {
+    name: "char?",
+    fn_ptr: |args| Ok(MalObject::Bool(args[0].as_char().is_ok())),
+    arity: Arity::exactly(1),
+};
+
+const CHAR: PrimitiveFn = PrimitiveFn {
+    name: "char",
+    fn_ptr: char_,
+    arity: Arity::exactly(1),
+};
+
+// The character with a given code point.
+fn char_(args: &[MalObject]) -> evaluator::Result {
+    match &args[0] {
+        MalObject::Char(_) => Ok(args[0].clone()),
+        obj => u32::try_from(obj.as_int()?)
+            .ok()
+            .and_then(std::char::from_u32)
+            .map(MalObject::Char)
+            .ok_or(evaluator::Error::TypeMismatch(TypeMismatch::OutOfRange)),
+    }
+}
+
+const INT: PrimitiveFn = PrimitiveFn {
+    name: "int",
+    fn_ptr: int_,
+    arity: Arity::exactly(1),
+};
+
+// The code point of a character.
+fn int_(args: &[MalObject]) -> evaluator::Result {
+    match &args[0] {
+        MalObject::Char(c) => Ok(MalObject::Integer(*c as MalInt)),
+        obj => Ok(MalObject::Integer(obj.as_int()?)),
+    }
+}
+
 const FUNCTION_TEST: PrimitiveFn = PrimitiveFn {
     name: "fn?",
     fn_ptr: function_test,
@@ -738,29 +917,63 @@ const READLINE: PrimitiveFn = PrimitiveFn {
 };
 fn readline_(args: &[MalObject]) -> evaluator::Result {
     let prompt = args[0].as_string()?;
-    lazy_static! {
-        pub static ref INTERFACE: Interface<DefaultTerminal> =
-            linefeed::Interface::new("mal_user").unwrap();
-    }
-    INTERFACE.set_prompt(prompt)?;
-    match INTERFACE.read_line() {
-        Ok(ReadResult::Eof) => Ok(MalObject::Nil),
-        Ok(ReadResult::Signal(_)) => Ok(MalObject::Nil),
-        Ok(ReadResult::Input(i)) => Ok(MalObject::String(i)),
-        Err(e) => Err(e.into()),
+    limits::check()?;
+    match console::read_line(prompt) {
+        Ok(Some(line)) => Ok(MalObject::String(line)),
+        Ok(None) => Ok(MalObject::Nil),
+        // The terminal stops waiting once the evaluation is interrupted.
+        Err(e) => {
+            limits::check()?;
+            Err(e.into())
+        }
     }
 }
 
-const TIME_MS: PrimitiveFn = PrimitiveFn {
-    name: "time-ms",
-    fn_ptr: time_ms_,
+const _WITH_OUT_STR: PrimitiveFn = PrimitiveFn {
+    name: "-with-out-str",
+    fn_ptr: _with_out_str,
+    arity: Arity::exactly(1),
+};
+// Backs the `with-out-str` macro: call a thunk, returning what it printed.
+fn _with_out_str(args: &[MalObject]) -> evaluator::Result {
+    let (result, text) = console::capture_current(|| evaluator::apply_fully(&args[0], &[]));
+    result.map(|_| MalObject::String(text))
+}
+
+const GC: PrimitiveFn = PrimitiveFn {
+    name: "gc",
+    fn_ptr: gc_,
+    arity: Arity::exactly(0),
+};
+fn gc_(_args: &[MalObject]) -> evaluator::Result {
+    Ok(gc_stats_map(gc::collect()))
+}
+
+const GC_STATS: PrimitiveFn = PrimitiveFn {
+    name: "gc-stats",
+    fn_ptr: gc_stats_,
     arity: Arity::exactly(0),
 };
-fn time_ms_(_args: &[MalObject]) -> evaluator::Result {
-    let duration = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap(); // TODO really ought not to hide this!
-    Ok(MalObject::Integer(duration.as_millis() as MalInt))
+fn gc_stats_(_args: &[MalObject]) -> evaluator::Result {
+    Ok(gc_stats_map(gc::stats()))
+}
+
+fn gc_stats_map(stats: gc::Stats) -> MalObject {
+    let mut map = HashMap::new();
+    for (key, value) in [
+        ("collections", stats.collections),
+        ("tracked", stats.tracked),
+        ("environments-freed", stats.environments_freed),
+        ("atoms-freed", stats.atoms_freed),
+    ]
+    .iter()
+    {
+        map.insert(
+            HashKey::Keyword(key.to_string()),
+            MalObject::Integer(*value as MalInt),
+        );
+    }
+    MalObject::wrap_map(map)
 }
 
 const META: PrimitiveFn = PrimitiveFn {
@@ -849,10 +1062,15 @@ fn _rust_log_level(args: &[MalObject]) -> evaluator::Result {
 }
 
 type Namespace = HashMap<&'static str, &'static PrimitiveFn>;
+
+fn namespace(funcs: &'static [PrimitiveFn]) -> Namespace {
+    funcs.iter().map(|func| (func.name, func)).collect()
+}
+
 lazy_static! {
-    pub static ref CORE: Namespace = {
-        let mut map = Namespace::new();
-        for func in [
+    pub static ref CORE: HashMap<Capability, Namespace> = {
+        let mut core = HashMap::new();
+        core.insert(Capability::Pure, namespace(&[
             // Arithmetic
             SUM,
             SUB,
@@ -866,10 +1084,29 @@ lazy_static! {
             // Working with strings
             PR_STR,
             STR,
-            PRN,
-            PRINTLN,
             READ_STRING,
-            SLURP,
+            text::SUBS,
+            text::SPLIT,
+            text::JOIN,
+            text::TRIM,
+            text::UPPER_CASE,
+            text::LOWER_CASE,
+            text::STARTS_WITH,
+            text::ENDS_WITH,
+            text::INDEX_OF,
+            text::REPLACE,
+            text::REVERSE,
+            text::FORMAT,
+            // Paths
+            fs::PATH_JOIN,
+            fs::BASENAME,
+            fs::DIRNAME,
+            // Regexes
+            text::RE_PATTERN,
+            text::RE_FIND,
+            text::RE_MATCHES,
+            text::RE_SEQ,
+            text::RE_GROUPS,
             // Working with lists
             CONS,
             CONCAT,
@@ -877,9 +1114,43 @@ lazy_static! {
             FIRST,
             REST,
             APPLY,
-            MAP,
             SEQ,
             CONJ,
+            // Higher-order sequence functions
+            sequences::MAP,
+            sequences::MAPCAT,
+            sequences::FILTER,
+            sequences::REMOVE,
+            sequences::TAKE,
+            sequences::DROP,
+            sequences::TAKE_WHILE,
+            sequences::DROP_WHILE,
+            sequences::SOME,
+            sequences::EVERY_TEST,
+            sequences::RANGE,
+            sequences::REVERSE,
+            sequences::LAST,
+            sequences::SORT,
+            sequences::SORT_BY,
+            sequences::GROUP_BY,
+            sequences::FREQUENCIES,
+            sequences::PARTITION,
+            sequences::INTERLEAVE,
+            sequences::ZIPMAP,
+            // Reducers and transducers
+            reducers::REDUCE,
+            reducers::REDUCED,
+            reducers::REDUCED_TEST,
+            reducers::UNREDUCED,
+            reducers::TRANSDUCE,
+            reducers::INTO,
+            reducers::COMP,
+            // Lazy sequences
+            sequences::ITERATE,
+            sequences::CYCLE,
+            sequences::REPEAT,
+            lazy::DOALL,
+            lazy::REALIZED_TEST,
             // Working with maps
             HASH_MAP,
             ASSOC,
@@ -915,19 +1186,82 @@ lazy_static! {
             MACRO_TEST,
             STRING_TEST,
             NUMBER_TEST,
+            CHAR_TEST,
+            CHAR,
+            INT,
             // Metadata
             META,
             WITH_META,
             // Exceptions
             THROW,
-            // Other
+            // Data interchange
+            JSON_PARSE,
+            JSON_STRINGIFY,
+            READ_EDN,
+            WRITE_EDN,
+        ]));
+        core.insert(Capability::IoRead, namespace(&[
+            SLURP,
+            fs::READ_LINES,
+            fs::FILE_EXISTS_TEST,
+            fs::DIR_TEST,
+            fs::LIST_DIR,
+            fs::FILE_SIZE,
+            fs::ABS_PATH,
+        ]));
+        core.insert(Capability::IoWrite, namespace(&[
+            fs::SPIT,
+            fs::MKDIR,
+            fs::DELETE_FILE,
+            fs::RENAME_FILE,
+        ]));
+        core.insert(Capability::Console, namespace(&[
+            PRN,
+            PRINTLN,
             READLINE,
-            TIME_MS,
+            _WITH_OUT_STR,
+        ]));
+        core.insert(Capability::Time, namespace(&[
+            time::TIME_MS,
+            time::NANO_TIME,
+            time::SLEEP,
+            time::NOW,
+            time::FREEZE_CLOCK,
+            time::INSTANT_TEST,
+            time::INSTANT,
+            time::INST_MS,
+            time::FORMAT_INST,
+            time::TO_UTC,
+            time::TO_LOCAL,
+            time::INST_PLUS,
+            time::INST_DIFF,
+            time::INST_FIELDS,
+        ]));
+        core.insert(Capability::Random, namespace(&[
+            random::RAND,
+            random::RAND_INT,
+            random::RAND_NTH,
+            random::SHUFFLE,
+            random::RANDOM_SEED,
+        ]));
+        core.insert(Capability::Process, namespace(&[
+            process::SH,
+            process::SPAWN,
+            process::PROC_READ_LINE,
+            process::PROC_WAIT,
+            process::PROC_KILL,
+            process::GETENV,
+            process::SETENV,
+            process::CWD,
+            process::CHDIR,
+            process::EXIT,
+        ]));
+        core.insert(Capability::Debug, namespace(&[
+            GC,
+            GC_STATS,
             // Naughty!
             _RUST_LOG_LEVEL,
-        ].iter() {
-            map.insert(func.name, func);
-        }
-        map
+        ]));
+        core
     };
 }
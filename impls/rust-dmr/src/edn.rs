@@ -0,0 +1,360 @@
+// Reading and writing EDN (https://github.com/edn-format/edn), so that mal can
+// exchange data with Clojure tooling.
+//
+// EDN mode never evaluates anything, and rejects mal's reader macros (quote,
+// deref, metadata, ...) rather than expanding them. On top of mal's syntax it
+// understands `#{}` sets, `#_` discards, `#:ns{}` namespaced maps, character
+// literals and tagged literals.
+//
+// Mal has no set type, so a set is read as a vector of its distinct elements,
+// marked as a set in its metadata so that it's written back out as a set.
+//
+// Tagged literals `#tag form` are passed to a handler for `tag`. Handlers for
+// `#inst` and `#uuid` are built in: `#inst` reads a timestamp string as an
+// instant, and `#uuid` checks the form is a UUID string and returns it unchanged.
+
+use crate::reader::{self, read_plain_chars};
+use crate::tokens::{tokenize, Close, Open, Token};
+use crate::types::{build_string, HashKey, MalMap, MalObject, MalVector};
+use crate::{evaluator, lazy, printer, strings, time};
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::iter::Peekable;
+use std::rc::Rc;
+use std::slice;
+
+#[derive(Debug)]
+pub enum Error {
+    Read(reader::Error),
+    ReaderMacro,
+    Unsupported(String),
+    BadCharacter(String),
+    BadMapKey(String),
+    DuplicateSetElement(String),
+    UnknownTag(String),
+    InvalidTagged(String, String),
+    CantWrite(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read(e) => write!(f, "{}", e),
+            Error::ReaderMacro => write!(f, "reader macros aren't allowed in EDN"),
+            Error::Unsupported(s) => write!(f, "can't represent {} in mal", s),
+            Error::BadCharacter(s) => write!(f, "unknown character literal \\{}", s),
+            Error::BadMapKey(s) => write!(f, "map keys must be strings or keywords, not {}", s),
+            Error::DuplicateSetElement(s) => write!(f, "duplicate set element {}", s),
+            Error::UnknownTag(tag) => write!(f, "no reader function for tag #{}", tag),
+            Error::InvalidTagged(tag, s) => write!(f, "invalid #{} literal {}", tag, s),
+            Error::CantWrite(s) => write!(f, "can't write {} as EDN", s),
+        }
+    }
+}
+
+impl From<Error> for evaluator::Error {
+    fn from(e: Error) -> Self {
+        evaluator::Error::Edn(e)
+    }
+}
+
+pub type TagHandler = Rc<dyn Fn(MalObject) -> evaluator::Result>;
+pub type DefaultTagHandler = Rc<dyn Fn(&str, MalObject) -> evaluator::Result>;
+
+/// Tag handlers to use for one read, on top of the registered ones.
+#[derive(Clone, Default)]
+pub struct Readers {
+    pub tags: HashMap<String, TagHandler>,
+    /// Called for tags with no handler. Without one, they're an error.
+    pub default: Option<DefaultTagHandler>,
+}
+
+thread_local! {
+    static TAGS: RefCell<HashMap<String, TagHandler>> = RefCell::new(builtin_tags());
+}
+
+fn builtin_tags() -> HashMap<String, TagHandler> {
+    lazy_static! {
+        static ref UUID: Regex = Regex::new(
+            r"^[[:xdigit:]]{8}-[[:xdigit:]]{4}-[[:xdigit:]]{4}-[[:xdigit:]]{4}-[[:xdigit:]]{12}$"
+        )
+        .unwrap();
+    }
+    fn checked(tag: &'static str, re: &'static Regex) -> TagHandler {
+        Rc::new(move |obj| match &obj {
+            MalObject::String(s) if re.is_match(s) => Ok(obj),
+            _ => Err(Error::InvalidTagged(tag.into(), obj.to_string()).into()),
+        })
+    }
+    let inst: TagHandler = Rc::new(|obj| match &obj {
+        MalObject::String(s) => time::parse_iso_8601(s)
+            .map(MalObject::Instant)
+            .map_err(|_| Error::InvalidTagged("inst".into(), obj.to_string()).into()),
+        _ => Err(Error::InvalidTagged("inst".into(), obj.to_string()).into()),
+    });
+    let mut tags = HashMap::new();
+    tags.insert("inst".to_string(), inst);
+    tags.insert("uuid".to_string(), checked("uuid", &UUID));
+    tags
+}
+
+/// Make `#tag` literals on this thread be read by `handler`.
+pub fn register_tag<F>(tag: &str, handler: F)
+where
+    F: Fn(MalObject) -> evaluator::Result + 'static,
+{
+    TAGS.with(|tags| tags.borrow_mut().insert(tag.into(), Rc::new(handler)));
+}
+
+/// Read the first form in `input`, or nil if there isn't one.
+pub fn read_str(input: &str, readers: &Readers) -> evaluator::Result {
+    let tokens = tokenize(input).map_err(|e| Error::Read(reader::Error::TokenizerError(e)))?;
+    let mut reader = EdnReader {
+        tokens: tokens.iter().peekable(),
+        readers,
+    };
+    match reader.read_form() {
+        Err(evaluator::Error::Edn(Error::Read(reader::Error::NoMoreTokens))) => Ok(MalObject::Nil),
+        result => result,
+    }
+}
+
+struct EdnReader<'a> {
+    tokens: Peekable<slice::Iter<'a, Token<'a>>>,
+    readers: &'a Readers,
+}
+
+fn read_error(e: reader::Error) -> evaluator::Error {
+    Error::Read(e).into()
+}
+
+impl<'a> EdnReader<'a> {
+    fn read_form(&mut self) -> evaluator::Result {
+        loop {
+            if let Some(obj) = self.read_optional_form()? {
+                return Ok(obj);
+            }
+        }
+    }
+
+    // Returns None if the token(s) read turned out to be discarded or a comment.
+    fn read_optional_form(&mut self) -> evaluator::Result<Option<MalObject>> {
+        let token = self
+            .tokens
+            .next()
+            .ok_or_else(|| read_error(reader::Error::NoMoreTokens))?;
+        match token {
+            Token::Open(Open::List) => self.read_sequence(Close::List).map(MalObject::wrap_list),
+            Token::Open(Open::Vector) => self
+                .read_sequence(Close::Vector)
+                .map(MalObject::wrap_vector),
+            Token::Open(Open::Map) => self.read_map(None),
+            Token::Close(c) => Err(read_error(reader::Error::UnexpectedCloseToken(*c))),
+            Token::StringLiteral(s) => {
+                build_string(s).map_err(|e| read_error(reader::Error::StringError(e)))
+            }
+            Token::Char(name) => strings::build_char(name)
+                .map(MalObject::Char)
+                .ok_or_else(|| Error::BadCharacter(name.to_string()).into()),
+            Token::Comment(_) => return Ok(None),
+            Token::UnaryOp(_) => Err(Error::ReaderMacro.into()),
+            Token::Regex(s) => Err(Error::Unsupported(format!("#\"{}\"", s.payload)).into()),
+            Token::PlainChars(chars) => return self.read_plain(chars),
+        }
+        .map(Some)
+    }
+
+    fn read_plain(&mut self, chars: &str) -> evaluator::Result<Option<MalObject>> {
+        if let Some(rest) = chars.strip_prefix("#_") {
+            // The discarded form may be glued on to the `#_`, as in `#_foo`.
+            if rest.is_empty() || self.read_plain(rest)?.is_none() {
+                self.read_form()?;
+            }
+            return Ok(None);
+        }
+        if chars == "#" {
+            return self.read_set().map(Some);
+        }
+        if let Some(namespace) = chars.strip_prefix("#:") {
+            self.expect_open_map()?;
+            return self.read_map(Some(namespace)).map(Some);
+        }
+        if let Some(tag) = chars.strip_prefix('#') {
+            if tag.starts_with('#') {
+                return Err(Error::Unsupported(chars.into()).into());
+            }
+            let value = self.read_form()?;
+            return self.read_tagged(tag, value).map(Some);
+        }
+        // Arbitrary precision integers, like `42N`, are just integers to us.
+        let number = chars.strip_suffix('N').filter(|digits| {
+            let unsigned = digits.trim_start_matches(['+', '-']);
+            !unsigned.is_empty() && unsigned.chars().all(|c| c.is_ascii_digit())
+        });
+        match read_plain_chars(number.unwrap_or(chars)) {
+            Ok(obj) => Ok(Some(obj)),
+            // Mal only has integers.
+            Err(reader::Error::ReadIntError) => Err(Error::Unsupported(chars.into()).into()),
+            Err(e) => Err(read_error(e)),
+        }
+    }
+
+    fn read_sequence(&mut self, closing: Close) -> evaluator::Result<Vec<MalObject>> {
+        let mut elements = Vec::new();
+        loop {
+            match self.tokens.peek() {
+                Some(Token::Close(c)) if *c == closing => {
+                    self.tokens.next();
+                    return Ok(elements);
+                }
+                Some(_) => {
+                    if let Some(obj) = self.read_optional_form()? {
+                        elements.push(obj);
+                    }
+                }
+                None => return Err(read_error(reader::Error::UnbalancedSequence)),
+            }
+        }
+    }
+
+    fn expect_open_map(&mut self) -> evaluator::Result<()> {
+        match self.tokens.next() {
+            Some(Token::Open(Open::Map)) => Ok(()),
+            Some(token) => Err(Error::Unsupported(format!("#{:?}", token)).into()),
+            None => Err(read_error(reader::Error::NoMoreTokens)),
+        }
+    }
+
+    // Keywords without a namespace are put in `namespace`, if given.
+    fn read_map(&mut self, namespace: Option<&str>) -> evaluator::Result {
+        let entries = self.read_sequence(Close::Map)?;
+        if entries.len() % 2 == 1 {
+            return Err(read_error(reader::Error::ReadMapError(
+                crate::types::MapError::MissingValue,
+            )));
+        }
+        let mut map = HashMap::new();
+        let mut iter = entries.into_iter();
+        while let (Some(key), Some(value)) = (iter.next(), iter.next()) {
+            let key = match (key, namespace) {
+                (MalObject::Keyword(k), Some(ns)) if !k.contains('/') => {
+                    HashKey::Keyword(format!("{}/{}", ns, k))
+                }
+                (key, _) => key
+                    .as_hashkey()
+                    .map_err(|_| Error::BadMapKey(key.to_string()))?,
+            };
+            map.insert(key, value);
+        }
+        Ok(MalObject::wrap_map(map))
+    }
+
+    fn read_set(&mut self) -> evaluator::Result {
+        self.expect_open_map()?;
+        let elements = self.read_sequence(Close::Map)?;
+        for (i, element) in elements.iter().enumerate() {
+            if elements[..i].contains(element) {
+                return Err(Error::DuplicateSetElement(element.to_string()).into());
+            }
+        }
+        Ok(MalObject::Vector(Rc::new(MalVector {
+            payload: elements,
+            meta: set_marker(),
+        })))
+    }
+
+    fn read_tagged(&mut self, tag: &str, value: MalObject) -> evaluator::Result {
+        if let Some(handler) = self.readers.tags.get(tag) {
+            return handler(value);
+        }
+        let registered = TAGS.with(|tags| tags.borrow().get(tag).cloned());
+        match (registered, &self.readers.default) {
+            (Some(handler), _) => handler(value),
+            (None, Some(default)) => default(tag, value),
+            (None, None) => Err(Error::UnknownTag(tag.into()).into()),
+        }
+    }
+}
+
+fn set_marker() -> MalObject {
+    let mut meta = HashMap::new();
+    meta.insert(HashKey::Keyword("edn/set".into()), MalObject::Bool(true));
+    MalObject::wrap_map(meta)
+}
+
+fn is_set(vec: &MalVector) -> bool {
+    match &vec.meta {
+        MalObject::Map(meta) => match meta.payload.get(&HashKey::Keyword("edn/set".into())) {
+            Some(flag) => crate::types::truthy(flag),
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+/// Write `obj` as EDN text.
+pub fn write_str(obj: &MalObject) -> Result<String, Error> {
+    let mut output = String::new();
+    write(&mut output, obj)?;
+    Ok(output)
+}
+
+fn write(output: &mut String, obj: &MalObject) -> Result<(), Error> {
+    match obj {
+        MalObject::String(s) => output.push_str(&strings::string_repr(s)),
+        MalObject::Char(c) => output.push_str(&strings::char_repr(*c)),
+        MalObject::Instant(_) => output.push_str(&obj.to_string()),
+        MalObject::Nil
+        | MalObject::Bool(_)
+        | MalObject::Integer(_)
+        | MalObject::Symbol(_)
+        | MalObject::Keyword(_) => output.push_str(&obj.to_string()),
+        MalObject::List(list) => write_all(output, "(", &list.payload, ")")?,
+        // Realised by `write-edn`.
+        MalObject::LazySeq(_) => {
+            let (elements, _) = lazy::realised_elements(obj, None);
+            write_all(output, "(", &elements, ")")?
+        }
+        MalObject::Vector(vec) if is_set(vec) => write_all(output, "#{", &vec.payload, "}")?,
+        MalObject::Vector(vec) => write_all(output, "[", &vec.payload, "]")?,
+        MalObject::Map(map) => write_map(output, map)?,
+        _ => {
+            let printed = printer::pr_str(obj, printer::PrintMode::ReadableRepresentation);
+            return Err(Error::CantWrite(printed));
+        }
+    }
+    Ok(())
+}
+
+fn write_all(
+    output: &mut String,
+    open: &str,
+    elements: &[MalObject],
+    close: &str,
+) -> Result<(), Error> {
+    output.push_str(open);
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            output.push(' ');
+        }
+        write(output, element)?;
+    }
+    output.push_str(close);
+    Ok(())
+}
+
+fn write_map(output: &mut String, map: &MalMap) -> Result<(), Error> {
+    output.push('{');
+    for (i, (key, value)) in map.payload.iter().enumerate() {
+        if i > 0 {
+            output.push_str(", ");
+        }
+        write(output, &key.into_mal_object())?;
+        output.push(' ');
+        write(output, value)?;
+    }
+    output.push('}');
+    Ok(())
+}
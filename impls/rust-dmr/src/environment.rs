@@ -1,9 +1,75 @@
-use crate::types::{MalObject, MalSymbol, PrimitiveEval, PrimitiveFnRef};
-use crate::{core, interpreter, prelude};
+use crate::console::Console;
+use crate::interpreter::Engine;
+use crate::namespaces::{self, Namespace};
+use crate::types::{Arity, MalObject, MalSymbol, PrimitiveEval, PrimitiveFnRef, PrimitivePayload};
+use crate::{core, evaluator, interpreter, prelude, printer};
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
+use std::str::FromStr;
+
+// The primitives in `core::CORE` are grouped by what they give mal code access
+// to, so that embedders can evaluate untrusted code without handing over the
+// filesystem or terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Computation with no side effects beyond atoms.
+    Pure,
+    IoRead,
+    IoWrite,
+    /// Printing to stdout and reading from stdin.
+    Console,
+    Time,
+    /// Pseudo-random numbers.
+    Random,
+    /// Running other programs, and environment variables, the working directory
+    /// and exiting for this one.
+    Process,
+    /// Poking at the interpreter itself: logging, garbage collection, ...
+    Debug,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 8] = [
+        Capability::Pure,
+        Capability::IoRead,
+        Capability::IoWrite,
+        Capability::Console,
+        Capability::Time,
+        Capability::Random,
+        Capability::Process,
+        Capability::Debug,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Pure => "pure",
+            Capability::IoRead => "io-read",
+            Capability::IoWrite => "io-write",
+            Capability::Console => "console",
+            Capability::Time => "time",
+            Capability::Random => "random",
+            Capability::Process => "process",
+            Capability::Debug => "debug",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownCapability(pub String);
+
+impl FromStr for Capability {
+    type Err = UnknownCapability;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Capability::ALL
+            .iter()
+            .find(|c| c.name() == s)
+            .copied()
+            .ok_or_else(|| UnknownCapability(s.into()))
+    }
+}
 
 pub struct Environment {
     /* Did a bit of cheating here by consulting the existing rust implementation.
@@ -25,6 +91,36 @@ pub struct Environment {
     */
     data: RefCell<HashMap<MalSymbol, MalObject>>,
     parent: Option<Rc<Environment>>,
+    // Set if this is the top-level environment of a namespace.
+    namespace: Option<Rc<Namespace>>,
+    // Set if this is a root environment.
+    console: Option<Rc<RefCell<Console>>>,
+}
+
+thread_local! {
+    // The root environments being evaluated in, innermost last.
+    static ACTIVE: RefCell<Vec<Weak<Environment>>> = const { RefCell::new(Vec::new()) };
+    // The last one to finish, so that results printed afterwards still see the
+    // settings of the environment they came from.
+    static FINISHED: RefCell<Weak<Environment>> = const { RefCell::new(Weak::new()) };
+}
+
+/// Held while evaluating in an environment: see `Environment::activate`.
+pub(crate) struct Activation;
+
+impl Drop for Activation {
+    fn drop(&mut self) {
+        if let Some(root) = ACTIVE.with(|active| active.borrow_mut().pop()) {
+            FINISHED.with(|finished| finished.replace(root));
+        }
+    }
+}
+
+// The root environment being evaluated in, or else the last one which was.
+pub(crate) fn active_root() -> Option<Rc<Environment>> {
+    let root = ACTIVE.with(|active| active.borrow().last().cloned());
+    root.unwrap_or_else(|| FINISHED.with(|finished| finished.borrow().clone()))
+        .upgrade()
 }
 
 #[derive(Debug)]
@@ -42,16 +138,37 @@ impl Environment {
     // there's no value matching `key`. But it seems more rustic for get to return
     // an Option.
     pub fn get(&self, key: &MalSymbol) -> Option<MalObject> {
+        self.lookup(key).or_else(|| namespaces::resolve(self, key))
+    }
+
+    fn lookup(&self, key: &MalSymbol) -> Option<MalObject> {
         match self.data.borrow().get(key) {
             // TODO is this done correctly---we clone the value?
             Some(value) => Some(value.clone()),
             None => match &self.parent {
                 // TODO: nonrecursive?
-                Some(parent) => parent.get(key),
+                Some(parent) => parent.lookup(key),
                 None => None,
             },
         }
     }
+
+    // Without looking in the parent.
+    pub(crate) fn get_local(&self, key: &MalSymbol) -> Option<MalObject> {
+        self.data.borrow().get(key).cloned()
+    }
+
+    // The environment `key` is bound in: this one or one of its ancestors.
+    pub(crate) fn owner(self: &Rc<Self>, key: &MalSymbol) -> Option<Rc<Environment>> {
+        let mut env = self;
+        loop {
+            if env.data.borrow().contains_key(key) {
+                return Some(env.clone());
+            }
+            env = env.parent.as_ref()?;
+        }
+    }
+
     pub(crate) fn fetch(&self, key: &MalSymbol) -> Result<MalObject, UnknownSymbol> {
         self.get(key).ok_or_else(|| UnknownSymbol(key.clone()))
     }
@@ -60,34 +177,134 @@ impl Environment {
         Self {
             data: RefCell::new(HashMap::new()),
             parent: None,
+            namespace: None,
+            console: Some(Rc::default()),
         }
     }
 
     pub fn default() -> Self {
+        Self::with_capabilities(&Capability::ALL)
+    }
+
+    pub fn builder() -> EnvironmentBuilder {
+        EnvironmentBuilder::new()
+    }
+
+    fn with_capabilities(capabilities: &[Capability]) -> Self {
         let mut data = HashMap::new();
-        for (&name, &func) in core::CORE.iter() {
-            data.insert(
-                MalSymbol(name.into()),
-                MalObject::Primitive(PrimitiveFnRef {
-                    payload: func,
-                    meta: Box::new(MalObject::Nil),
-                }),
-            );
+        for capability in capabilities {
+            for (&name, &func) in core::CORE[capability].iter() {
+                data.insert(
+                    MalSymbol(name.into()),
+                    MalObject::Primitive(PrimitiveFnRef {
+                        payload: PrimitivePayload::Static(func),
+                        meta: Box::new(MalObject::Nil),
+                    }),
+                );
+            }
         }
         data.insert(
             MalSymbol("*host-language*".into()),
             MalObject::String("rust-dmr".into()),
         );
+        data.insert(MalSymbol(printer::PRINT_LENGTH.into()), MalObject::Nil);
+        let namespace = namespaces::install(&mut data, capabilities);
+        namespace.declare_dynamic(printer::PRINT_LENGTH);
         Self {
             data: RefCell::new(data),
             parent: None,
+            namespace: Some(namespace),
+            console: Some(Rc::default()),
         }
     }
 
+    /// Bind `name` to a primitive implemented by a Rust closure.
+    ///
+    /// ```
+    /// use rust_dmr_mal::environment::Environment;
+    /// use rust_dmr_mal::types::{Arity, MalObject};
+    /// use std::cell::Cell;
+    /// use std::rc::Rc;
+    ///
+    /// let env = Rc::new(Environment::default());
+    /// let calls = Rc::new(Cell::new(0));
+    /// let counter = calls.clone();
+    /// env.register("tick", Arity::exactly(0), move |_| {
+    ///     counter.set(counter.get() + 1);
+    ///     Ok(MalObject::Integer(counter.get()))
+    /// });
+    /// rust_dmr_mal::interpreter::rep("(do (tick) (tick))", &env).unwrap();
+    /// assert_eq!(calls.get(), 2);
+    /// ```
+    pub fn register<F>(&self, name: &str, arity: Arity, func: F) -> Option<MalObject>
+    where
+        F: Fn(&[MalObject]) -> evaluator::Result + 'static,
+    {
+        self.set(MalSymbol(name.into()), MalObject::native(name, arity, func))
+    }
+
+    pub(crate) fn parent(&self) -> Option<&Rc<Environment>> {
+        self.parent.as_ref()
+    }
+
+    pub(crate) fn namespace(&self) -> Option<&Rc<Namespace>> {
+        self.namespace.as_ref()
+    }
+
+    fn root(self: &Rc<Self>) -> &Rc<Environment> {
+        match &self.parent {
+            Some(parent) => parent.root(),
+            None => self,
+        }
+    }
+
+    // Where `prn`, `println` and `readline` send and take their text.
+    pub(crate) fn console(&self) -> &Rc<RefCell<Console>> {
+        match &self.parent {
+            Some(parent) => parent.console(),
+            None => self.console.as_ref().unwrap(),
+        }
+    }
+
+    // Make this environment's root the one which primitives act on, such as
+    // `println` writing to its console, until the activation is dropped.
+    pub(crate) fn activate(self: &Rc<Self>) -> Activation {
+        let root = Rc::downgrade(self.root());
+        ACTIVE.with(|active| active.borrow_mut().push(root));
+        Activation
+    }
+
+    // For the cycle collector. None if the data is currently borrowed mutably.
+    pub(crate) fn try_values(&self) -> Option<Vec<MalObject>> {
+        let data = self.data.try_borrow().ok()?;
+        Some(data.values().cloned().collect())
+    }
+
+    pub(crate) fn clear(&self) {
+        let old = self.data.replace(HashMap::new());
+        // Dropping the old data may free closures, environments, ... only do so once we've
+        // stopped borrowing.
+        drop(old);
+    }
+
     pub(crate) fn spawn_from(parent: &Rc<Environment>) -> Rc<Environment> {
         Rc::new(Environment {
             data: RefCell::new(HashMap::new()),
             parent: Some(parent.clone()),
+            namespace: None,
+            console: None,
+        })
+    }
+
+    pub(crate) fn for_namespace(
+        root: &Rc<Environment>,
+        namespace: Rc<Namespace>,
+    ) -> Rc<Environment> {
+        Rc::new(Environment {
+            data: RefCell::new(HashMap::new()),
+            parent: Some(root.clone()),
+            namespace: Some(namespace),
+            console: None,
         })
     }
 
@@ -117,19 +334,105 @@ impl fmt::Display for Environment {
     }
 }
 
+/// Constructs a root environment containing only the primitives from the chosen
+/// capability groups.
+///
+/// ```
+/// use rust_dmr_mal::environment::{Capability, Environment};
+/// let env = Environment::builder()
+///     .with(Capability::Pure)
+///     .with_prelude()
+///     .build()
+///     .unwrap();
+/// ```
+pub struct EnvironmentBuilder {
+    capabilities: HashSet<Capability>,
+    prelude: bool,
+    eval: bool,
+    engine: Engine,
+}
+
+impl EnvironmentBuilder {
+    pub fn new() -> Self {
+        Self {
+            capabilities: HashSet::new(),
+            prelude: false,
+            eval: false,
+            engine: Engine::TreeWalker,
+        }
+    }
+
+    pub fn with(mut self, capability: Capability) -> Self {
+        self.capabilities.insert(capability);
+        self
+    }
+
+    pub fn with_all(mut self) -> Self {
+        self.capabilities.extend(Capability::ALL.iter());
+        self
+    }
+
+    /// Define the functions and macros in `prelude::PRELUDE`. Note that
+    /// `load-file` needs `Capability::IoRead` to be of any use.
+    pub fn with_prelude(mut self) -> Self {
+        self.prelude = true;
+        self
+    }
+
+    pub fn with_eval(mut self) -> Self {
+        self.eval = true;
+        self
+    }
+
+    /// Which engine to use when evaluating the prelude and `eval`.
+    pub fn using(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    pub fn build(self) -> Result<Rc<Environment>, String> {
+        let capabilities: Vec<_> = self.capabilities.into_iter().collect();
+        let env = Rc::new(Environment::with_capabilities(&capabilities));
+        namespaces::set_engine(&env, self.engine);
+        if self.prelude {
+            read_prelude_using(&env, self.engine)?;
+        }
+        if self.eval {
+            add_eval_using(&env, self.engine);
+        }
+        Ok(env)
+    }
+}
+
+impl Default for EnvironmentBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub fn read_prelude(env: &Rc<Environment>) -> Result<(), String> {
+    read_prelude_using(env, Engine::TreeWalker)
+}
+
+pub fn read_prelude_using(env: &Rc<Environment>, engine: Engine) -> Result<(), String> {
     let result: Result<Vec<_>, _> = prelude::PRELUDE
         .lines()
         .map(str::trim)
         .filter(|s| !s.is_empty())
-        .map(|s| interpreter::rep(s, env))
+        .map(|s| interpreter::rep_using(s, env, engine))
         .collect();
     result.map(|_| ())
 }
 
 pub fn add_eval(env: &Rc<Environment>) {
+    add_eval_using(env, Engine::TreeWalker)
+}
+
+pub fn add_eval_using(env: &Rc<Environment>, engine: Engine) {
     let dummy = MalObject::Eval(PrimitiveEval {
         env: Rc::downgrade(env),
+        engine,
     });
     env.set(MalSymbol("eval".into()), dummy);
+    namespaces::set_engine(env, engine);
 }
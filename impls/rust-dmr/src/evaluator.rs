@@ -1,9 +1,12 @@
 use crate::environment::{Environment, UnknownSymbol};
 use crate::evaluator::ApplyOutcome::EvaluateFurther;
+use crate::interpreter::Engine;
 use crate::types::{
     Arity, Closure, MalMap, MalObject, MalSymbol, PrimitiveEval, PrimitiveFnRef, TypeMismatch,
 };
-use crate::{environment, reader, special_forms, types};
+use crate::{
+    edn, environment, fs, json, limits, namespaces, reader, special_forms, text, time, types, vm,
+};
 
 use itertools::Itertools;
 
@@ -19,6 +22,7 @@ pub enum Error {
     ListHeadNotSymbol,
     Def(special_forms::DefError),
     Let(special_forms::LetError),
+    Binding(special_forms::BindingError),
     Do(special_forms::DoError),
     Fn(special_forms::FnError),
     MissingCatchFromTry,
@@ -30,7 +34,19 @@ pub enum Error {
     // TODO the arrangement of all these errors needs a rethink IMO!
     ReadError(reader::Error),
     IOError(std::io::Error),
+    File(fs::Error),
+    Json(json::Error),
+    Edn(edn::Error),
+    Format(text::FormatError),
+    Regex(regex::Error),
+    Time(time::Error),
     UserException(MalObject),
+    LazyCycle,
+    Namespace(namespaces::Error),
+    StepLimitExceeded(u64),
+    DepthLimitExceeded(usize),
+    Timeout(std::time::Duration),
+    Interrupted,
 }
 
 #[derive(Debug)]
@@ -49,12 +65,19 @@ impl fmt::Display for Error {
             Error::TypeMismatch(e) => write!(f, "type mismatch: {:?}", e),
             Error::Def(e) => write!(f, "def!: {:?}", e),
             Error::Let(e) => write!(f, "let*: {:?}", e),
+            Error::Binding(e) => write!(f, "binding: {:?}", e),
             Error::Do(e) => write!(f, "do: {:?}", e),
             Error::Fn(e) => write!(f, "fn*: {:?}", e),
             Error::BadArgCount(e) => write!(f, "{}", e),
             Error::DivideByZero => write!(f, "cannot divide by zero!"),
             Error::ReadError(e) => write!(f, "read error: {}", e),
             Error::IOError(e) => write!(f, "io error: {}", e),
+            Error::File(e) => write!(f, "io error: {}", e),
+            Error::Json(e) => write!(f, "json error: {}", e),
+            Error::Edn(e) => write!(f, "edn error: {}", e),
+            Error::Format(e) => write!(f, "format error: {}", e),
+            Error::Regex(e) => write!(f, "bad regex: {}", e),
+            Error::Time(e) => write!(f, "time error: {}", e),
             Error::BadIndex(i, r) => {
                 write!(f, "bad index: {} not in range [{}, {})", i, r.start, r.end)
             }
@@ -65,6 +88,16 @@ impl fmt::Display for Error {
                 e.original, e.then
             ),
             Error::UserException(e) => write!(f, "UserException: {}", e),
+            Error::LazyCycle => write!(f, "lazy sequence depends on its own value"),
+            Error::Namespace(e) => write!(f, "{}", e),
+            Error::StepLimitExceeded(n) => {
+                write!(f, "evaluation exceeded the limit of {} steps", n)
+            }
+            Error::DepthLimitExceeded(n) => {
+                write!(f, "evaluation exceeded the maximum nesting depth of {}", n)
+            }
+            Error::Timeout(t) => write!(f, "evaluation timed out after {:?}", t),
+            Error::Interrupted => write!(f, "Interrupted"),
         }
     }
 }
@@ -85,6 +118,7 @@ impl From<&Error> for MalObject {
     fn from(e: &Error) -> Self {
         match e {
             Error::UserException(obj) => obj.clone(),
+            Error::File(e) => e.to_mal(),
             _ => MalObject::String(format!("{}", e)),
         }
     }
@@ -96,9 +130,11 @@ pub(crate) type EvalContext = (MalObject, Rc<Environment>);
 pub(crate) fn EVAL(orig_ast: &MalObject, orig_env: &Rc<Environment>) -> Result {
     log::trace!("Call EVAL with {}", orig_ast);
     use MalObject::{List, Symbol};
+    let _nesting = limits::Nesting::enter()?;
     let mut ast = orig_ast.clone();
     let mut env = orig_env.clone();
     let result = loop {
+        limits::step()?;
         ast = macroexpand(&ast, &env)?;
         log::trace!("macroexpand produced {}", ast);
         match &ast {
@@ -119,10 +155,28 @@ pub(crate) fn EVAL(orig_ast: &MalObject, orig_env: &Rc<Environment>) -> Result {
                             "defmacro!" => {
                                 break special_forms::apply_def(&argv.payload[1..], &env, true)
                             }
-                            "let*" => break special_forms::apply_let(&argv.payload[1..], &env),
-                            "do" => break special_forms::apply_do(&argv.payload[1..], &env),
-                            "if" => break special_forms::apply_if(&argv.payload[1..], &env),
+                            "let*" => {
+                                let (new_ast, new_env) =
+                                    special_forms::apply_let(&argv.payload[1..], &env)?;
+                                ast = new_ast;
+                                env = new_env;
+                                continue;
+                            }
+                            "binding" => {
+                                break special_forms::apply_binding(&argv.payload[1..], &env)
+                            }
+                            "do" => {
+                                ast = special_forms::apply_do(&argv.payload[1..], &env)?.0;
+                                continue;
+                            }
+                            "if" => {
+                                ast = special_forms::apply_if(&argv.payload[1..], &env)?.0;
+                                continue;
+                            }
                             "fn*" => break special_forms::apply_fn(&argv.payload[1..], &env),
+                            "lazy-seq" => {
+                                break special_forms::apply_lazy_seq(&argv.payload[1..], &env)
+                            }
                             // Any other initial symbol will be interpreted a a function call and
                             // handled below
                             "quote" => {
@@ -210,18 +264,23 @@ pub(crate) fn apply(callable: &MalObject, args: &[MalObject]) -> Result<ApplyOut
     use MalObject::{Closure, Eval, Primitive};
     match callable {
         Primitive(f) => call_primitive(f, args).map(ApplyOutcome::Finished),
+        Closure(f) if f.compiled.is_some() => vm::call_closure(f, args).map(ApplyOutcome::Finished),
         Closure(f) => {
             let ast = f.body.clone();
             let env = make_closure_env(f, args)?;
             Ok(ApplyOutcome::EvaluateFurther(ast, env))
         }
-        Eval(PrimitiveEval { env }) => {
+        Eval(PrimitiveEval { env, engine }) => {
             Arity::exactly(1)
                 .validate_for(args.len(), "eval")
                 .map_err(Error::BadArgCount)?;
             let env = env.upgrade().expect("eval: env destroyed");
+            let env = namespaces::current(&env);
             log::info!("Call from mal to EVAL with {}", args[0]);
-            Ok(EvaluateFurther(args[0].clone(), env))
+            match engine {
+                Engine::TreeWalker => Ok(EvaluateFurther(args[0].clone(), env)),
+                Engine::Bytecode => vm::EVAL(&args[0], &env).map(ApplyOutcome::Finished),
+            }
         }
         _ => Err(Error::TypeMismatch(TypeMismatch::NotCallable)),
     }
@@ -269,20 +328,20 @@ pub(crate) fn pretty_print_args(args: &[MalObject]) -> String {
 }
 
 pub fn call_primitive(func: &PrimitiveFnRef, args: &[MalObject]) -> Result {
-    let func = func.payload;
-    func.arity
-        .validate_for(args.len(), func.name)
+    let func = &func.payload;
+    func.arity()
+        .validate_for(args.len(), func.name())
         .map_err(Error::BadArgCount)?;
-    log::trace!("Call {} with {}", func.name, pretty_print_args(args));
-    let result = (func.fn_ptr)(args);
+    log::trace!("Call {} with {}", func.name(), pretty_print_args(args));
+    let result = func.call(args);
     match &result {
-        Ok(val) => log::trace!("Call to {} resulted in {}", func.name, val),
-        Err(e) => log::trace!("Call to {} failed: {}", func.name, e),
+        Ok(val) => log::trace!("Call to {} resulted in {}", func.name(), val),
+        Err(e) => log::trace!("Call to {} failed: {}", func.name(), e),
     }
     result
 }
 
-fn make_closure_env(func: &Closure, args: &[MalObject]) -> Result<Rc<Environment>> {
+pub(crate) fn make_closure_env(func: &Closure, args: &[MalObject]) -> Result<Rc<Environment>> {
     log::trace!("Call {} with {}", func, pretty_print_args(args));
     func.parameters
         .arity()
@@ -300,7 +359,7 @@ fn make_closure_env(func: &Closure, args: &[MalObject]) -> Result<Rc<Environment
     Ok(env)
 }
 
-fn is_macro_call<'a>(ast: &'a MalObject, env: &Environment) -> Option<&'a MalSymbol> {
+pub(crate) fn is_macro_call<'a>(ast: &'a MalObject, env: &Environment) -> Option<&'a MalSymbol> {
     let symbol = ast
         .as_list()
         .ok()
@@ -318,7 +377,7 @@ fn is_macro_call<'a>(ast: &'a MalObject, env: &Environment) -> Option<&'a MalSym
     }
 }
 
-fn macroexpand(ast: &MalObject, env: &Rc<Environment>) -> Result {
+pub(crate) fn macroexpand(ast: &MalObject, env: &Rc<Environment>) -> Result {
     let mut ast = ast.clone();
     let env = env.clone();
     while let Some(symbol) = is_macro_call(&ast, &env) {
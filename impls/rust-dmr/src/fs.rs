@@ -0,0 +1,292 @@
+// File system primitives.
+//
+// Functions which only look at the file system need the io-read capability and
+// those which change it need io-write. The path helpers just manipulate strings,
+// apart from `abs-path` which depends on the working directory.
+//
+// Failures are reported as an `Error` saying what we were trying to do to which
+// path. When caught by `try*`, they become a map (see `Error::to_mal`) so that
+// mal code can tell a missing file from a permissions problem.
+
+use crate::core::flag;
+use crate::types::{Arity, HashKey, MalInt, MalObject, PrimitiveFn};
+use crate::{evaluator, lazy, printer};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+#[derive(Debug)]
+pub struct Error {
+    pub operation: &'static str,
+    pub path: PathBuf,
+    pub source: io::Error,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "can't {} {}: {}",
+            self.operation,
+            self.path.display(),
+            self.source
+        )
+    }
+}
+
+impl Error {
+    // {:kind :not-found, :operation "read", :path "...", :message "..."}
+    pub(crate) fn to_mal(&self) -> MalObject {
+        let kind = match self.source.kind() {
+            ErrorKind::NotFound => "not-found",
+            ErrorKind::PermissionDenied => "permission-denied",
+            ErrorKind::AlreadyExists => "already-exists",
+            ErrorKind::InvalidData => "invalid-data",
+            _ => "other",
+        };
+        let mut map = HashMap::new();
+        let mut insert = |key: &str, value| map.insert(HashKey::Keyword(key.into()), value);
+        insert("kind", MalObject::new_keyword(kind));
+        insert("operation", MalObject::String(self.operation.into()));
+        insert("path", path_string(&self.path));
+        insert("message", MalObject::String(self.to_string()));
+        MalObject::wrap_map(map)
+    }
+}
+
+// Wrap the result of an io operation on `path` in a structured error.
+pub(crate) fn attempt<T>(
+    operation: &'static str,
+    path: &Path,
+    result: io::Result<T>,
+) -> evaluator::Result<T> {
+    result.map_err(|source| {
+        evaluator::Error::File(Error {
+            operation,
+            path: path.to_path_buf(),
+            source,
+        })
+    })
+}
+
+fn path_arg(obj: &MalObject) -> evaluator::Result<&Path> {
+    Ok(Path::new(obj.as_string()?))
+}
+
+fn path_string(path: &Path) -> MalObject {
+    MalObject::String(path.to_string_lossy().into_owned())
+}
+
+pub(crate) const SPIT: PrimitiveFn = PrimitiveFn {
+    name: "spit",
+    fn_ptr: spit,
+    arity: Arity::Between(2..=3),
+};
+
+// (spit path content) writes content, as `str` would print it, replacing the
+// file. (spit path content {:append true}) adds to the end instead.
+fn spit(args: &[MalObject]) -> evaluator::Result {
+    let path = path_arg(&args[0])?;
+    let content = printer::pr_str(&args[1], printer::PrintMode::Directly);
+    let append = flag(args.get(2), "append")?;
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path);
+    attempt(
+        "write",
+        path,
+        file.and_then(|mut f| f.write_all(content.as_bytes())),
+    )?;
+    Ok(MalObject::Nil)
+}
+
+pub(crate) const FILE_EXISTS_TEST: PrimitiveFn = PrimitiveFn {
+    name: "file-exists?",
+    fn_ptr: |args| Ok(MalObject::Bool(path_arg(&args[0])?.exists())),
+    arity: Arity::exactly(1),
+};
+
+pub(crate) const DIR_TEST: PrimitiveFn = PrimitiveFn {
+    name: "dir?",
+    fn_ptr: |args| Ok(MalObject::Bool(path_arg(&args[0])?.is_dir())),
+    arity: Arity::exactly(1),
+};
+
+pub(crate) const LIST_DIR: PrimitiveFn = PrimitiveFn {
+    name: "list-dir",
+    fn_ptr: list_dir,
+    arity: Arity::exactly(1),
+};
+
+// The names of a directory's entries, sorted.
+fn list_dir(args: &[MalObject]) -> evaluator::Result {
+    let path = path_arg(&args[0])?;
+    let entries = attempt("list", path, fs::read_dir(path))?;
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = attempt("list", path, entry)?;
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+    Ok(MalObject::wrap_vector(
+        names.into_iter().map(MalObject::String).collect(),
+    ))
+}
+
+pub(crate) const MKDIR: PrimitiveFn = PrimitiveFn {
+    name: "mkdir",
+    fn_ptr: mkdir,
+    arity: Arity::exactly(1),
+};
+
+// Also creates any missing parent directories, and is fine if the directory
+// already exists.
+fn mkdir(args: &[MalObject]) -> evaluator::Result {
+    let path = path_arg(&args[0])?;
+    attempt("create directory", path, fs::create_dir_all(path))?;
+    Ok(MalObject::Nil)
+}
+
+pub(crate) const DELETE_FILE: PrimitiveFn = PrimitiveFn {
+    name: "delete-file",
+    fn_ptr: delete_file,
+    arity: Arity::exactly(1),
+};
+
+// Deletes a file or an empty directory.
+fn delete_file(args: &[MalObject]) -> evaluator::Result {
+    let path = path_arg(&args[0])?;
+    let result = match path.is_dir() {
+        true => fs::remove_dir(path),
+        false => fs::remove_file(path),
+    };
+    attempt("delete", path, result)?;
+    Ok(MalObject::Nil)
+}
+
+pub(crate) const RENAME_FILE: PrimitiveFn = PrimitiveFn {
+    name: "rename-file",
+    fn_ptr: rename_file,
+    arity: Arity::exactly(2),
+};
+
+fn rename_file(args: &[MalObject]) -> evaluator::Result {
+    let from = path_arg(&args[0])?;
+    let to = path_arg(&args[1])?;
+    attempt("rename", from, fs::rename(from, to))?;
+    Ok(MalObject::Nil)
+}
+
+pub(crate) const FILE_SIZE: PrimitiveFn = PrimitiveFn {
+    name: "file-size",
+    fn_ptr: file_size,
+    arity: Arity::exactly(1),
+};
+
+// In bytes.
+fn file_size(args: &[MalObject]) -> evaluator::Result {
+    let path = path_arg(&args[0])?;
+    let metadata = attempt("inspect", path, fs::metadata(path))?;
+    Ok(MalObject::Integer(metadata.len() as MalInt))
+}
+
+pub(crate) const READ_LINES: PrimitiveFn = PrimitiveFn {
+    name: "read-lines",
+    fn_ptr: read_lines,
+    arity: Arity::exactly(1),
+};
+
+// A lazy sequence of a file's lines, without their line endings. The file is
+// read as the sequence is realised, so it needn't fit in memory, and is closed
+// once the last line has been read.
+fn read_lines(args: &[MalObject]) -> evaluator::Result {
+    let path = path_arg(&args[0])?;
+    let file = attempt("read", path, File::open(path))?;
+    Ok(lines_from(
+        Rc::new(RefCell::new(BufReader::new(file))),
+        path.to_path_buf(),
+    ))
+}
+
+fn lines_from(reader: Rc<RefCell<BufReader<File>>>, path: PathBuf) -> MalObject {
+    lazy::new(move || {
+        let mut line = String::new();
+        let read = reader.borrow_mut().read_line(&mut line);
+        if attempt("read", &path, read)? == 0 {
+            return Ok(MalObject::Nil);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        let rest = lines_from(reader.clone(), path.clone());
+        Ok(lazy::cons(MalObject::String(line), rest))
+    })
+}
+
+pub(crate) const PATH_JOIN: PrimitiveFn = PrimitiveFn {
+    name: "path-join",
+    fn_ptr: path_join,
+    arity: Arity::at_least(1),
+};
+
+// As with rust's `PathBuf::push`, an absolute component replaces everything
+// before it.
+fn path_join(args: &[MalObject]) -> evaluator::Result {
+    let mut path = PathBuf::new();
+    for arg in args {
+        path.push(path_arg(arg)?);
+    }
+    Ok(path_string(&path))
+}
+
+pub(crate) const BASENAME: PrimitiveFn = PrimitiveFn {
+    name: "basename",
+    fn_ptr: |args| {
+        let name = path_arg(&args[0])?.file_name();
+        Ok(name.map_or(MalObject::Nil, |name| path_string(Path::new(name))))
+    },
+    arity: Arity::exactly(1),
+};
+
+pub(crate) const DIRNAME: PrimitiveFn = PrimitiveFn {
+    name: "dirname",
+    fn_ptr: |args| {
+        let parent = path_arg(&args[0])?.parent();
+        Ok(parent.map_or(MalObject::Nil, path_string))
+    },
+    arity: Arity::exactly(1),
+};
+
+pub(crate) const ABS_PATH: PrimitiveFn = PrimitiveFn {
+    name: "abs-path",
+    fn_ptr: abs_path,
+    arity: Arity::exactly(1),
+};
+
+// Relative paths are resolved against the working directory. `.` and `..` are
+// tidied up without looking at the file system, so the path needn't exist.
+fn abs_path(args: &[MalObject]) -> evaluator::Result {
+    let path = path_arg(&args[0])?;
+    let cwd = attempt("resolve", path, std::env::current_dir())?;
+    let mut absolute = PathBuf::new();
+    for component in cwd.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            component => absolute.push(component),
+        }
+    }
+    Ok(path_string(&absolute))
+}
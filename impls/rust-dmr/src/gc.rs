@@ -0,0 +1,289 @@
+// Closures hold an `Rc` to the environment they were defined in, and `def!`
+// stores closures back into that same environment. So every recursive function
+// makes a reference cycle which reference counting alone will never free.
+//
+// This module is a trial-deletion cycle collector (in the style of CPython's).
+// Any environment captured by a closure, and every atom, is registered here. To
+// collect, we walk everything reachable from the registered objects and count
+// how many of each `Rc`'s strong references come from inside that graph. An
+// object with more strong references than that is held from outside---by a
+// Rust variable, the VM's stack, an embedder---so it and everything it can
+// reach is alive. Whatever remains is only kept alive by cycles. We break those
+// cycles by emptying the environments and atoms involved, and reference
+// counting takes care of the rest.
+//
+// Anything we don't walk (closure bodies, compiled code and lazy sequences) only
+// ever makes us more conservative: references from there look external.
+
+use crate::environment::Environment;
+use crate::types::{Closure, MalList, MalMap, MalObject, MalVector};
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::rc::{Rc, Weak};
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Stats {
+    pub collections: usize,
+    /// Environments and atoms currently registered with the collector.
+    pub tracked: usize,
+    pub environments_freed: usize,
+    pub atoms_freed: usize,
+}
+
+// Collect automatically once this many objects are tracked.
+const INITIAL_THRESHOLD: usize = 10_000;
+
+struct Registry {
+    environments: HashMap<usize, Weak<Environment>>,
+    atoms: HashMap<usize, Weak<RefCell<MalObject>>>,
+    threshold: usize,
+    stats: Stats,
+}
+
+impl Registry {
+    fn tracked(&self) -> usize {
+        self.environments.len() + self.atoms.len()
+    }
+}
+
+thread_local! {
+    static REGISTRY: RefCell<Registry> = RefCell::new(Registry {
+        environments: HashMap::new(),
+        atoms: HashMap::new(),
+        threshold: INITIAL_THRESHOLD,
+        stats: Stats::default(),
+    });
+}
+
+pub(crate) fn track_environment(env: &Rc<Environment>) {
+    let due = REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        // Always overwrite: the address may have belonged to an environment which has since
+        // been dropped.
+        registry
+            .environments
+            .insert(Rc::as_ptr(env) as usize, Rc::downgrade(env));
+        registry.tracked() > registry.threshold
+    });
+    if due {
+        collect();
+    }
+}
+
+pub(crate) fn track_atom(atom: &Rc<RefCell<MalObject>>) {
+    let due = REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        registry
+            .atoms
+            .insert(Rc::as_ptr(atom) as usize, Rc::downgrade(atom));
+        registry.tracked() > registry.threshold
+    });
+    if due {
+        collect();
+    }
+}
+
+pub fn stats() -> Stats {
+    REGISTRY.with(|registry| {
+        let registry = registry.borrow();
+        Stats {
+            tracked: registry.tracked(),
+            ..registry.stats
+        }
+    })
+}
+
+#[derive(Clone)]
+enum Node {
+    Environment(Rc<Environment>),
+    Closure(Rc<Closure>),
+    List(Rc<MalList>),
+    Vector(Rc<MalVector>),
+    Map(Rc<MalMap>),
+    Atom(Rc<RefCell<MalObject>>),
+}
+
+impl Node {
+    fn address(&self) -> usize {
+        match self {
+            Node::Environment(x) => Rc::as_ptr(x) as usize,
+            Node::Closure(x) => Rc::as_ptr(x) as usize,
+            Node::List(x) => Rc::as_ptr(x) as usize,
+            Node::Vector(x) => Rc::as_ptr(x) as usize,
+            Node::Map(x) => Rc::as_ptr(x) as usize,
+            Node::Atom(x) => Rc::as_ptr(x) as usize,
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Node::Environment(x) => Rc::strong_count(x),
+            Node::Closure(x) => Rc::strong_count(x),
+            Node::List(x) => Rc::strong_count(x),
+            Node::Vector(x) => Rc::strong_count(x),
+            Node::Map(x) => Rc::strong_count(x),
+            Node::Atom(x) => Rc::strong_count(x),
+        }
+    }
+
+    // Returns None if we can't look inside right now, e.g. because a RefCell is borrowed.
+    fn children(&self) -> Option<Vec<Node>> {
+        let mut children = Vec::new();
+        match self {
+            Node::Environment(env) => {
+                if let Some(parent) = env.parent() {
+                    children.push(Node::Environment(parent.clone()));
+                }
+                for value in env.try_values()?.iter() {
+                    visit(value, &mut children);
+                }
+            }
+            Node::Closure(closure) => {
+                children.push(Node::Environment(closure.parent.clone()));
+                visit(&closure.meta, &mut children);
+            }
+            Node::List(list) => {
+                list.payload
+                    .iter()
+                    .for_each(|obj| visit(obj, &mut children));
+                visit(&list.meta, &mut children);
+            }
+            Node::Vector(vec) => {
+                vec.payload.iter().for_each(|obj| visit(obj, &mut children));
+                visit(&vec.meta, &mut children);
+            }
+            Node::Map(map) => {
+                map.payload
+                    .values()
+                    .for_each(|obj| visit(obj, &mut children));
+                visit(&map.meta, &mut children);
+            }
+            Node::Atom(atom) => visit(&*atom.try_borrow().ok()?, &mut children),
+        };
+        Some(children)
+    }
+}
+
+fn visit(obj: &MalObject, out: &mut Vec<Node>) {
+    match obj {
+        MalObject::List(x) => out.push(Node::List(x.clone())),
+        MalObject::Vector(x) => out.push(Node::Vector(x.clone())),
+        MalObject::Map(x) => out.push(Node::Map(x.clone())),
+        MalObject::Closure(x) => out.push(Node::Closure(x.clone())),
+        MalObject::Atom(x) => out.push(Node::Atom(x.payload.clone())),
+        MalObject::Primitive(x) => visit(&x.meta, out),
+        _ => (),
+    }
+}
+
+/// Free any environments and atoms which are only kept alive by reference cycles.
+pub fn collect() -> Stats {
+    let start: Vec<Node> = REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        registry
+            .environments
+            .retain(|_, weak| weak.strong_count() > 0);
+        registry.atoms.retain(|_, weak| weak.strong_count() > 0);
+        let environments = registry.environments.values().filter_map(Weak::upgrade);
+        let atoms = registry.atoms.values().filter_map(Weak::upgrade);
+        environments
+            .map(Node::Environment)
+            .chain(atoms.map(Node::Atom))
+            .collect()
+    });
+
+    // Find everything reachable from the registered objects. We hold exactly one clone of
+    // each in `nodes`.
+    let mut nodes: HashMap<usize, Node> = HashMap::new();
+    let mut edges: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut internal: HashMap<usize, usize> = HashMap::new();
+    let mut opaque: Vec<usize> = Vec::new();
+    let mut pending: Vec<usize> = Vec::new();
+    for node in start {
+        let address = node.address();
+        if nodes.insert(address, node).is_none() {
+            pending.push(address);
+        }
+    }
+    while let Some(address) = pending.pop() {
+        let children = match nodes[&address].children() {
+            Some(children) => children,
+            None => {
+                opaque.push(address);
+                continue;
+            }
+        };
+        let mut targets = Vec::with_capacity(children.len());
+        for child in children {
+            let child_address = child.address();
+            *internal.entry(child_address).or_insert(0) += 1;
+            targets.push(child_address);
+            if let Entry::Vacant(entry) = nodes.entry(child_address) {
+                entry.insert(child);
+                pending.push(child_address);
+            }
+        }
+        edges.insert(address, targets);
+    }
+
+    // Anything with strong references from outside the graph is alive, as is everything
+    // reachable from it.
+    let mut alive: Vec<usize> = nodes
+        .iter()
+        .filter(|(address, node)| {
+            let internal = internal.get(address).copied().unwrap_or(0);
+            node.strong_count() > internal + 1
+        })
+        .map(|(&address, _)| address)
+        .chain(opaque)
+        .collect();
+    let mut marked: HashSet<usize> = HashSet::new();
+    while let Some(address) = alive.pop() {
+        if !marked.insert(address) {
+            continue;
+        }
+        if let Some(targets) = edges.get(&address) {
+            alive.extend(targets.iter().filter(|t| !marked.contains(t)));
+        }
+    }
+
+    let mut environments_freed = 0;
+    let mut atoms_freed = 0;
+    for (address, node) in nodes.iter() {
+        if marked.contains(address) {
+            continue;
+        }
+        match node {
+            Node::Environment(env) => {
+                env.clear();
+                environments_freed += 1;
+            }
+            Node::Atom(atom) => {
+                let old = atom.replace(MalObject::Nil);
+                drop(old);
+                atoms_freed += 1;
+            }
+            _ => (),
+        }
+    }
+    drop(nodes);
+
+    let stats = REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        registry
+            .environments
+            .retain(|_, weak| weak.strong_count() > 0);
+        registry.atoms.retain(|_, weak| weak.strong_count() > 0);
+        registry.threshold = INITIAL_THRESHOLD.max(2 * registry.tracked());
+        registry.stats.collections += 1;
+        registry.stats.environments_freed += environments_freed;
+        registry.stats.atoms_freed += atoms_freed;
+        Stats {
+            tracked: registry.tracked(),
+            ..registry.stats
+        }
+    });
+    log::debug!("Garbage collection: {:?}", stats);
+    stats
+}
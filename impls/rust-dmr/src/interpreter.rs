@@ -1,5 +1,11 @@
-use crate::types::MalObject;
-use crate::{environment, evaluator, printer, reader};
+use crate::convert::{FromMal, IntoMal};
+use crate::environment::{Environment, UnknownSymbol};
+use crate::limits::{self, Limits};
+use crate::types::{Arity, MalObject, MalSymbol};
+use crate::{console, environment, evaluator, namespaces, printer, reader, vm};
+use std::cell::Cell;
+use std::fmt;
+use std::io::{BufRead, Write};
 use std::rc::Rc;
 
 pub type Result = std::result::Result<MalObject, Error>;
@@ -9,6 +15,40 @@ pub enum Error {
     Eval(evaluator::Error),
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read(e) => write!(f, "{}", e),
+            Error::Eval(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Which machinery should be used to evaluate mal forms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    /// Walk the AST directly: see `evaluator::EVAL`.
+    TreeWalker,
+    /// Compile each form to bytecode and run it on the stack machine in `vm`.
+    Bytecode,
+}
+
+impl Engine {
+    pub(crate) fn eval(
+        self,
+        ast: &MalObject,
+        env: &Rc<environment::Environment>,
+    ) -> evaluator::Result {
+        let _active = env.activate();
+        // Top-level forms belong to the current namespace.
+        let env = &namespaces::current(env);
+        match self {
+            Engine::TreeWalker => evaluator::EVAL(ast, env),
+            Engine::Bytecode => vm::EVAL(ast, env),
+        }
+    }
+}
+
 #[allow(non_snake_case)]
 pub fn READ(line: &str) -> Result {
     reader::read_str(line).map_err(Error::Read)
@@ -19,11 +59,142 @@ pub fn PRINT(result: &Result) -> printer::Result {
     printer::print(result)
 }
 
-#[allow(non_snake_case)]
-fn EVAL(ast: &MalObject, env: &Rc<environment::Environment>) -> Result {
-    evaluator::EVAL(ast, env).map_err(Error::Eval)
+pub fn rep(line: &str, env: &Rc<environment::Environment>) -> printer::Result {
+    rep_using(line, env, Engine::TreeWalker)
 }
 
-pub fn rep(line: &str, env: &Rc<environment::Environment>) -> printer::Result {
-    PRINT(&READ(line).and_then(|ast| EVAL(&ast, env)))
+pub fn rep_using(
+    line: &str,
+    env: &Rc<environment::Environment>,
+    engine: Engine,
+) -> printer::Result {
+    PRINT(&READ(line).and_then(|ast| engine.eval(&ast, env).map_err(Error::Eval)))
+}
+
+/// A mal interpreter for embedding in Rust programs.
+///
+/// ```
+/// use rust_dmr_mal::convert::FromMal;
+/// use rust_dmr_mal::interpreter::Interpreter;
+/// let mal = Interpreter::new().unwrap();
+/// mal.set_global("xs", vec![1, 2, 3]);
+/// mal.eval_str("(def! total (apply + xs))").unwrap();
+/// assert_eq!(mal.get_global::<i64>("total").unwrap(), 6);
+/// let double = mal.eval_str("(fn* (x) (* 2 x))").unwrap();
+/// let xs = mal.get_global("xs").unwrap();
+/// let doubled = mal.call("map", &[double, xs]).unwrap();
+/// assert_eq!(Vec::<i64>::from_mal(&doubled).unwrap(), vec![2, 4, 6]);
+/// ```
+pub struct Interpreter {
+    env: Rc<Environment>,
+    engine: Engine,
+    limits: Cell<Option<Limits>>,
+}
+
+impl Interpreter {
+    /// An interpreter with every capability, the prelude and `eval`.
+    pub fn new() -> std::result::Result<Self, String> {
+        Self::new_using(Engine::TreeWalker)
+    }
+
+    /// Like `new`, but evaluating everything with `engine`, starting with the
+    /// prelude.
+    pub fn new_using(engine: Engine) -> std::result::Result<Self, String> {
+        Environment::builder()
+            .with_all()
+            .with_prelude()
+            .with_eval()
+            .using(engine)
+            .build()
+            .map(|env| Self {
+                env,
+                engine,
+                limits: Cell::new(None),
+            })
+    }
+
+    /// Evaluate in an existing environment, e.g. one made by `Environment::builder`.
+    pub fn with_environment(env: Rc<Environment>) -> Self {
+        Self {
+            env,
+            engine: Engine::TreeWalker,
+            limits: Cell::new(None),
+        }
+    }
+
+    /// Evaluate with `engine` from now on, including in `eval` and files loaded
+    /// by `require`. Whatever has already been evaluated, such as the prelude,
+    /// stays as it was: `new_using` evaluates the prelude with `engine` too.
+    pub fn using(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        match self.env.get_local(&MalSymbol("eval".into())) {
+            Some(MalObject::Eval(_)) => environment::add_eval_using(&self.env, engine),
+            _ => namespaces::set_engine(&self.env, engine),
+        }
+        self
+    }
+
+    pub fn environment(&self) -> &Rc<Environment> {
+        &self.env
+    }
+
+    /// Evaluate every form in `src`, returning the value of the last one (or nil
+    /// if there are none).
+    pub fn eval_str(&self, src: &str) -> Result {
+        let forms = reader::read_all(src).map_err(Error::Read)?;
+        let _limits = self.limits.get().map(limits::scoped);
+        let mut result = MalObject::Nil;
+        for form in forms.iter() {
+            result = self.engine.eval(form, &self.env).map_err(Error::Eval)?;
+        }
+        Ok(result)
+    }
+
+    /// Call the function bound to `name` with the given arguments.
+    pub fn call(&self, name: &str, args: &[MalObject]) -> Result {
+        let callable = self.lookup(name)?;
+        let _active = self.env.activate();
+        let _limits = self.limits.get().map(limits::scoped);
+        evaluator::apply_fully(&callable, args).map_err(Error::Eval)
+    }
+
+    /// See `Environment::register`.
+    pub fn register<F>(&self, name: &str, arity: Arity, func: F)
+    where
+        F: Fn(&[MalObject]) -> evaluator::Result + 'static,
+    {
+        self.env.register(name, arity, func);
+    }
+
+    /// Send the output of `prn` and `println` to `output`.
+    pub fn set_output<W: Write + 'static>(&self, output: W) {
+        console::set_output(&self.env, output);
+    }
+
+    /// Have `readline` read from `input`.
+    pub fn set_input<R: BufRead + 'static>(&self, input: R) {
+        console::set_input(&self.env, input);
+    }
+
+    /// Limit what this interpreter's evaluations may do, instead of using the
+    /// limits set for the thread by `limits::set_limits`.
+    pub fn set_limits(&self, limits: Limits) {
+        self.limits.set(Some(limits));
+    }
+
+    pub fn set_global<T: IntoMal>(&self, name: &str, value: T) {
+        self.env.set(MalSymbol(name.into()), value.into_mal());
+    }
+
+    pub fn get_global<T: FromMal>(&self, name: &str) -> std::result::Result<T, Error> {
+        let obj = self.lookup(name)?;
+        T::from_mal(&obj).map_err(|e| Error::Eval(evaluator::Error::TypeMismatch(e)))
+    }
+
+    fn lookup(&self, name: &str) -> Result {
+        let symbol = MalSymbol(name.into());
+        self.env
+            .get(&symbol)
+            .ok_or_else(|| Error::Eval(evaluator::Error::UnknownSymbol(UnknownSymbol(symbol))))
+    }
 }
@@ -0,0 +1,130 @@
+// Conversion between JSON text and mal data, for `json-parse` and `json-stringify`.
+//
+// Objects become maps, arrays become vectors and null becomes nil. Mal only has
+// integers, so numbers with a fractional part are rejected. Going the other way,
+// keywords and symbols are written as their names, characters as one-character
+// strings, lists and vectors as arrays.
+
+use crate::types::{HashKey, MalInt, MalObject};
+use crate::{lazy, time};
+use serde_json::{Map, Number, Value};
+use std::convert::TryFrom;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Malformed JSON. The message includes the line and column.
+    Syntax(serde_json::Error),
+    NotAnInteger(Number),
+    CantEncode(String),
+    Io(serde_json::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Syntax(e) => write!(f, "malformed JSON: {}", e),
+            Error::NotAnInteger(n) => write!(f, "JSON number {} is not an integer", n),
+            Error::CantEncode(obj) => write!(f, "can't encode {} as JSON", obj),
+            Error::Io(e) => write!(f, "failed to write JSON: {}", e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Keys {
+    Strings,
+    Keywords,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Layout {
+    Compact,
+    /// One entry per line, indented by two spaces per level.
+    Pretty,
+}
+
+pub fn parse(text: &str, keys: Keys) -> Result<MalObject, Error> {
+    let value: Value = serde_json::from_str(text).map_err(Error::Syntax)?;
+    from_value(value, keys)
+}
+
+fn from_value(value: Value, keys: Keys) -> Result<MalObject, Error> {
+    Ok(match value {
+        Value::Null => MalObject::Nil,
+        Value::Bool(b) => MalObject::Bool(b),
+        Value::Number(n) => MalObject::Integer(to_integer(n)?),
+        Value::String(s) => MalObject::String(s),
+        Value::Array(elements) => MalObject::wrap_vector(
+            elements
+                .into_iter()
+                .map(|v| from_value(v, keys))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(entries) => MalObject::wrap_map(
+            entries
+                .into_iter()
+                .map(|(k, v)| {
+                    let key = match keys {
+                        Keys::Strings => HashKey::String(k),
+                        Keys::Keywords => HashKey::Keyword(k),
+                    };
+                    Ok((key, from_value(v, keys)?))
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+    })
+}
+
+// Whole numbers written with an exponent or a decimal point (`1e3`, `2.0`) are fine.
+fn to_integer(n: Number) -> Result<MalInt, Error> {
+    let integer = match (n.as_i64(), n.as_f64()) {
+        (Some(i), _) => MalInt::try_from(i).ok(),
+        (None, Some(f)) if f.fract() == 0.0 && f.abs() < MalInt::MAX as f64 => Some(f as MalInt),
+        _ => None,
+    };
+    integer.ok_or(Error::NotAnInteger(n))
+}
+
+pub fn stringify(obj: &MalObject, layout: Layout) -> Result<String, Error> {
+    let value = to_value(obj)?;
+    let result = match layout {
+        Layout::Compact => serde_json::to_string(&value),
+        Layout::Pretty => serde_json::to_string_pretty(&value),
+    };
+    result.map_err(Error::Io)
+}
+
+fn to_value(obj: &MalObject) -> Result<Value, Error> {
+    Ok(match obj {
+        MalObject::Nil => Value::Null,
+        MalObject::Bool(b) => Value::Bool(*b),
+        MalObject::Integer(i) => Value::Number((*i as i64).into()),
+        MalObject::String(s) | MalObject::Keyword(s) => Value::String(s.clone()),
+        MalObject::Char(c) => Value::String(c.to_string()),
+        MalObject::Instant(i) => Value::String(time::iso_8601(i)),
+        MalObject::Symbol(s) => Value::String(s.0.clone()),
+        MalObject::List(list) => to_array(&list.payload)?,
+        MalObject::LazySeq(_) => to_array(&lazy::realised_elements(obj, None).0)?,
+        MalObject::Vector(vec) => to_array(&vec.payload)?,
+        MalObject::Map(map) => {
+            let mut entries = Map::new();
+            for (key, value) in map.payload.iter() {
+                let key = match key {
+                    HashKey::String(s) | HashKey::Keyword(s) => s.clone(),
+                };
+                entries.insert(key, to_value(value)?);
+            }
+            Value::Object(entries)
+        }
+        _ => return Err(Error::CantEncode(obj.to_string())),
+    })
+}
+
+fn to_array(elements: &[MalObject]) -> Result<Value, Error> {
+    elements
+        .iter()
+        .map(to_value)
+        .collect::<Result<_, _>>()
+        .map(Value::Array)
+}
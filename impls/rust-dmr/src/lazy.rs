@@ -0,0 +1,320 @@
+// Lazy sequences.
+//
+// A `LazySeq` starts out holding a thunk which produces a sequence. The first
+// time anything looks inside it, the thunk is called and the result cached as
+// either "empty" or a first element and the rest of the sequence. The rest is
+// usually another lazy sequence, so a chain of them is realised one element at
+// a time and can go on forever.
+//
+// `(lazy-seq body...)` makes one whose thunk evaluates the body, and the
+// sequence functions return them when given one (see `sequences`). Primitives
+// which need a whole sequence realise it all, so they hang on infinite ones.
+//
+// Printing realises what it's about to print, up to `*print-length*` elements
+// of each sequence. If realising fails, whatever couldn't be is shown as `...`;
+// the printing primitives realise first so that such errors are thrown.
+
+use crate::evaluator::{self, Error};
+use crate::sequences::elements;
+use crate::types::{Arity, MalObject, PrimitiveFn, TypeMismatch};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+type Thunk = Rc<dyn Fn() -> evaluator::Result>;
+
+enum State {
+    Pending(Thunk),
+    // The thunk is running.
+    Realising,
+    Empty,
+    Cons(MalObject, MalObject),
+}
+
+pub struct LazySeq {
+    state: RefCell<State>,
+}
+
+impl fmt::Debug for LazySeq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LazySeq")
+    }
+}
+
+/// A lazy sequence whose elements are those of the sequence `thunk` returns.
+pub fn new<F>(thunk: F) -> MalObject
+where
+    F: Fn() -> evaluator::Result + 'static,
+{
+    wrap(State::Pending(Rc::new(thunk)))
+}
+
+/// A lazy sequence whose elements are those of the sequence `f` returns when
+/// called with no arguments.
+pub fn from_fn(f: MalObject) -> MalObject {
+    new(move || evaluator::apply_fully(&f, &[]))
+}
+
+/// A lazy sequence which is already realised, for prepending to another one
+/// without realising it.
+pub fn cons(first: MalObject, rest: MalObject) -> MalObject {
+    wrap(State::Cons(first, rest))
+}
+
+fn wrap(state: State) -> MalObject {
+    MalObject::LazySeq(Rc::new(LazySeq {
+        state: RefCell::new(state),
+    }))
+}
+
+pub fn is_lazy(obj: &MalObject) -> bool {
+    matches!(obj, MalObject::LazySeq(_))
+}
+
+// The split of a realised sequence into its first element and the rest.
+type Split = Option<(MalObject, MalObject)>;
+
+impl LazySeq {
+    /// The first element and the rest, or `None` if the sequence is empty,
+    /// realising it if need be.
+    pub fn realise(&self) -> evaluator::Result<Split> {
+        if let Some(split) = self.realised() {
+            return Ok(split);
+        }
+        let thunk = match self.state.replace(State::Realising) {
+            State::Pending(thunk) => thunk,
+            _ => return Err(Error::LazyCycle),
+        };
+        // A thunk may itself return an unrealised lazy sequence, and so on.
+        // Follow such chains in a loop rather than recursing, and give every
+        // sequence in the chain the same contents.
+        let mut chain: Vec<(Rc<LazySeq>, Thunk)> = Vec::new();
+        let mut current = thunk.clone();
+        let result = loop {
+            let value = match current() {
+                Ok(value) => value,
+                Err(e) => break Err(e),
+            };
+            match value {
+                MalObject::LazySeq(next) => {
+                    if let Some(split) = next.realised() {
+                        break Ok(split);
+                    }
+                    match next.state.replace(State::Realising) {
+                        State::Pending(thunk) => {
+                            current = thunk.clone();
+                            chain.push((next, thunk));
+                        }
+                        _ => break Err(Error::LazyCycle),
+                    }
+                }
+                other => break split(&other),
+            }
+        };
+        let settle = |seq: &LazySeq, thunk: Thunk| {
+            seq.state.replace(match &result {
+                Ok(Some((first, rest))) => State::Cons(first.clone(), rest.clone()),
+                Ok(None) => State::Empty,
+                // Leave it to be tried again.
+                Err(_) => State::Pending(thunk),
+            });
+        };
+        settle(self, thunk);
+        for (seq, thunk) in chain {
+            settle(&seq, thunk);
+        }
+        result
+    }
+
+    /// As `realise`, but `None` if that would mean calling the thunk.
+    pub fn realised(&self) -> Option<Split> {
+        match &*self.state.borrow() {
+            State::Empty => Some(None),
+            State::Cons(first, rest) => Some(Some((first.clone(), rest.clone()))),
+            State::Pending(_) | State::Realising => None,
+        }
+    }
+}
+
+// The first element and the rest of an eagerly evaluated sequence.
+fn split(obj: &MalObject) -> evaluator::Result<Split> {
+    let elements = elements(obj)?;
+    Ok(elements
+        .split_first()
+        .map(|(first, rest)| (first.clone(), MalObject::wrap_list(rest.to_vec()))))
+}
+
+// Dropping the head of a long realised chain would otherwise drop each
+// sequence from inside the previous one's destructor, and overflow the stack.
+impl Drop for LazySeq {
+    fn drop(&mut self) {
+        let mut rest = match self.state.get_mut() {
+            State::Cons(_, rest) => std::mem::replace(rest, MalObject::Nil),
+            _ => return,
+        };
+        while let MalObject::LazySeq(seq) = rest {
+            rest = match Rc::try_unwrap(seq) {
+                Ok(mut seq) => match seq.state.get_mut() {
+                    State::Cons(_, rest) => std::mem::replace(rest, MalObject::Nil),
+                    _ => break,
+                },
+                Err(_) => break,
+            };
+        }
+    }
+}
+
+/// A position in a sequence of any kind, which realises lazy sequences only as
+/// far as it's advanced.
+#[derive(Clone)]
+pub struct Cursor {
+    seq: MalObject,
+    index: usize,
+}
+
+impl Cursor {
+    pub fn new(obj: &MalObject) -> evaluator::Result<Self> {
+        let seq = match obj {
+            MalObject::Nil | MalObject::List(_) | MalObject::Vector(_) | MalObject::LazySeq(_) => {
+                obj.clone()
+            }
+            _ => MalObject::wrap_list(elements(obj)?.into_owned()),
+        };
+        Ok(Self { seq, index: 0 })
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            seq: MalObject::Nil,
+            index: 0,
+        }
+    }
+
+    /// The next element, or `None` at the end.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> evaluator::Result<Option<MalObject>> {
+        loop {
+            let next = match &self.seq {
+                MalObject::Nil => return Ok(None),
+                MalObject::List(_) | MalObject::Vector(_) => {
+                    let element = self.seq.as_seq()?.get(self.index).cloned();
+                    self.index += 1;
+                    return Ok(element);
+                }
+                MalObject::LazySeq(seq) => match seq.realise()? {
+                    Some((first, rest)) => {
+                        self.seq = rest;
+                        self.index = 0;
+                        return Ok(Some(first));
+                    }
+                    None => MalObject::Nil,
+                },
+                // The rest of a lazy sequence may be any kind of collection.
+                other => MalObject::wrap_list(elements(other)?.into_owned()),
+            };
+            self.seq = next;
+            self.index = 0;
+        }
+    }
+
+    /// Whatever hasn't been visited yet, as a sequence.
+    pub fn rest(self) -> MalObject {
+        match self.seq.as_seq() {
+            Ok(elements) if self.index > 0 => {
+                let remaining = elements.get(self.index..).unwrap_or_default();
+                MalObject::wrap_list(remaining.to_vec())
+            }
+            _ => self.seq,
+        }
+    }
+}
+
+/// Every element of `obj`, realising it completely.
+pub fn realise_all(obj: &MalObject) -> evaluator::Result<Vec<MalObject>> {
+    let mut cursor = Cursor::new(obj)?;
+    let mut result = Vec::new();
+    while let Some(x) = cursor.next()? {
+        result.push(x);
+    }
+    Ok(result)
+}
+
+/// Realise lazy sequences in `obj`, and anything inside it: the first `limit`
+/// elements of each, and whether there are any more, or all of them.
+pub fn realise_deeply(obj: &MalObject, limit: Option<usize>) -> evaluator::Result<()> {
+    let shown = |len: usize| limit.map_or(len, |limit| limit.min(len));
+    match obj {
+        MalObject::List(_) | MalObject::Vector(_) => {
+            let elements = obj.as_seq()?;
+            for x in &elements[..shown(elements.len())] {
+                realise_deeply(x, limit)?;
+            }
+        }
+        MalObject::Map(map) => {
+            for value in map.payload.values().take(shown(map.payload.len())) {
+                realise_deeply(value, limit)?;
+            }
+        }
+        MalObject::LazySeq(_) => {
+            let mut cursor = Cursor::new(obj)?;
+            let mut count = 0;
+            while limit != Some(count) {
+                match cursor.next()? {
+                    Some(x) => realise_deeply(&x, limit)?,
+                    None => return Ok(()),
+                }
+                count += 1;
+            }
+            cursor.next()?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Up to `limit` of the elements realised so far, and whether there are any
+/// more, realised or not.
+pub(crate) fn realised_elements(obj: &MalObject, limit: Option<usize>) -> (Vec<MalObject>, bool) {
+    let limit = limit.unwrap_or(usize::MAX);
+    let mut result = Vec::new();
+    let mut current = obj.clone();
+    loop {
+        current = match &current {
+            MalObject::LazySeq(seq) => match seq.realised() {
+                Some(Some(_)) if result.len() == limit => return (result, true),
+                Some(Some((first, rest))) => {
+                    result.push(first);
+                    rest
+                }
+                Some(None) => return (result, false),
+                None => return (result, true),
+            },
+            MalObject::Nil => return (result, false),
+            other => {
+                let elements = elements(other).unwrap_or_default();
+                let shown = elements.len().min(limit - result.len());
+                result.extend_from_slice(&elements[..shown]);
+                return (result, shown < elements.len());
+            }
+        }
+    }
+}
+
+pub(crate) const DOALL: PrimitiveFn = PrimitiveFn {
+    name: "doall",
+    fn_ptr: |args| {
+        realise_all(&args[0])?;
+        Ok(args[0].clone())
+    },
+    arity: Arity::exactly(1),
+};
+
+// Whether the thunk has been called: only the first element need be realised.
+pub(crate) const REALIZED_TEST: PrimitiveFn = PrimitiveFn {
+    name: "realized?",
+    fn_ptr: |args| match &args[0] {
+        MalObject::LazySeq(seq) => Ok(MalObject::Bool(seq.realised().is_some())),
+        _ => Err(TypeMismatch::NotASequence.into()),
+    },
+    arity: Arity::exactly(1),
+};
@@ -2,14 +2,33 @@
 extern crate lazy_static;
 
 pub mod cmdline;
+pub mod compiler;
+pub mod console;
+pub mod convert;
+pub mod edn;
 pub mod environment;
 pub mod evaluator;
+pub mod fs;
+pub mod gc;
 pub mod interpreter;
+pub mod json;
+pub mod lazy;
+pub mod limits;
+pub mod namespaces;
 pub mod prelude;
 pub mod printer;
+pub mod process;
+pub mod random;
 pub mod reader;
+pub mod reducers;
+pub mod sequences;
+#[cfg(feature = "serde")]
+pub mod serialization;
 pub mod special_forms;
+pub mod text;
+pub mod time;
 pub mod types;
+pub mod vm;
 
 mod core;
 mod strings;
@@ -0,0 +1,204 @@
+// Bounds on how much work a single top-level evaluation may do, so that
+// embedders can run untrusted snippets without hanging or overflowing the
+// native stack. The same machinery lets Ctrl-C abort the current evaluation.
+//
+// The budget is reset whenever evaluation starts from scratch (i.e. at nesting
+// depth zero). Once a limit is exceeded, or an interrupt noticed, every
+// subsequent step fails too, so a runaway loop can't escape by catching the
+// error with `try*`.
+
+use crate::evaluator::{Error, Result};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// By default only the depth is limited, so that runaway recursion is reported
+/// as an error rather than overflowing the native stack.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Maximum number of evaluation steps: iterations of the tree-walker's
+    /// `EVAL` loop, or instructions executed by the VM.
+    pub max_steps: Option<u64>,
+    /// Maximum depth of nested evaluations and VM call frames.
+    pub max_depth: Option<usize>,
+    /// Wall-clock time allowed.
+    pub timeout: Option<Duration>,
+}
+
+// About half the depth at which the main thread's 8 MiB stack overflows when
+// recursing through a primitive like `map`, the hungriest route we know of.
+// Unoptimised builds need several times as much stack for each level.
+const DEFAULT_MAX_DEPTH: usize = if cfg!(debug_assertions) { 300 } else { 2_000 };
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_steps: None,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            timeout: None,
+        }
+    }
+}
+
+struct Budget {
+    limits: Limits,
+    steps: u64,
+    depth: usize,
+    deadline: Option<Instant>,
+    interrupted: bool,
+}
+
+thread_local! {
+    static BUDGET: RefCell<Budget> = RefCell::new(Budget {
+        limits: Limits::default(),
+        steps: 0,
+        depth: 0,
+        deadline: None,
+        interrupted: false,
+    });
+}
+
+lazy_static! {
+    // Set from a signal handler, or another thread, so it can't live in BUDGET.
+    static ref INTERRUPT: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+}
+
+// Looking at the clock is comparatively expensive, so only do so every this
+// many steps.
+const CLOCK_INTERVAL: u64 = 1024;
+
+/// Limit all subsequent evaluations on this thread.
+pub fn set_limits(limits: Limits) {
+    BUDGET.with(|budget| budget.borrow_mut().limits = limits);
+}
+
+pub fn limits() -> Limits {
+    BUDGET.with(|budget| budget.borrow().limits)
+}
+
+// Puts the previous limits back when dropped.
+pub(crate) struct Scoped {
+    previous: Limits,
+}
+
+/// Limit evaluations on this thread until the result is dropped.
+pub(crate) fn scoped(limits: Limits) -> Scoped {
+    let previous = BUDGET.with(|budget| std::mem::replace(&mut budget.borrow_mut().limits, limits));
+    Scoped { previous }
+}
+
+impl Drop for Scoped {
+    fn drop(&mut self) {
+        set_limits(self.previous);
+    }
+}
+
+/// Abort the evaluation currently in progress. Safe to call from any thread.
+pub fn interrupt() {
+    INTERRUPT.store(true, Ordering::Relaxed);
+}
+
+/// Make SIGINT (i.e. Ctrl-C) call `interrupt` rather than kill the process.
+pub fn interrupt_on_sigint() -> std::io::Result<()> {
+    signal_hook::flag::register(signal_hook::SIGINT, INTERRUPT.clone()).map(|_| ())
+}
+
+/// Account for one evaluation step.
+pub(crate) fn step() -> Result<()> {
+    BUDGET.with(|budget| {
+        let mut budget = budget.borrow_mut();
+        if budget.interrupted || INTERRUPT.swap(false, Ordering::Relaxed) {
+            budget.interrupted = true;
+            return Err(Error::Interrupted);
+        }
+        budget.steps += 1;
+        if let Some(max_steps) = budget.limits.max_steps {
+            if budget.steps > max_steps {
+                return Err(Error::StepLimitExceeded(max_steps));
+            }
+        }
+        if let (Some(deadline), Some(timeout)) = (budget.deadline, budget.limits.timeout) {
+            if budget.steps % CLOCK_INTERVAL == 0 && Instant::now() > deadline {
+                // Make sure we notice straight away next time.
+                budget.steps -= 1;
+                return Err(Error::Timeout(timeout));
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Fail if the evaluation has been interrupted or has run out of time, without
+/// using up a step. For primitives which block, like `sleep`.
+pub(crate) fn check() -> Result<()> {
+    BUDGET.with(|budget| {
+        let mut budget = budget.borrow_mut();
+        if budget.interrupted || INTERRUPT.swap(false, Ordering::Relaxed) {
+            budget.interrupted = true;
+            return Err(Error::Interrupted);
+        }
+        if let (Some(deadline), Some(timeout)) = (budget.deadline, budget.limits.timeout) {
+            if Instant::now() > deadline {
+                // Make sure the next step looks at the clock.
+                budget.steps += CLOCK_INTERVAL - 1 - budget.steps % CLOCK_INTERVAL;
+                return Err(Error::Timeout(timeout));
+            }
+        }
+        Ok(())
+    })
+}
+
+fn check_depth() -> Result<()> {
+    BUDGET.with(|budget| {
+        let budget = budget.borrow();
+        match budget.limits.max_depth {
+            Some(max_depth) if budget.depth >= max_depth => {
+                Err(Error::DepthLimitExceeded(max_depth))
+            }
+            _ => Ok(()),
+        }
+    })
+}
+
+// Held for the duration of a nested evaluation. The VM also uses this to
+// account for its call frames.
+pub(crate) struct Nesting {
+    levels: usize,
+}
+
+impl Nesting {
+    pub(crate) fn enter() -> Result<Nesting> {
+        check_depth()?;
+        BUDGET.with(|budget| {
+            let mut budget = budget.borrow_mut();
+            if budget.depth == 0 {
+                // Forget about any Ctrl-C pressed while we weren't evaluating.
+                INTERRUPT.store(false, Ordering::Relaxed);
+                budget.interrupted = false;
+                budget.steps = 0;
+                budget.deadline = budget.limits.timeout.map(|t| Instant::now() + t);
+            }
+            budget.depth += 1;
+        });
+        Ok(Nesting { levels: 1 })
+    }
+
+    pub(crate) fn deepen(&mut self) -> Result<()> {
+        check_depth()?;
+        BUDGET.with(|budget| budget.borrow_mut().depth += 1);
+        self.levels += 1;
+        Ok(())
+    }
+
+    pub(crate) fn shallower(&mut self) {
+        BUDGET.with(|budget| budget.borrow_mut().depth -= 1);
+        self.levels -= 1;
+    }
+}
+
+impl Drop for Nesting {
+    fn drop(&mut self) {
+        BUDGET.with(|budget| budget.borrow_mut().depth -= self.levels);
+    }
+}
@@ -0,0 +1,382 @@
+// Namespaces.
+//
+// Each namespace is an environment of its own, so two libraries can both define
+// `helper` without clobbering each other. The root environment is the `user`
+// namespace and every other namespace is a child of it: code anywhere sees the
+// primitives, the prelude and whatever `user` defines, but a namespace's own
+// definitions stay in that namespace. `foo/bar` names `bar` in the namespace
+// `foo`, or in the namespace which `foo` is an alias for. Definitions made with
+// `(def! ^:private name ...)` can't be named from other namespaces.
+//
+// Definitions made with `(def! ^:dynamic name ...)` can be rebound by `binding`
+// for as long as its body is being evaluated.
+//
+// Top-level forms, and those passed to `eval`, are evaluated in the current
+// namespace, which `in-ns` changes. `require` loads a namespace from a file the
+// first time it is asked for: `foo.bar-baz` lives in `foo/bar_baz.mal` in one of
+// the directories on the load path, or else in the working directory.
+
+use crate::environment::{Capability, Environment, UnknownSymbol};
+use crate::evaluator;
+use crate::interpreter::Engine;
+use crate::types::{Arity, MalObject, MalSymbol};
+use crate::{fs, reader};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+
+pub const USER: &str = "user";
+
+/// Directories to look for required namespaces in, separated as in `PATH`.
+pub const LOAD_PATH_VARIABLE: &str = "MAL_PATH";
+
+const CURRENT: &str = "*ns*";
+
+#[derive(Debug)]
+pub enum Error {
+    UnknownNamespace(String),
+    NotFound { name: String, file: PathBuf },
+    CyclicLoad(String),
+    BadRequire(MalObject),
+    NotDynamic(MalSymbol),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownNamespace(name) => write!(f, "no namespace {}", name),
+            Error::NotFound { name, file } => write!(
+                f,
+                "can't find {}: no {} on the load path",
+                name,
+                file.display()
+            ),
+            Error::CyclicLoad(name) => write!(f, "cyclic require: {} is still loading", name),
+            Error::BadRequire(spec) => write!(f, "bad require spec {}", spec),
+            Error::NotDynamic(name) => write!(f, "can't bind {}: it isn't dynamic", name),
+        }
+    }
+}
+
+impl From<Error> for evaluator::Error {
+    fn from(e: Error) -> Self {
+        evaluator::Error::Namespace(e)
+    }
+}
+
+pub(crate) struct Namespace {
+    name: String,
+    // Alias => namespace name.
+    aliases: RefCell<HashMap<String, String>>,
+    private: RefCell<HashSet<MalSymbol>>,
+    dynamic: RefCell<HashSet<MalSymbol>>,
+    registry: Rc<Registry>,
+}
+
+// Shared by all the namespaces under one root environment.
+struct Registry {
+    // Only known once something has been evaluated at the top level.
+    root: RefCell<Weak<Environment>>,
+    // Every namespace but `user`.
+    namespaces: RefCell<HashMap<String, Rc<Environment>>>,
+    current: RefCell<String>,
+    // Namespaces part way through being loaded by `require`.
+    loading: RefCell<HashSet<String>>,
+    load_path: RefCell<Vec<PathBuf>>,
+    engine: Cell<Engine>,
+}
+
+impl Registry {
+    fn root(&self) -> Result<Rc<Environment>, Error> {
+        self.root
+            .borrow()
+            .upgrade()
+            .ok_or_else(|| Error::UnknownNamespace(USER.into()))
+    }
+
+    fn find(&self, name: &str) -> Option<Rc<Environment>> {
+        match name {
+            USER => self.root.borrow().upgrade(),
+            _ => self.namespaces.borrow().get(name).cloned(),
+        }
+    }
+
+    fn current(&self) -> Result<Rc<Environment>, Error> {
+        let name = self.current.borrow().clone();
+        self.find(&name).ok_or(Error::UnknownNamespace(name))
+    }
+
+    // Make `name` the current namespace, creating it if need be.
+    fn switch(self: &Rc<Self>, name: &str) -> Result<Rc<Environment>, Error> {
+        let root = self.root()?;
+        let env = match self.find(name) {
+            Some(env) => env,
+            None => {
+                let namespace = Namespace::new(name, self);
+                let env = Environment::for_namespace(&root, namespace);
+                self.namespaces
+                    .borrow_mut()
+                    .insert(name.into(), env.clone());
+                env
+            }
+        };
+        self.current.replace(name.into());
+        root.set(MalSymbol(CURRENT.into()), MalObject::new_symbol(name));
+        Ok(env)
+    }
+
+    fn locate(&self, name: &str) -> Result<PathBuf, Error> {
+        let file: PathBuf = format!("{}.mal", name.replace('.', "/").replace('-', "_")).into();
+        self.load_path
+            .borrow()
+            .iter()
+            .map(|dir| dir.join(&file))
+            .chain(std::iter::once(file.clone()))
+            .find(|path| path.is_file())
+            .ok_or_else(|| Error::NotFound {
+                name: name.into(),
+                file,
+            })
+    }
+
+    // Evaluate the file defining `name` in that namespace, unless it exists already.
+    fn load(self: &Rc<Self>, name: &str) -> evaluator::Result<()> {
+        if self.loading.borrow().contains(name) {
+            return Err(Error::CyclicLoad(name.into()).into());
+        }
+        if self.find(name).is_some() {
+            return Ok(());
+        }
+        let path = self.locate(name)?;
+        let text = fs::attempt("read", &path, std::fs::read_to_string(&path))?;
+        let forms = reader::read_all(&text).map_err(evaluator::Error::ReadError)?;
+        let previous = self.current.borrow().clone();
+        let root = self.root()?;
+        self.loading.borrow_mut().insert(name.into());
+        self.switch(name)?;
+        let engine = self.engine.get();
+        let result = forms
+            .iter()
+            .try_for_each(|form| engine.eval(form, &root).map(drop));
+        self.loading.borrow_mut().remove(name);
+        self.switch(&previous)?;
+        if result.is_err() {
+            // So that requiring it again tries again.
+            self.namespaces.borrow_mut().remove(name);
+        }
+        result
+    }
+}
+
+impl Namespace {
+    fn new(name: &str, registry: &Rc<Registry>) -> Rc<Self> {
+        Rc::new(Self {
+            name: name.into(),
+            aliases: RefCell::new(HashMap::new()),
+            private: RefCell::new(HashSet::new()),
+            dynamic: RefCell::new(HashSet::new()),
+            registry: registry.clone(),
+        })
+    }
+
+    pub(crate) fn declare_dynamic(&self, name: &str) {
+        self.dynamic.borrow_mut().insert(MalSymbol(name.into()));
+    }
+}
+
+// The namespace whose environment `env` is, or is nested in.
+fn home(mut env: &Environment) -> Option<&Rc<Namespace>> {
+    loop {
+        if let Some(namespace) = env.namespace() {
+            return Some(namespace);
+        }
+        env = env.parent()?;
+    }
+}
+
+/// Set up the `user` namespace for a new root environment whose bindings are
+/// `data`, adding `in-ns` and, given `Capability::IoRead`, `require`.
+pub(crate) fn install(
+    data: &mut HashMap<MalSymbol, MalObject>,
+    capabilities: &[Capability],
+) -> Rc<Namespace> {
+    let registry = Rc::new(Registry {
+        root: RefCell::new(Weak::new()),
+        namespaces: RefCell::new(HashMap::new()),
+        current: RefCell::new(USER.into()),
+        loading: RefCell::new(HashSet::new()),
+        load_path: RefCell::new(Vec::new()),
+        engine: Cell::new(Engine::TreeWalker),
+    });
+    let mut define = |name: &str, value| data.insert(MalSymbol(name.into()), value);
+    define(CURRENT, MalObject::new_symbol(USER));
+    let registry_ = registry.clone();
+    define(
+        "in-ns",
+        MalObject::native("in-ns", Arity::exactly(1), move |args| {
+            registry_.switch(args[0].as_symbol()?.as_str())?;
+            Ok(args[0].clone())
+        }),
+    );
+    if capabilities.contains(&Capability::IoRead) {
+        let registry_ = registry.clone();
+        define(
+            "require",
+            MalObject::native("require", Arity::at_least(0), move |args| {
+                for spec in args {
+                    require(&registry_, spec)?;
+                }
+                Ok(MalObject::Nil)
+            }),
+        );
+    }
+    Namespace::new(USER, &registry)
+}
+
+// `foo.bar`, or `[foo.bar :as b :refer [x y]]`.
+fn require(registry: &Rc<Registry>, spec: &MalObject) -> evaluator::Result<()> {
+    let bad = || Error::BadRequire(spec.clone());
+    let (name, options) = match spec {
+        MalObject::Symbol(name) => (name, &[][..]),
+        _ => match spec.as_seq().map_err(|_| bad())? {
+            [MalObject::Symbol(name), options @ ..] if options.len() % 2 == 0 => (name, options),
+            _ => return Err(bad().into()),
+        },
+    };
+    registry.load(name.as_str())?;
+    let here = registry.current()?;
+    let required = registry
+        .find(name.as_str())
+        .ok_or_else(|| Error::UnknownNamespace(name.to_string()))?;
+    for option in options.chunks(2) {
+        match (&option[0], &option[1]) {
+            (MalObject::Keyword(key), MalObject::Symbol(alias)) if key == "as" => {
+                home(&here)
+                    .unwrap()
+                    .aliases
+                    .borrow_mut()
+                    .insert(alias.to_string(), name.to_string());
+            }
+            (MalObject::Keyword(key), names) if key == "refer" => {
+                for referred in names.to_seq().map_err(|_| bad())?.iter() {
+                    let referred = referred.as_symbol()?;
+                    let value = public(&required, referred).ok_or_else(|| {
+                        let qualified = MalSymbol(format!("{}/{}", name, referred));
+                        evaluator::Error::UnknownSymbol(UnknownSymbol(qualified))
+                    })?;
+                    here.set(referred.clone(), value);
+                }
+            }
+            _ => return Err(bad().into()),
+        }
+    }
+    Ok(())
+}
+
+// What `name` is bound to in the namespace `env`, unless it's private.
+fn public(env: &Environment, name: &MalSymbol) -> Option<MalObject> {
+    let namespace = env.namespace()?;
+    match namespace.private.borrow().contains(name) {
+        true => None,
+        false => env.get_local(name),
+    }
+}
+
+/// The environment top-level forms evaluated in `env` should really be
+/// evaluated in: that of the current namespace, if `env` is a namespace.
+pub(crate) fn current(env: &Rc<Environment>) -> Rc<Environment> {
+    let registry = match env.namespace() {
+        Some(namespace) => &namespace.registry,
+        None => return env.clone(),
+    };
+    if env.parent().is_none() {
+        registry.root.replace(Rc::downgrade(env));
+    }
+    registry.current().unwrap_or_else(|_| env.clone())
+}
+
+/// Look up a qualified symbol such as `foo/bar` from `env`. The private
+/// definitions of a namespace are only visible from inside it.
+pub(crate) fn resolve(env: &Environment, symbol: &MalSymbol) -> Option<MalObject> {
+    let (target, name) = qualified(env, symbol)?;
+    target.get_local(&name)
+}
+
+// The namespace a qualified symbol refers to and the name within it, provided
+// that name is visible from `env`.
+fn qualified(env: &Environment, symbol: &MalSymbol) -> Option<(Rc<Environment>, MalSymbol)> {
+    let (prefix, name) = split(symbol.as_str())?;
+    let home = home(env)?;
+    let target = match home.aliases.borrow().get(prefix) {
+        Some(full) => home.registry.find(full),
+        None => home.registry.find(prefix),
+    }?;
+    let name = MalSymbol(name.into());
+    let visible = match target.namespace() {
+        Some(namespace) => {
+            namespace.name == home.name || !namespace.private.borrow().contains(&name)
+        }
+        None => false,
+    };
+    match visible {
+        true => Some((target, name)),
+        false => None,
+    }
+}
+
+/// The environment in which the dynamic variable `symbol` is defined, as seen
+/// from `env`, along with its unqualified name.
+pub(crate) fn dynamic_var(
+    env: &Rc<Environment>,
+    symbol: &MalSymbol,
+) -> evaluator::Result<(Rc<Environment>, MalSymbol)> {
+    let (owner, name) = match env.owner(symbol) {
+        Some(owner) => (owner, symbol.clone()),
+        None => qualified(env, symbol)
+            .filter(|(target, name)| target.get_local(name).is_some())
+            .ok_or_else(|| evaluator::Error::UnknownSymbol(UnknownSymbol(symbol.clone())))?,
+    };
+    match home(&owner) {
+        Some(namespace) if namespace.dynamic.borrow().contains(&name) => Ok((owner, name)),
+        _ => Err(Error::NotDynamic(symbol.clone()).into()),
+    }
+}
+
+// `foo/bar` => ("foo", "bar"). The division function `/` is not qualified.
+fn split(symbol: &str) -> Option<(&str, &str)> {
+    match symbol.find('/') {
+        Some(i) if i > 0 && i + 1 < symbol.len() => Some((&symbol[..i], &symbol[i + 1..])),
+        _ => None,
+    }
+}
+
+/// Stop `name` being visible from other namespaces than the one `env` is in.
+pub(crate) fn make_private(env: &Environment, name: &MalSymbol) {
+    if let Some(namespace) = home(env) {
+        namespace.private.borrow_mut().insert(name.clone());
+    }
+}
+
+/// Let `binding` rebind `name`, which is defined in `env`.
+pub(crate) fn make_dynamic(env: &Environment, name: &MalSymbol) {
+    if let Some(namespace) = home(env) {
+        namespace.dynamic.borrow_mut().insert(name.clone());
+    }
+}
+
+/// The engine `require` should evaluate files with.
+pub(crate) fn set_engine(env: &Environment, engine: Engine) {
+    if let Some(namespace) = home(env) {
+        namespace.registry.engine.set(engine);
+    }
+}
+
+/// Have `require` look in `dir`, after any directories added before it.
+pub fn add_to_load_path<P: AsRef<Path>>(env: &Environment, dir: P) {
+    if let Some(namespace) = home(env) {
+        let dir = dir.as_ref().to_path_buf();
+        namespace.registry.load_path.borrow_mut().push(dir);
+    }
+}
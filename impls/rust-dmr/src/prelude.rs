@@ -1,5 +1,7 @@
 pub const PRELUDE: &str = r#"
 (def! not (fn* (a) (if a false true)))
 (def! load-file (fn* (f) (do (map eval (rest (read-string (str "(do " (slurp f) "\nnil)")))) nil)))
+(defmacro! with-out-str (fn* (& body) (list '-with-out-str (list 'fn* '() (cons 'do (concat body '(nil)))))))
+(defmacro! ns (fn* (name & clauses) (cons 'do (cons (list 'in-ns (list 'quote name)) (map (fn* (clause) (if (= (first clause) :require) (cons 'require (map (fn* (spec) (list 'quote spec)) (rest clause))) (throw (str "ns: unknown clause " (first clause))))) clauses)))))
 (defmacro! cond (fn* (& xs) (if (> (count xs) 0) (list 'if (first xs) (if (> (count xs) 1) (nth xs 1) (throw "odd number of forms to cond")) (cons 'cond (rest (rest xs)))))))
 "#;
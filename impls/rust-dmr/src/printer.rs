@@ -1,7 +1,24 @@
-use crate::types::{Closure, HashKey, MalObject};
-use crate::{interpreter, reader, strings, types};
+use crate::types::{Closure, HashKey, MalObject, MalSymbol};
+use crate::{environment, evaluator, interpreter, lazy, reader, strings, time, types};
 use std::fmt;
 
+pub(crate) const PRINT_LENGTH: &str = "*print-length*";
+
+/// The most elements of any one list, vector, map or lazy sequence to print,
+/// after which the rest are shown as `...`. This is what `*print-length*` is
+/// in the environment being evaluated in, or else the last one which was,
+/// including any `binding` of it.
+pub fn print_length() -> Option<usize> {
+    let root = environment::active_root()?;
+    let limit = root.get_local(&MalSymbol(PRINT_LENGTH.into()))?;
+    limit.as_int().ok().map(|n| n.max(0) as usize)
+}
+
+/// Realise whatever part of any lazy sequences in `obj` would be printed.
+pub(crate) fn realise(obj: &MalObject) -> evaluator::Result<()> {
+    lazy::realise_deeply(obj, print_length())
+}
+
 pub enum Outcome {
     String(String),
     Empty,
@@ -12,10 +29,13 @@ pub fn print(result: &interpreter::Result) -> Result {
     use interpreter::Error::*;
     use reader::Error::*;
     match result {
-        Ok(obj) => Ok(Outcome::String(pr_str(
-            &obj,
-            PrintMode::ReadableRepresentation,
-        ))),
+        Ok(obj) => match realise(obj) {
+            Ok(()) => Ok(Outcome::String(pr_str(
+                obj,
+                PrintMode::ReadableRepresentation,
+            ))),
+            Err(e) => Err(format!("{}", e)),
+        },
         Err(Read(ReadComment)) => Ok(Outcome::Empty),
         Err(Read(e)) => Err(format!("{}", e)),
         Err(Eval(e)) => Err(format!("{}", e)),
@@ -37,6 +57,15 @@ pub(crate) fn pr_str(object: &MalObject, mode: PrintMode) -> String {
             output.push(')');
             output
         }
+        MalObject::LazySeq(_) => {
+            // Printing can't fail: if realising does, show what we have and `...`.
+            let _ = lazy::realise_deeply(object, print_length());
+            let (elements, more) = lazy::realised_elements(object, print_length());
+            let mut output: String = "(".into();
+            write_elements(&mut output, &elements, more, mode).unwrap();
+            output.push(')');
+            output
+        }
         MalObject::Vector(x) => {
             let mut output: String = "[".into();
             write_sequence(&mut output, &x.payload, mode).unwrap();
@@ -49,6 +78,18 @@ pub(crate) fn pr_str(object: &MalObject, mode: PrintMode) -> String {
             output.push('}');
             output
         }
+        MalObject::Char(c) => match mode {
+            PrintMode::ReadableRepresentation => object.to_string(),
+            PrintMode::Directly => c.to_string(),
+        },
+        MalObject::Regex(re) => match mode {
+            PrintMode::ReadableRepresentation => object.to_string(),
+            PrintMode::Directly => re.as_str().to_string(),
+        },
+        MalObject::Instant(instant) => match mode {
+            PrintMode::ReadableRepresentation => object.to_string(),
+            PrintMode::Directly => time::iso_8601(instant),
+        },
         _ => format!("{}", object),
     }
 }
@@ -88,18 +129,34 @@ impl fmt::Display for types::MalSymbol {
 }
 
 fn write_sequence(f: &mut impl fmt::Write, seq: &[MalObject], mode: PrintMode) -> fmt::Result {
-    let mut iter = seq.iter().peekable();
+    let shown = print_length().map_or(seq.len(), |limit| limit.min(seq.len()));
+    write_elements(f, &seq[..shown], shown < seq.len(), mode)
+}
+
+// Separated by spaces, and followed by `...` if there are `more`.
+fn write_elements(
+    f: &mut impl fmt::Write,
+    elements: &[MalObject],
+    more: bool,
+    mode: PrintMode,
+) -> fmt::Result {
+    let mut iter = elements.iter().peekable();
     while let Some(obj) = iter.next() {
         write!(f, "{}", pr_str(obj, mode))?;
         if iter.peek().is_some() {
             write!(f, " ")?;
         }
     }
+    if more {
+        let separator = if elements.is_empty() { "" } else { " " };
+        write!(f, "{}...", separator)?;
+    }
     Ok(())
 }
 
 fn write_map(f: &mut impl fmt::Write, map: &types::MalMap, mode: PrintMode) -> fmt::Result {
-    let mut iter = map.payload.iter().peekable();
+    let limit = print_length().unwrap_or(usize::MAX);
+    let mut iter = map.payload.iter().take(limit).peekable();
     while let Some((key, value)) = iter.next() {
         write!(
             f,
@@ -111,6 +168,10 @@ fn write_map(f: &mut impl fmt::Write, map: &types::MalMap, mode: PrintMode) -> f
             write!(f, " ")?;
         }
     }
+    if map.payload.len() > limit {
+        let separator = if limit == 0 { "" } else { " " };
+        write!(f, "{}...", separator)?;
+    }
     Ok(())
 }
 
@@ -161,15 +222,21 @@ impl fmt::Display for MalObject {
             Integer(x) => write!(f, "{}", x),
             Bool(x) => write!(f, "{}", x),
             String(x) => write!(f, "{:?}", x),
+            Char(x) => write!(f, "{}", strings::char_repr(*x)),
             Symbol(x) => write!(f, "{}", x),
             Keyword(x) => write!(f, ":{}", x),
             List(x) => write!(f, "{}", x),
             Vector(x) => write!(f, "{}", x),
             Map(x) => write!(f, "{}", x),
-            Primitive(x) => write!(f, "{}", x.payload.name),
+            Primitive(x) => write!(f, "{}", x.payload.name()),
             Closure(x) => write!(f, "{}", x),
             Eval(_) => write!(f, "eval"),
             Atom(x) => write!(f, "{}", x),
+            Process(x) => write!(f, "#<process {}>", x.pid()),
+            Regex(x) => write!(f, "#\"{}\"", x.as_str().replace('"', "\\\"")),
+            Instant(x) => write!(f, "#inst \"{}\"", time::iso_8601(x)),
+            LazySeq(_) => write!(f, "{}", pr_str(self, PrintMode::ReadableRepresentation)),
+            Reduced(x) => write!(f, "#reduced {}", x),
         }
     }
 }
@@ -0,0 +1,356 @@
+// Running other programs, and controlling this one, for using mal as a
+// scripting language.
+//
+// `sh` runs a command to completion and collects its output. `spawn` starts one
+// and returns a `Process` handle, so that its output can be read a line at a
+// time while it runs. Both take the command and its arguments as strings,
+// optionally followed by a map of options:
+//   :in, a string to write to the command's standard input;
+//   :dir, the working directory to run it in;
+//   :env, a map of environment variables to set, or to remove if nil.
+//
+// Failing to start a command is reported like a file system error on the
+// command's path.
+//
+// Waiting for a command, or for a line of its output, gives up if the
+// evaluation is interrupted or times out (see `limits`). An interrupted `sh`
+// kills its command; a process from `spawn` is left running.
+//
+// `getenv`, `setenv`, `cwd`, `chdir` and `exit` act on the interpreter's own
+// process, so affect every interpreter in it.
+
+use crate::fs::attempt;
+use crate::types::{Arity, HashKey, MalInt, MalMapInternal, MalObject, PrimitiveFn, TypeMismatch};
+use crate::{evaluator, limits, printer};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+#[derive(Debug)]
+pub struct Process {
+    pid: u32,
+    child: RefCell<Child>,
+    // Read on another thread, so that waiting for a line can be interrupted.
+    lines: RefCell<Option<Receiver<io::Result<String>>>>,
+}
+
+// How often to look for an interrupt while waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+impl Process {
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+}
+
+struct Invocation<'a> {
+    program: &'a str,
+    command: Command,
+    input: Option<String>,
+}
+
+fn option<'a>(options: &'a MalMapInternal, name: &str) -> Option<&'a MalObject> {
+    options
+        .get(&HashKey::Keyword(name.into()))
+        .filter(|value| !value.is_nil())
+}
+
+// Split the arguments to `sh` or `spawn` into a command and its options.
+fn invocation(args: &[MalObject]) -> evaluator::Result<Invocation<'_>> {
+    let (args, options) = match args.split_last() {
+        Some((MalObject::Map(options), rest)) => (rest, Some(&options.payload)),
+        _ => (args, None),
+    };
+    let program = match args.first() {
+        Some(program) => program.as_string()?,
+        None => return Err(TypeMismatch::NotAString.into()),
+    };
+    let mut command = Command::new(program);
+    for arg in &args[1..] {
+        command.arg(arg.as_string()?);
+    }
+    let mut input = None;
+    if let Some(options) = options {
+        if let Some(text) = option(options, "in") {
+            input = Some(printer::pr_str(text, printer::PrintMode::Directly));
+        }
+        if let Some(dir) = option(options, "dir") {
+            command.current_dir(dir.as_string()?);
+        }
+        if let Some(env) = option(options, "env") {
+            for (name, value) in env.as_map()?.iter() {
+                let name = match name {
+                    HashKey::String(s) | HashKey::Keyword(s) => s,
+                };
+                match value {
+                    MalObject::Nil => command.env_remove(name),
+                    value => {
+                        command.env(name, printer::pr_str(value, printer::PrintMode::Directly))
+                    }
+                };
+            }
+        }
+    }
+    command.stdin(match input {
+        Some(_) => Stdio::piped(),
+        None => Stdio::null(),
+    });
+    Ok(Invocation {
+        program,
+        command,
+        input,
+    })
+}
+
+// Start the command, feeding it any input on another thread so that a command
+// which fills its output pipe before reading all its input can't deadlock us.
+fn start(invocation: Invocation) -> evaluator::Result<Child> {
+    let Invocation {
+        program,
+        mut command,
+        input,
+    } = invocation;
+    let mut child = attempt("run", Path::new(program), command.spawn())?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        // If the command exits without reading its input, that's its business.
+        thread::spawn(move || stdin.write_all(input.as_bytes()));
+    }
+    Ok(child)
+}
+
+// Wait for the command to finish, unless the evaluation is interrupted first.
+fn wait(child: &mut Child) -> evaluator::Result<ExitStatus> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        limits::check()?;
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+// Read everything from `pipe` on another thread, so that a command can't block
+// writing to one pipe while we wait for the other.
+fn read_all<R: Read + Send + 'static>(pipe: Option<R>) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            // Keep whatever was read before any error.
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        bytes
+    })
+}
+
+// The exit code, or nil if the command was killed by a signal.
+fn exit_code(status: ExitStatus) -> MalObject {
+    match status.code() {
+        Some(code) => MalObject::Integer(code as MalInt),
+        None => MalObject::Nil,
+    }
+}
+
+pub(crate) const SH: PrimitiveFn = PrimitiveFn {
+    name: "sh",
+    fn_ptr: sh,
+    arity: Arity::at_least(1),
+};
+
+// Returns {:exit 0 :out "..." :err "..."}.
+fn sh(args: &[MalObject]) -> evaluator::Result {
+    let mut invocation = invocation(args)?;
+    invocation
+        .command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = start(invocation)?;
+    let stdout = read_all(child.stdout.take());
+    let stderr = read_all(child.stderr.take());
+    let status = wait(&mut child).inspect_err(|_| {
+        // Don't leave the command running once we've given up on it.
+        let _ = child.kill();
+        let _ = child.wait();
+    })?;
+    let text = |output: JoinHandle<Vec<u8>>| {
+        let bytes = output.join().unwrap_or_default();
+        MalObject::String(String::from_utf8_lossy(&bytes).into_owned())
+    };
+    let mut result = HashMap::new();
+    result.insert(HashKey::Keyword("exit".into()), exit_code(status));
+    result.insert(HashKey::Keyword("out".into()), text(stdout));
+    result.insert(HashKey::Keyword("err".into()), text(stderr));
+    Ok(MalObject::wrap_map(result))
+}
+
+pub(crate) const SPAWN: PrimitiveFn = PrimitiveFn {
+    name: "spawn",
+    fn_ptr: spawn,
+    arity: Arity::at_least(1),
+};
+
+// The process's standard error goes to ours.
+fn spawn(args: &[MalObject]) -> evaluator::Result {
+    let mut invocation = invocation(args)?;
+    invocation.command.stdout(Stdio::piped());
+    let mut child = start(invocation)?;
+    let lines = child.stdout.take().map(|stdout| {
+        let (sender, receiver) = mpsc::channel();
+        // Carry on reading after `proc-wait` hangs up, so the process never
+        // blocks on a full pipe.
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let _ = sender.send(line);
+            }
+        });
+        receiver
+    });
+    Ok(MalObject::Process(Rc::new(Process {
+        pid: child.id(),
+        child: RefCell::new(child),
+        lines: RefCell::new(lines),
+    })))
+}
+
+fn as_process(obj: &MalObject) -> evaluator::Result<&Process> {
+    match obj {
+        MalObject::Process(process) => Ok(process),
+        _ => Err(TypeMismatch::NotAProcess.into()),
+    }
+}
+
+pub(crate) const PROC_READ_LINE: PrimitiveFn = PrimitiveFn {
+    name: "proc-read-line",
+    fn_ptr: proc_read_line,
+    arity: Arity::exactly(1),
+};
+
+// The next line of output, without its line ending, or nil at the end.
+fn proc_read_line(args: &[MalObject]) -> evaluator::Result {
+    let process = as_process(&args[0])?;
+    let lines = process.lines.borrow();
+    let lines = match lines.as_ref() {
+        Some(lines) => lines,
+        None => return Ok(MalObject::Nil),
+    };
+    loop {
+        match lines.recv_timeout(POLL_INTERVAL) {
+            Ok(line) => return Ok(MalObject::String(line?)),
+            Err(RecvTimeoutError::Disconnected) => return Ok(MalObject::Nil),
+            Err(RecvTimeoutError::Timeout) => limits::check()?,
+        }
+    }
+}
+
+pub(crate) const PROC_WAIT: PrimitiveFn = PrimitiveFn {
+    name: "proc-wait",
+    fn_ptr: proc_wait,
+    arity: Arity::exactly(1),
+};
+
+// Wait for the process to finish and return its exit code. Any output which
+// hasn't been read is thrown away.
+fn proc_wait(args: &[MalObject]) -> evaluator::Result {
+    let process = as_process(&args[0])?;
+    process.lines.borrow_mut().take();
+    let status = wait(&mut process.child.borrow_mut())?;
+    Ok(exit_code(status))
+}
+
+pub(crate) const PROC_KILL: PrimitiveFn = PrimitiveFn {
+    name: "proc-kill",
+    fn_ptr: proc_kill,
+    arity: Arity::exactly(1),
+};
+
+// Killing a process which has already finished does nothing.
+fn proc_kill(args: &[MalObject]) -> evaluator::Result {
+    let process = as_process(&args[0])?;
+    let mut child = process.child.borrow_mut();
+    if let Ok(None) = child.try_wait() {
+        child.kill()?;
+    }
+    Ok(MalObject::Nil)
+}
+
+pub(crate) const GETENV: PrimitiveFn = PrimitiveFn {
+    name: "getenv",
+    fn_ptr: getenv,
+    arity: Arity::Between(0..=1),
+};
+
+// (getenv name) is the variable's value, or nil if it isn't set (or isn't
+// unicode). (getenv) is a map of every variable.
+fn getenv(args: &[MalObject]) -> evaluator::Result {
+    match args.first() {
+        Some(name) => Ok(match std::env::var(name.as_string()?) {
+            Ok(value) => MalObject::String(value),
+            Err(_) => MalObject::Nil,
+        }),
+        None => Ok(MalObject::wrap_map(
+            std::env::vars()
+                .map(|(name, value)| (HashKey::String(name), MalObject::String(value)))
+                .collect(),
+        )),
+    }
+}
+
+pub(crate) const SETENV: PrimitiveFn = PrimitiveFn {
+    name: "setenv",
+    fn_ptr: setenv,
+    arity: Arity::exactly(2),
+};
+
+// Setting a variable to nil removes it.
+fn setenv(args: &[MalObject]) -> evaluator::Result {
+    let name = args[0].as_string()?;
+    match &args[1] {
+        MalObject::Nil => std::env::remove_var(name),
+        value => std::env::set_var(name, printer::pr_str(value, printer::PrintMode::Directly)),
+    }
+    Ok(MalObject::Nil)
+}
+
+pub(crate) const CWD: PrimitiveFn = PrimitiveFn {
+    name: "cwd",
+    fn_ptr: |_| {
+        Ok(MalObject::String(
+            std::env::current_dir()?.to_string_lossy().into_owned(),
+        ))
+    },
+    arity: Arity::exactly(0),
+};
+
+pub(crate) const CHDIR: PrimitiveFn = PrimitiveFn {
+    name: "chdir",
+    fn_ptr: chdir,
+    arity: Arity::exactly(1),
+};
+
+fn chdir(args: &[MalObject]) -> evaluator::Result {
+    let path = Path::new(args[0].as_string()?);
+    attempt("change directory to", path, std::env::set_current_dir(path))?;
+    Ok(MalObject::Nil)
+}
+
+pub(crate) const EXIT: PrimitiveFn = PrimitiveFn {
+    name: "exit",
+    fn_ptr: exit,
+    arity: Arity::Between(0..=1),
+};
+
+// Exit immediately, with status 0 unless another is given. Console output is
+// already flushed after every write, so there's nothing to tidy up.
+fn exit(args: &[MalObject]) -> evaluator::Result {
+    let status = match args.first() {
+        Some(status) => i32::try_from(status.as_int()?).map_err(|_| TypeMismatch::OutOfRange)?,
+        None => 0,
+    };
+    std::process::exit(status)
+}
@@ -0,0 +1,123 @@
+// Pseudo-random numbers.
+//
+// Each thread has its own SplitMix64 generator (see
+// http://prng.di.unimi.it/splitmix64.c). It's fast, tiny and good enough for
+// scripts, but not for anything cryptographic. Seeding it makes every
+// subsequent random primitive on that thread deterministic, which is what you
+// want in tests.
+//
+// A thread's generator starts off seeded from the clock. The command line
+// seeds it from MAL_RANDOM_SEED instead, if that's set.
+
+use crate::evaluator;
+use crate::types::{Arity, MalInt, MalObject, PrimitiveFn, TypeMismatch};
+use std::cell::Cell;
+use std::convert::TryFrom;
+use std::time::SystemTime;
+
+/// The environment variable the command line reads a seed from.
+pub const SEED_VARIABLE: &str = "MAL_RANDOM_SEED";
+
+thread_local! {
+    static STATE: Cell<u64> = Cell::new(clock_seed());
+}
+
+fn clock_seed() -> u64 {
+    match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_nanos() as u64,
+        Err(_) => 0,
+    }
+}
+
+/// Reset this thread's generator, so that it produces the same numbers as it
+/// did the last time it was given this seed.
+pub fn seed(seed: u64) {
+    STATE.with(|state| state.set(seed));
+}
+
+fn next() -> u64 {
+    let state = STATE.with(|state| {
+        let next = state.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+        state.set(next);
+        next
+    });
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// Uniform in [0, bound). Rejects the top end of the range, where some results
+// would be more likely than others.
+fn below(bound: u64) -> u64 {
+    let zone = u64::MAX - u64::MAX % bound;
+    loop {
+        let x = next();
+        if x < zone {
+            return x % bound;
+        }
+    }
+}
+
+fn positive(obj: &MalObject) -> evaluator::Result<u64> {
+    u64::try_from(obj.as_int()?)
+        .ok()
+        .filter(|&n| n > 0)
+        .ok_or(evaluator::Error::TypeMismatch(TypeMismatch::OutOfRange))
+}
+
+pub(crate) const RAND: PrimitiveFn = PrimitiveFn {
+    name: "rand",
+    fn_ptr: |_| Ok(MalObject::Integer((next() >> 1) as MalInt)),
+    arity: Arity::exactly(0),
+};
+
+pub(crate) const RAND_INT: PrimitiveFn = PrimitiveFn {
+    name: "rand-int",
+    fn_ptr: |args| Ok(MalObject::Integer(below(positive(&args[0])?) as MalInt)),
+    arity: Arity::exactly(1),
+};
+
+pub(crate) const RAND_NTH: PrimitiveFn = PrimitiveFn {
+    name: "rand-nth",
+    fn_ptr: rand_nth,
+    arity: Arity::exactly(1),
+};
+
+fn rand_nth(args: &[MalObject]) -> evaluator::Result {
+    let seq = args[0].to_seq()?;
+    match seq.len() {
+        0 => Err(evaluator::Error::BadIndex(0, 0..0)),
+        n => Ok(seq[below(n as u64) as usize].clone()),
+    }
+}
+
+pub(crate) const SHUFFLE: PrimitiveFn = PrimitiveFn {
+    name: "shuffle",
+    fn_ptr: shuffle,
+    arity: Arity::exactly(1),
+};
+
+// A vector of the elements in a random order (Fisher-Yates).
+fn shuffle(args: &[MalObject]) -> evaluator::Result {
+    let mut elements = args[0].to_seq()?.into_owned();
+    for i in (1..elements.len()).rev() {
+        elements.swap(i, below(i as u64 + 1) as usize);
+    }
+    Ok(MalObject::wrap_vector(elements))
+}
+
+pub(crate) const RANDOM_SEED: PrimitiveFn = PrimitiveFn {
+    name: "random-seed!",
+    fn_ptr: random_seed,
+    arity: Arity::Between(0..=1),
+};
+
+// With no seed, go back to seeding from the clock.
+fn random_seed(args: &[MalObject]) -> evaluator::Result {
+    match args.first() {
+        Some(n) => seed(n.as_int()? as u64),
+        None => seed(clock_seed()),
+    }
+    Ok(MalObject::Nil)
+}
@@ -1,7 +1,10 @@
-use crate::strings::BuildError;
+use crate::strings::{build_char, BuildError};
+use crate::time;
 use crate::tokens;
 use crate::tokens::{tokenize, Close, Token, TokenizerError};
-use crate::types::{build_keyword, build_map, build_string, MalInt, MalObject, MapError};
+use crate::types::{
+    build_keyword, build_map, build_regex, build_string, MalInt, MalObject, MapError,
+};
 use std::iter::Peekable;
 use std::{fmt, slice};
 
@@ -18,6 +21,9 @@ pub enum Error {
     Unimplemented,
     ReadMapError(MapError),
     StringError(BuildError),
+    BadRegex(regex::Error),
+    BadCharacter(String),
+    BadInstant(String),
 }
 
 impl fmt::Display for Error {
@@ -36,6 +42,9 @@ impl fmt::Display for Error {
             UnexpectedCloseToken(c) => write!(f, "unexpected Close::{:?} token while parsing", c),
             ReadMapError(e) => write!(f, "{:?}", e),
             StringError(e) => write!(f, "error building string: {:?}", e),
+            BadRegex(e) => write!(f, "bad regex literal: {}", e),
+            BadCharacter(name) => write!(f, "unknown character literal \\{}", name),
+            BadInstant(s) => write!(f, "bad #inst literal {}", s),
             Unimplemented => write!(f, "haven't implemented this yet, but no need to panic!()"),
         }
     }
@@ -54,6 +63,22 @@ pub fn read_str(input: &str) -> Result {
     result
 }
 
+/// Read every form in `input`, e.g. the contents of a file.
+pub fn read_all(input: &str) -> std::result::Result<Vec<MalObject>, Error> {
+    let tokens = tokenize(input).map_err(Error::TokenizerError)?;
+    let mut reader = tokens.iter().peekable();
+    let mut forms = Vec::new();
+    while reader.peek().is_some() {
+        match read_form(&mut reader) {
+            Ok(obj) => forms.push(obj),
+            // Trailing comments are fine.
+            Err(Error::ReadComment) => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(forms)
+}
+
 fn read_form(reader: &mut Reader) -> Result {
     use crate::tokens::Open::*;
     use crate::tokens::UnaryOp::*;
@@ -66,8 +91,13 @@ fn read_form(reader: &mut Reader) -> Result {
             Token::Open(Vector) => read_vector(reader),
             Token::Open(Map) => read_map(reader),
             Token::Close(kind) => Err(Error::UnexpectedCloseToken(*kind)),
+            Token::PlainChars("#inst") => read_instant(reader),
             Token::PlainChars(_) => read_atom(token),
+            Token::Char(name) => build_char(name)
+                .map(MalObject::Char)
+                .ok_or_else(|| Error::BadCharacter(name.to_string())),
             Token::StringLiteral(s) => build_string(s).map_err(Error::StringError),
+            Token::Regex(s) => build_regex(s).map_err(Error::BadRegex),
             Token::Comment(_) => match &reader.peek() {
                 None => Err(Error::ReadComment),
                 Some(_) => continue,
@@ -124,7 +154,7 @@ fn read_atom(token: &Token) -> Result {
     }
 }
 
-fn read_plain_chars(chars: &str) -> Result {
+pub(crate) fn read_plain_chars(chars: &str) -> Result {
     let mut iter = chars.chars();
     let first = iter.next().unwrap();
     match first {
@@ -156,6 +186,17 @@ fn read_int(chars: &str) -> Result {
         .map(MalObject::Integer)
 }
 
+// `#inst "2020-07-01T12:30:00Z"`, as instants are printed.
+fn read_instant(reader: &mut Reader) -> Result {
+    match reader.next() {
+        Some(Token::StringLiteral(s)) => time::parse_iso_8601(s.payload)
+            .map(MalObject::Instant)
+            .map_err(|_| Error::BadInstant(format!("\"{}\"", s.payload))),
+        Some(token) => Err(Error::BadInstant(format!("{:?}", token))),
+        None => Err(Error::NoMoreTokens),
+    }
+}
+
 fn read_unary_operand(reader: &mut Reader, opname: &str) -> Result {
     let list = vec![MalObject::new_symbol(opname), read_form(reader)?];
     Ok(MalObject::wrap_list(list))
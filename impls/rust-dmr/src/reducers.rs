@@ -0,0 +1,327 @@
+// Reduction and transducers.
+//
+// Everything here walks collections in place, one element at a time, rather
+// than copying them into a list first. A reducing function can stop a
+// reduction early by returning `(reduced x)`, which makes x the result.
+//
+// A transducer is a function from one reducing function to another, such as
+// `(map f)`, `(filter pred)` or `(take n)`. Composing them with `comp` gives a
+// pipeline which `transduce` and `into` run without building any intermediate
+// sequences. As in Clojure, a reducing function is called with no arguments
+// for an initial value, with the accumulated result and an element for each
+// step, and with just the result to finish off.
+
+use crate::evaluator::{self, apply_fully};
+use crate::lazy::{self, Cursor};
+use crate::types::{truthy, Arity, MalObject, PrimitiveFn, TypeMismatch};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Call `f` with each element of a seqable collection, until it returns false.
+fn for_each<F>(coll: &MalObject, mut f: F) -> evaluator::Result<()>
+where
+    F: FnMut(MalObject) -> evaluator::Result<bool>,
+{
+    match coll {
+        MalObject::Nil => {}
+        MalObject::List(_) | MalObject::Vector(_) => {
+            for x in coll.as_seq()? {
+                if !f(x.clone())? {
+                    break;
+                }
+            }
+        }
+        MalObject::Map(map) => {
+            for (key, value) in map.payload.iter() {
+                let entry = MalObject::wrap_vector(vec![key.into_mal_object(), value.clone()]);
+                if !f(entry)? {
+                    break;
+                }
+            }
+        }
+        MalObject::String(s) => {
+            for c in s.chars() {
+                if !f(MalObject::String(c.to_string()))? {
+                    break;
+                }
+            }
+        }
+        MalObject::LazySeq(_) => {
+            let mut cursor = Cursor::new(coll)?;
+            while let Some(x) = cursor.next()? {
+                if !f(x)? {
+                    break;
+                }
+            }
+        }
+        _ => return Err(TypeMismatch::NotASequence.into()),
+    }
+    Ok(())
+}
+
+/// Fold `step` over `coll` starting from `init`, stopping early if it returns
+/// a `reduced` value.
+pub(crate) fn fold<F>(coll: &MalObject, init: MalObject, mut step: F) -> evaluator::Result
+where
+    F: FnMut(MalObject, MalObject) -> evaluator::Result,
+{
+    let mut acc = init;
+    for_each(coll, |x| {
+        match step(std::mem::replace(&mut acc, MalObject::Nil), x)? {
+            MalObject::Reduced(result) => {
+                acc = (*result).clone();
+                Ok(false)
+            }
+            result => {
+                acc = result;
+                Ok(true)
+            }
+        }
+    })?;
+    Ok(acc)
+}
+
+fn ensure_reduced(obj: MalObject) -> MalObject {
+    match obj {
+        MalObject::Reduced(_) => obj,
+        _ => MalObject::Reduced(Rc::new(obj)),
+    }
+}
+
+pub(crate) const REDUCE: PrimitiveFn = PrimitiveFn {
+    name: "reduce",
+    fn_ptr: reduce,
+    arity: Arity::Between(2..=3),
+};
+
+// (reduce f init coll) folds f over coll starting from init. (reduce f coll)
+// starts from the first element, or returns (f) if coll is empty.
+fn reduce(args: &[MalObject]) -> evaluator::Result {
+    let f = &args[0];
+    let step = |acc, x| apply_fully(f, &[acc, x]);
+    if let [_, init, coll] = args {
+        return fold(coll, init.clone(), step);
+    }
+    let mut cursor = Cursor::new(&args[1])?;
+    match cursor.next()? {
+        Some(first) => fold(&cursor.rest(), first, step),
+        None => apply_fully(f, &[]),
+    }
+}
+
+pub(crate) const REDUCED: PrimitiveFn = PrimitiveFn {
+    name: "reduced",
+    fn_ptr: |args| Ok(MalObject::Reduced(Rc::new(args[0].clone()))),
+    arity: Arity::exactly(1),
+};
+
+pub(crate) const REDUCED_TEST: PrimitiveFn = PrimitiveFn {
+    name: "reduced?",
+    fn_ptr: |args| Ok(MalObject::Bool(matches!(args[0], MalObject::Reduced(_)))),
+    arity: Arity::exactly(1),
+};
+
+pub(crate) const UNREDUCED: PrimitiveFn = PrimitiveFn {
+    name: "unreduced",
+    fn_ptr: |args| match &args[0] {
+        MalObject::Reduced(x) => Ok((**x).clone()),
+        x => Ok(x.clone()),
+    },
+    arity: Arity::exactly(1),
+};
+
+// A reducing function which starts and finishes as `rf` does, and takes each
+// step by calling `step` with `rf`, the result so far and the element.
+fn reducing_fn<F>(rf: MalObject, step: F) -> MalObject
+where
+    F: Fn(&MalObject, MalObject, MalObject) -> evaluator::Result + 'static,
+{
+    MalObject::native(
+        "reducing-fn",
+        Arity::Between(0..=2),
+        move |args| match args {
+            [] => apply_fully(&rf, &[]),
+            [acc] => apply_fully(&rf, std::slice::from_ref(acc)),
+            [acc, x] => step(&rf, acc.clone(), x.clone()),
+            _ => unreachable!(),
+        },
+    )
+}
+
+// A transducer, turning reducing functions into the ones `make` builds around
+// them.
+fn transducer<F>(name: &str, make: F) -> MalObject
+where
+    F: Fn(MalObject) -> MalObject + 'static,
+{
+    MalObject::native(name, Arity::exactly(1), move |args| {
+        Ok(make(args[0].clone()))
+    })
+}
+
+/// `(map f)`: call `f` on each element.
+pub(crate) fn mapping(f: MalObject) -> MalObject {
+    transducer("map", move |rf| {
+        let f = f.clone();
+        reducing_fn(rf, move |rf, acc, x| {
+            let y = apply_fully(&f, &[x])?;
+            apply_fully(rf, &[acc, y])
+        })
+    })
+}
+
+/// `(filter pred)` and `(remove pred)`: keep the elements for which `pred`'s
+/// truthiness is `wanted`.
+pub(crate) fn filtering(pred: MalObject, wanted: bool) -> MalObject {
+    let name = if wanted { "filter" } else { "remove" };
+    transducer(name, move |rf| {
+        let pred = pred.clone();
+        reducing_fn(rf, move |rf, acc, x| {
+            match truthy(&apply_fully(&pred, std::slice::from_ref(&x))?) == wanted {
+                true => apply_fully(rf, &[acc, x]),
+                false => Ok(acc),
+            }
+        })
+    })
+}
+
+/// `(take n)`: the first `n` elements, after which the reduction stops.
+pub(crate) fn taking(n: usize) -> MalObject {
+    transducer("take", move |rf| {
+        // Each reduction has its own count.
+        let left = Cell::new(n);
+        reducing_fn(rf, move |rf, acc, x| {
+            if left.get() == 0 {
+                return Ok(ensure_reduced(acc));
+            }
+            left.set(left.get() - 1);
+            let result = apply_fully(rf, &[acc, x])?;
+            match left.get() {
+                0 => Ok(ensure_reduced(result)),
+                _ => Ok(result),
+            }
+        })
+    })
+}
+
+pub(crate) const TRANSDUCE: PrimitiveFn = PrimitiveFn {
+    name: "transduce",
+    fn_ptr: transduce,
+    arity: Arity::Between(3..=4),
+};
+
+// (transduce xform f coll) or (transduce xform f init coll). Without init,
+// starts from (f).
+fn transduce(args: &[MalObject]) -> evaluator::Result {
+    let (xform, f, coll) = (&args[0], &args[1], &args[args.len() - 1]);
+    let rf = apply_fully(xform, std::slice::from_ref(f))?;
+    let init = match args {
+        [_, _, init, _] => init.clone(),
+        _ => apply_fully(f, &[])?,
+    };
+    let result = fold(coll, init, |acc, x| apply_fully(&rf, &[acc, x]))?;
+    apply_fully(&rf, &[result])
+}
+
+pub(crate) const INTO: PrimitiveFn = PrimitiveFn {
+    name: "into",
+    fn_ptr: into,
+    arity: Arity::Between(2..=3),
+};
+
+// (into to from) or (into to xform from): conj each element of from, passed
+// through xform if given, onto to. Elements added to a map must be [key value]
+// pairs.
+fn into(args: &[MalObject]) -> evaluator::Result {
+    let (to, from) = (&args[0], &args[args.len() - 1]);
+    let collected = Rc::new(RefCell::new(Vec::new()));
+    match args {
+        [_, xform, _] => {
+            let sink = collected.clone();
+            let collect = MalObject::native("collect", Arity::Between(0..=2), move |args| {
+                if let [_, x] = args {
+                    sink.borrow_mut().push(x.clone());
+                }
+                Ok(MalObject::Nil)
+            });
+            let rf = apply_fully(xform, &[collect])?;
+            let result = fold(from, MalObject::Nil, |acc, x| apply_fully(&rf, &[acc, x]))?;
+            apply_fully(&rf, &[result])?;
+        }
+        _ => for_each(from, |x| {
+            collected.borrow_mut().push(x);
+            Ok(true)
+        })?,
+    }
+    let added = collected.replace(Vec::new());
+    add_all(to, added)
+}
+
+// `to` with `added` conj'ed on, one at a time.
+fn add_all(to: &MalObject, added: Vec<MalObject>) -> evaluator::Result {
+    Ok(match to {
+        MalObject::Nil | MalObject::List(_) => {
+            let mut result: Vec<_> = added.into_iter().rev().collect();
+            result.extend_from_slice(to.as_seq().unwrap_or_default());
+            MalObject::wrap_list(result)
+        }
+        MalObject::Vector(vec) => {
+            let mut result = vec.payload.clone();
+            result.extend(added);
+            MalObject::wrap_vector(result)
+        }
+        MalObject::LazySeq(_) => added
+            .into_iter()
+            .fold(to.clone(), |seq, x| lazy::cons(x, seq)),
+        MalObject::Map(map) => {
+            let mut result: HashMap<_, _> = map.payload.clone();
+            for entry in added {
+                match entry.to_seq()?.as_ref() {
+                    [key, value] => result.insert(key.as_hashkey()?, value.clone()),
+                    other => {
+                        return Err(TypeMismatch::WrongLength {
+                            expected: 2,
+                            got: other.len(),
+                        }
+                        .into())
+                    }
+                };
+            }
+            MalObject::wrap_map(result)
+        }
+        _ => return Err(TypeMismatch::NotASequence.into()),
+    })
+}
+
+pub(crate) const COMP: PrimitiveFn = PrimitiveFn {
+    name: "comp",
+    fn_ptr: comp,
+    arity: Arity::at_least(0),
+};
+
+// (comp f g h) calls h with its arguments, then g with the result, then f.
+// Composing transducers this way runs them left to right.
+fn comp(args: &[MalObject]) -> evaluator::Result {
+    match args {
+        [] => Ok(MalObject::native("identity", Arity::exactly(1), |args| {
+            Ok(args[0].clone())
+        })),
+        [f] => Ok(f.clone()),
+        _ => {
+            let fs = args.to_vec();
+            Ok(MalObject::native(
+                "composed",
+                Arity::at_least(0),
+                move |args| {
+                    let (last, rest) = fs.split_last().unwrap();
+                    let mut result = apply_fully(last, args)?;
+                    for f in rest.iter().rev() {
+                        result = apply_fully(f, &[result])?;
+                    }
+                    Ok(result)
+                },
+            ))
+        }
+    }
+}
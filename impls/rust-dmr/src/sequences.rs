@@ -0,0 +1,691 @@
+// The usual higher-order sequence functions, implemented natively because
+// writing them in mal makes them painfully slow.
+//
+// Anything seqable can be passed where a collection is expected: lists,
+// vectors, nil (which is empty), maps (which are sequences of [key value]
+// vectors) and strings (which are sequences of one-character strings, as with
+// `seq`). Results which are sequences are lists, apart from the values of
+// `group-by`'s map.
+//
+// `map`, `filter`, `remove`, `take`, `drop`, `take-while`, `drop-while` and
+// `mapcat` are lazy when given a lazy sequence, and return one. `iterate`,
+// `cycle`, `(repeat x)` and `(range)` make infinite lazy sequences. Everything
+// else realises its arguments completely, apart from `some` and `every?`,
+// which stop as soon as they know their answer.
+//
+// Given no collection, `map`, `filter`, `remove` and `take` return transducers
+// instead (see `reducers`).
+
+use crate::evaluator::{self, apply_fully};
+use crate::lazy::{self, Cursor};
+use crate::reducers;
+use crate::types::{truthy, Arity, HashKey, MalInt, MalObject, PrimitiveFn, TypeMismatch};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+// The elements of a seqable collection.
+pub(crate) fn elements(obj: &MalObject) -> evaluator::Result<Cow<'_, [MalObject]>> {
+    Ok(match obj {
+        MalObject::Nil => Cow::Borrowed(&[]),
+        MalObject::LazySeq(_) => Cow::Owned(lazy::realise_all(obj)?),
+        MalObject::List(list) => Cow::Borrowed(&list.payload),
+        MalObject::Vector(vec) => Cow::Borrowed(&vec.payload),
+        MalObject::Map(map) => Cow::Owned(
+            map.payload
+                .iter()
+                .map(|(key, value)| {
+                    MalObject::wrap_vector(vec![key.into_mal_object(), value.clone()])
+                })
+                .collect(),
+        ),
+        MalObject::String(s) => Cow::Owned(
+            s.chars()
+                .map(|c| MalObject::String(c.to_string()))
+                .collect(),
+        ),
+        _ => return Err(TypeMismatch::NotASequence.into()),
+    })
+}
+
+// A count, where negative numbers mean zero.
+fn count(obj: &MalObject) -> evaluator::Result<usize> {
+    Ok(usize::try_from(obj.as_int()?).unwrap_or(0))
+}
+
+// (f x) is truthy.
+fn satisfies(f: &MalObject, x: &MalObject) -> evaluator::Result<bool> {
+    Ok(truthy(&apply_fully(f, std::slice::from_ref(x))?))
+}
+
+// Call `f` with the first elements of every collection, then the second, and
+// so on, stopping at the end of the shortest.
+fn map_over(f: &MalObject, colls: &[MalObject]) -> evaluator::Result<Vec<MalObject>> {
+    if let [coll] = colls {
+        return elements(coll)?
+            .iter()
+            .map(|x| apply_fully(f, std::slice::from_ref(x)))
+            .collect();
+    }
+    let colls: Vec<_> = colls.iter().map(elements).collect::<Result<_, _>>()?;
+    let shortest = colls.iter().map(|coll| coll.len()).min().unwrap_or(0);
+    (0..shortest)
+        .map(|i| {
+            let args: Vec<_> = colls.iter().map(|coll| coll[i].clone()).collect();
+            apply_fully(f, &args)
+        })
+        .collect()
+}
+
+fn cursors(colls: &[MalObject]) -> evaluator::Result<Vec<Cursor>> {
+    colls.iter().map(Cursor::new).collect()
+}
+
+// As `map_over`, a step at a time.
+fn lazy_map(f: MalObject, cursors: Vec<Cursor>) -> MalObject {
+    lazy::new(move || {
+        let mut cursors = cursors.clone();
+        let mut args = Vec::with_capacity(cursors.len());
+        for cursor in cursors.iter_mut() {
+            match cursor.next()? {
+                Some(x) => args.push(x),
+                None => return Ok(MalObject::Nil),
+            }
+        }
+        let first = apply_fully(&f, &args)?;
+        Ok(lazy::cons(first, lazy_map(f.clone(), cursors)))
+    })
+}
+
+pub(crate) const MAP: PrimitiveFn = PrimitiveFn {
+    name: "map",
+    fn_ptr: |args| match args {
+        [f] => Ok(reducers::mapping(f.clone())),
+        _ if args[1..].iter().any(lazy::is_lazy) => {
+            Ok(lazy_map(args[0].clone(), cursors(&args[1..])?))
+        }
+        _ => Ok(MalObject::wrap_list(map_over(&args[0], &args[1..])?)),
+    },
+    arity: Arity::at_least(1),
+};
+
+/// The elements of each of the sequences `seqs` produces in turn, realising
+/// them only as they're needed.
+pub(crate) fn lazy_concat(seqs: Cursor) -> MalObject {
+    concat_from(Cursor::empty(), seqs)
+}
+
+// The rest of `part`, then the rest of `seqs`.
+fn concat_from(part: Cursor, seqs: Cursor) -> MalObject {
+    lazy::new(move || {
+        let (mut part, mut seqs) = (part.clone(), seqs.clone());
+        loop {
+            if let Some(first) = part.next()? {
+                return Ok(lazy::cons(first, concat_from(part, seqs)));
+            }
+            part = match seqs.next()? {
+                Some(next) => Cursor::new(&next)?,
+                None => return Ok(MalObject::Nil),
+            };
+        }
+    })
+}
+
+pub(crate) const MAPCAT: PrimitiveFn = PrimitiveFn {
+    name: "mapcat",
+    fn_ptr: mapcat,
+    arity: Arity::at_least(2),
+};
+
+// Like `map`, concatenating the results.
+fn mapcat(args: &[MalObject]) -> evaluator::Result {
+    if args[1..].iter().any(lazy::is_lazy) {
+        let parts = lazy_map(args[0].clone(), cursors(&args[1..])?);
+        return Ok(lazy_concat(Cursor::new(&parts)?));
+    }
+    let mut result = Vec::new();
+    for part in map_over(&args[0], &args[1..])? {
+        result.extend_from_slice(&elements(&part)?);
+    }
+    Ok(MalObject::wrap_list(result))
+}
+
+pub(crate) const FILTER: PrimitiveFn = PrimitiveFn {
+    name: "filter",
+    fn_ptr: |args| keep_if(args, true),
+    arity: Arity::Between(1..=2),
+};
+
+pub(crate) const REMOVE: PrimitiveFn = PrimitiveFn {
+    name: "remove",
+    fn_ptr: |args| keep_if(args, false),
+    arity: Arity::Between(1..=2),
+};
+
+fn keep_if(args: &[MalObject], wanted: bool) -> evaluator::Result {
+    let (pred, coll) = match args {
+        [pred, coll] => (pred, coll),
+        _ => return Ok(reducers::filtering(args[0].clone(), wanted)),
+    };
+    if lazy::is_lazy(coll) {
+        return Ok(lazy_keep_if(pred.clone(), Cursor::new(coll)?, wanted));
+    }
+    let mut kept = Vec::new();
+    for x in elements(coll)?.iter() {
+        if satisfies(pred, x)? == wanted {
+            kept.push(x.clone());
+        }
+    }
+    Ok(MalObject::wrap_list(kept))
+}
+
+fn lazy_keep_if(pred: MalObject, cursor: Cursor, wanted: bool) -> MalObject {
+    lazy::new(move || {
+        let mut cursor = cursor.clone();
+        while let Some(x) = cursor.next()? {
+            if satisfies(&pred, &x)? == wanted {
+                return Ok(lazy::cons(x, lazy_keep_if(pred.clone(), cursor, wanted)));
+            }
+        }
+        Ok(MalObject::Nil)
+    })
+}
+
+pub(crate) const TAKE: PrimitiveFn = PrimitiveFn {
+    name: "take",
+    fn_ptr: |args| {
+        if let [n] = args {
+            return Ok(reducers::taking(count(n)?));
+        }
+        if lazy::is_lazy(&args[1]) {
+            return Ok(lazy_take(count(&args[0])?, Cursor::new(&args[1])?));
+        }
+        let coll = elements(&args[1])?;
+        let n = count(&args[0])?.min(coll.len());
+        Ok(MalObject::wrap_list(coll[..n].to_vec()))
+    },
+    arity: Arity::Between(1..=2),
+};
+
+fn lazy_take(n: usize, cursor: Cursor) -> MalObject {
+    if n == 0 {
+        return MalObject::new_list();
+    }
+    lazy::new(move || {
+        let mut cursor = cursor.clone();
+        Ok(match cursor.next()? {
+            Some(x) => lazy::cons(x, lazy_take(n - 1, cursor)),
+            None => MalObject::Nil,
+        })
+    })
+}
+
+pub(crate) const DROP: PrimitiveFn = PrimitiveFn {
+    name: "drop",
+    fn_ptr: |args| {
+        let n = count(&args[0])?;
+        if lazy::is_lazy(&args[1]) {
+            let cursor = Cursor::new(&args[1])?;
+            return Ok(lazy::new(move || {
+                let mut cursor = cursor.clone();
+                for _ in 0..n {
+                    if cursor.next()?.is_none() {
+                        break;
+                    }
+                }
+                Ok(cursor.rest())
+            }));
+        }
+        let coll = elements(&args[1])?;
+        let n = n.min(coll.len());
+        Ok(MalObject::wrap_list(coll[n..].to_vec()))
+    },
+    arity: Arity::exactly(2),
+};
+
+// How many elements from the start of coll satisfy pred.
+fn prefix_length(pred: &MalObject, coll: &[MalObject]) -> evaluator::Result<usize> {
+    for (i, x) in coll.iter().enumerate() {
+        if !satisfies(pred, x)? {
+            return Ok(i);
+        }
+    }
+    Ok(coll.len())
+}
+
+pub(crate) const TAKE_WHILE: PrimitiveFn = PrimitiveFn {
+    name: "take-while",
+    fn_ptr: |args| {
+        if lazy::is_lazy(&args[1]) {
+            return Ok(lazy_take_while(args[0].clone(), Cursor::new(&args[1])?));
+        }
+        let coll = elements(&args[1])?;
+        let n = prefix_length(&args[0], &coll)?;
+        Ok(MalObject::wrap_list(coll[..n].to_vec()))
+    },
+    arity: Arity::exactly(2),
+};
+
+fn lazy_take_while(pred: MalObject, cursor: Cursor) -> MalObject {
+    lazy::new(move || {
+        let mut cursor = cursor.clone();
+        Ok(match cursor.next()? {
+            Some(x) if satisfies(&pred, &x)? => {
+                lazy::cons(x, lazy_take_while(pred.clone(), cursor))
+            }
+            _ => MalObject::Nil,
+        })
+    })
+}
+
+pub(crate) const DROP_WHILE: PrimitiveFn = PrimitiveFn {
+    name: "drop-while",
+    fn_ptr: |args| {
+        if lazy::is_lazy(&args[1]) {
+            let (pred, cursor) = (args[0].clone(), Cursor::new(&args[1])?);
+            return Ok(lazy::new(move || {
+                let mut cursor = cursor.clone();
+                while let Some(x) = cursor.next()? {
+                    if !satisfies(&pred, &x)? {
+                        return Ok(lazy::cons(x, cursor.rest()));
+                    }
+                }
+                Ok(MalObject::Nil)
+            }));
+        }
+        let coll = elements(&args[1])?;
+        let n = prefix_length(&args[0], &coll)?;
+        Ok(MalObject::wrap_list(coll[n..].to_vec()))
+    },
+    arity: Arity::exactly(2),
+};
+
+pub(crate) const SOME: PrimitiveFn = PrimitiveFn {
+    name: "some",
+    fn_ptr: some,
+    arity: Arity::exactly(2),
+};
+
+// The first truthy (pred x), or nil.
+fn some(args: &[MalObject]) -> evaluator::Result {
+    let mut coll = Cursor::new(&args[1])?;
+    while let Some(x) = coll.next()? {
+        let result = apply_fully(&args[0], &[x])?;
+        if truthy(&result) {
+            return Ok(result);
+        }
+    }
+    Ok(MalObject::Nil)
+}
+
+pub(crate) const EVERY_TEST: PrimitiveFn = PrimitiveFn {
+    name: "every?",
+    fn_ptr: every,
+    arity: Arity::exactly(2),
+};
+
+fn every(args: &[MalObject]) -> evaluator::Result {
+    let mut coll = Cursor::new(&args[1])?;
+    while let Some(x) = coll.next()? {
+        if !satisfies(&args[0], &x)? {
+            return Ok(MalObject::Bool(false));
+        }
+    }
+    Ok(MalObject::Bool(true))
+}
+
+pub(crate) const RANGE: PrimitiveFn = PrimitiveFn {
+    name: "range",
+    fn_ptr: range,
+    arity: Arity::Between(0..=3),
+};
+
+// (range end), (range start end) or (range start end step). The step may be
+// negative, but not zero. (range) is every natural number, lazily.
+fn range(args: &[MalObject]) -> evaluator::Result {
+    if args.is_empty() {
+        return Ok(count_from(0));
+    }
+    let ints: Vec<MalInt> = args
+        .iter()
+        .map(|arg| arg.as_int())
+        .collect::<Result<_, _>>()?;
+    let (start, end, step) = match ints[..] {
+        [end] => (0, end, 1),
+        [start, end] => (start, end, 1),
+        [start, end, step] => (start, end, step),
+        _ => unreachable!(),
+    };
+    if step == 0 {
+        return Err(TypeMismatch::OutOfRange.into());
+    }
+    let mut result = Vec::new();
+    let mut i = start;
+    while (step > 0 && i < end) || (step < 0 && i > end) {
+        result.push(MalObject::Integer(i));
+        i = match i.checked_add(step) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    Ok(MalObject::wrap_list(result))
+}
+
+// start, start + 1, ... until it would overflow.
+fn count_from(start: MalInt) -> MalObject {
+    lazy::new(move || {
+        let rest = match start.checked_add(1) {
+            Some(next) => count_from(next),
+            None => MalObject::Nil,
+        };
+        Ok(lazy::cons(MalObject::Integer(start), rest))
+    })
+}
+
+pub(crate) const ITERATE: PrimitiveFn = PrimitiveFn {
+    name: "iterate",
+    fn_ptr: |args| Ok(iterate(args[0].clone(), args[1].clone())),
+    arity: Arity::exactly(2),
+};
+
+// x, (f x), (f (f x)), ...
+fn iterate(f: MalObject, x: MalObject) -> MalObject {
+    let rest = {
+        let x = x.clone();
+        lazy::new(move || {
+            Ok(iterate(
+                f.clone(),
+                apply_fully(&f, std::slice::from_ref(&x))?,
+            ))
+        })
+    };
+    lazy::cons(x, rest)
+}
+
+pub(crate) const CYCLE: PrimitiveFn = PrimitiveFn {
+    name: "cycle",
+    fn_ptr: |args| {
+        let elements: Rc<[MalObject]> = elements(&args[0])?.into_owned().into();
+        match elements.is_empty() {
+            true => Ok(MalObject::new_list()),
+            false => Ok(cycle_from(elements, 0)),
+        }
+    },
+    arity: Arity::exactly(1),
+};
+
+// The elements of a collection over and over, starting from the ith.
+fn cycle_from(elements: Rc<[MalObject]>, i: usize) -> MalObject {
+    lazy::new(move || {
+        let first = elements[i].clone();
+        let rest = cycle_from(elements.clone(), (i + 1) % elements.len());
+        Ok(lazy::cons(first, rest))
+    })
+}
+
+pub(crate) const REPEAT: PrimitiveFn = PrimitiveFn {
+    name: "repeat",
+    fn_ptr: repeat,
+    arity: Arity::Between(1..=2),
+};
+
+// (repeat x) is x forever, and (repeat n x) a list of n xs.
+fn repeat(args: &[MalObject]) -> evaluator::Result {
+    match args {
+        [x] => Ok(repeat_forever(x.clone())),
+        [n, x] => Ok(MalObject::wrap_list(vec![x.clone(); count(n)?])),
+        _ => unreachable!(),
+    }
+}
+
+fn repeat_forever(x: MalObject) -> MalObject {
+    lazy::new(move || Ok(lazy::cons(x.clone(), repeat_forever(x.clone()))))
+}
+
+pub(crate) const REVERSE: PrimitiveFn = PrimitiveFn {
+    name: "reverse",
+    fn_ptr: |args| {
+        let mut result = elements(&args[0])?.into_owned();
+        result.reverse();
+        Ok(MalObject::wrap_list(result))
+    },
+    arity: Arity::exactly(1),
+};
+
+pub(crate) const LAST: PrimitiveFn = PrimitiveFn {
+    name: "last",
+    fn_ptr: |args| {
+        Ok(elements(&args[0])?
+            .last()
+            .cloned()
+            .unwrap_or(MalObject::Nil))
+    },
+    arity: Arity::exactly(1),
+};
+
+// The natural order: numbers, strings, keywords, symbols, characters and
+// instants each among themselves, and sequences element by element. Nil comes
+// before everything, and false before true. Anything else can't be compared.
+pub(crate) fn compare(x: &MalObject, y: &MalObject) -> evaluator::Result<Ordering> {
+    use MalObject::*;
+    if let (Ok(xs), Ok(ys)) = (x.to_seq(), y.to_seq()) {
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            match compare(x, y)? {
+                Ordering::Equal => {}
+                unequal => return Ok(unequal),
+            }
+        }
+        return Ok(xs.len().cmp(&ys.len()));
+    }
+    Ok(match (x, y) {
+        (Nil, Nil) => Ordering::Equal,
+        (Nil, _) => Ordering::Less,
+        (_, Nil) => Ordering::Greater,
+        (Integer(x), Integer(y)) => x.cmp(y),
+        (Bool(x), Bool(y)) => x.cmp(y),
+        (String(x), String(y)) | (Keyword(x), Keyword(y)) => x.cmp(y),
+        (Symbol(x), Symbol(y)) => x.0.cmp(&y.0),
+        (Char(x), Char(y)) => x.cmp(y),
+        (Instant(x), Instant(y)) => x.cmp(y),
+        _ => return Err(TypeMismatch::NotComparable.into()),
+    })
+}
+
+// A comparator may return a number, negative when its first argument comes
+// first, or be a predicate saying whether its first argument comes first.
+fn call_comparator(f: &MalObject, x: &MalObject, y: &MalObject) -> evaluator::Result<Ordering> {
+    match apply_fully(f, &[x.clone(), y.clone()])? {
+        MalObject::Integer(n) => Ok(n.cmp(&0)),
+        result if truthy(&result) => Ok(Ordering::Less),
+        _ => match truthy(&apply_fully(f, &[y.clone(), x.clone()])?) {
+            true => Ok(Ordering::Greater),
+            false => Ok(Ordering::Equal),
+        },
+    }
+}
+
+// A stable sort by keys, which stops at the first error from the comparator.
+fn sort_keyed(
+    mut keyed: Vec<(MalObject, MalObject)>,
+    comparator: Option<&MalObject>,
+) -> evaluator::Result {
+    let mut error = None;
+    keyed.sort_by(|(x, _), (y, _)| {
+        if error.is_some() {
+            return Ordering::Equal;
+        }
+        let ordering = match comparator {
+            Some(f) => call_comparator(f, x, y),
+            None => compare(x, y),
+        };
+        ordering.unwrap_or_else(|e| {
+            error = Some(e);
+            Ordering::Equal
+        })
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(MalObject::wrap_list(
+            keyed.into_iter().map(|(_, x)| x).collect(),
+        )),
+    }
+}
+
+pub(crate) const SORT: PrimitiveFn = PrimitiveFn {
+    name: "sort",
+    fn_ptr: sort,
+    arity: Arity::Between(1..=2),
+};
+
+// (sort coll) or (sort comparator coll).
+fn sort(args: &[MalObject]) -> evaluator::Result {
+    let (comparator, coll) = match args {
+        [comparator, coll] => (Some(comparator), coll),
+        _ => (None, &args[0]),
+    };
+    let keyed = elements(coll)?
+        .iter()
+        .map(|x| (x.clone(), x.clone()))
+        .collect();
+    sort_keyed(keyed, comparator)
+}
+
+pub(crate) const SORT_BY: PrimitiveFn = PrimitiveFn {
+    name: "sort-by",
+    fn_ptr: sort_by,
+    arity: Arity::Between(2..=3),
+};
+
+// (sort-by keyfn coll) or (sort-by keyfn comparator coll). Calls keyfn once per
+// element.
+fn sort_by(args: &[MalObject]) -> evaluator::Result {
+    let comparator = match args.len() {
+        3 => Some(&args[1]),
+        _ => None,
+    };
+    let coll = elements(&args[args.len() - 1])?;
+    let keys = map_over(&args[0], &args[args.len() - 1..])?;
+    sort_keyed(
+        keys.into_iter().zip(coll.iter().cloned()).collect(),
+        comparator,
+    )
+}
+
+pub(crate) const GROUP_BY: PrimitiveFn = PrimitiveFn {
+    name: "group-by",
+    fn_ptr: group_by,
+    arity: Arity::exactly(2),
+};
+
+// A map from each (f x) to a vector of the xs, in order.
+fn group_by(args: &[MalObject]) -> evaluator::Result {
+    let mut groups: HashMap<HashKey, Vec<MalObject>> = HashMap::new();
+    for x in elements(&args[1])?.iter() {
+        let key = apply_fully(&args[0], std::slice::from_ref(x))?.as_hashkey()?;
+        groups.entry(key).or_default().push(x.clone());
+    }
+    Ok(MalObject::wrap_map(
+        groups
+            .into_iter()
+            .map(|(key, group)| (key, MalObject::wrap_vector(group)))
+            .collect(),
+    ))
+}
+
+pub(crate) const FREQUENCIES: PrimitiveFn = PrimitiveFn {
+    name: "frequencies",
+    fn_ptr: frequencies,
+    arity: Arity::exactly(1),
+};
+
+// A map from each distinct element to how many times it appears.
+fn frequencies(args: &[MalObject]) -> evaluator::Result {
+    let mut counts = HashMap::new();
+    for x in elements(&args[0])?.iter() {
+        *counts.entry(x.as_hashkey()?).or_insert(0) += 1;
+    }
+    Ok(MalObject::wrap_map(
+        counts
+            .into_iter()
+            .map(|(key, n)| (key, MalObject::Integer(n)))
+            .collect(),
+    ))
+}
+
+pub(crate) const PARTITION: PrimitiveFn = PrimitiveFn {
+    name: "partition",
+    fn_ptr: partition,
+    arity: Arity::Between(2..=4),
+};
+
+// (partition n coll) splits coll into lists of n elements, leaving out any left
+// over at the end. (partition n step coll) starts a list every step elements.
+// (partition n step pad coll) fills up the last list from pad, which may leave
+// it short.
+fn partition(args: &[MalObject]) -> evaluator::Result {
+    let n = count(&args[0])?;
+    let step = match args.len() {
+        2 => n,
+        _ => count(&args[1])?,
+    };
+    if n == 0 || step == 0 {
+        return Err(TypeMismatch::OutOfRange.into());
+    }
+    let pad = match args.len() {
+        4 => Some(elements(&args[2])?),
+        _ => None,
+    };
+    let coll = elements(&args[args.len() - 1])?;
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while start < coll.len() {
+        let mut part = coll[start..coll.len().min(start + n)].to_vec();
+        if part.len() < n {
+            if let Some(pad) = &pad {
+                part.extend(pad.iter().take(n - part.len()).cloned());
+                parts.push(MalObject::wrap_list(part));
+            }
+            break;
+        }
+        parts.push(MalObject::wrap_list(part));
+        start += step;
+    }
+    Ok(MalObject::wrap_list(parts))
+}
+
+pub(crate) const INTERLEAVE: PrimitiveFn = PrimitiveFn {
+    name: "interleave",
+    fn_ptr: interleave,
+    arity: Arity::at_least(0),
+};
+
+// The first element of each collection, then the second, and so on, until the
+// shortest runs out.
+fn interleave(args: &[MalObject]) -> evaluator::Result {
+    let colls: Vec<_> = args.iter().map(elements).collect::<Result<_, _>>()?;
+    let shortest = colls.iter().map(|coll| coll.len()).min().unwrap_or(0);
+    let mut result = Vec::with_capacity(shortest * colls.len());
+    for i in 0..shortest {
+        result.extend(colls.iter().map(|coll| coll[i].clone()));
+    }
+    Ok(MalObject::wrap_list(result))
+}
+
+pub(crate) const ZIPMAP: PrimitiveFn = PrimitiveFn {
+    name: "zipmap",
+    fn_ptr: zipmap,
+    arity: Arity::exactly(2),
+};
+
+// A map from keys to the corresponding vals, as far as the shorter goes.
+fn zipmap(args: &[MalObject]) -> evaluator::Result {
+    let keys = elements(&args[0])?;
+    let vals = elements(&args[1])?;
+    let mut map = HashMap::new();
+    for (key, val) in keys.iter().zip(vals.iter()) {
+        map.insert(key.as_hashkey()?, val.clone());
+    }
+    Ok(MalObject::wrap_map(map))
+}
@@ -0,0 +1,410 @@
+//! `serde` support for `MalObject`, enabled by the `serde` feature.
+//!
+//! Lists, vectors and lazy sequences (which are realised completely) serialize
+//! as sequences and maps as maps. Keywords become strings with a leading `:`,
+//! and such strings deserialize back to keywords. Symbols become plain strings.
+//! Functions and atoms can't be serialized.
+//!
+//! `from_mal` deserializes Rust values straight from mal data. Keywords stand
+//! for their bare name there, so a struct can be read from a map like
+//! `{:name "x" :port 80}`.
+//!
+//! ```
+//! use rust_dmr_mal::{reader, serialization};
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize, Debug, PartialEq)]
+//! struct Server {
+//!     name: String,
+//!     ports: Vec<u16>,
+//!     backup: Option<String>,
+//! }
+//!
+//! let obj = reader::read_str(r#"{:name "db" :ports [5432 5433] :backup nil}"#).unwrap();
+//! let server: Server = serialization::from_mal(&obj).unwrap();
+//! assert_eq!(server.ports, vec![5432, 5433]);
+//! ```
+
+use crate::types::{HashKey, MalInt, MalObject};
+use crate::{lazy, time};
+use serde::de::{
+    self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess,
+    Visitor,
+};
+use serde::ser::{self, Serialize, Serializer};
+use serde::{forward_to_deserialize_any, Deserialize};
+use std::collections::hash_map;
+use std::convert::TryFrom;
+use std::fmt;
+
+#[derive(Debug)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+impl Serialize for MalObject {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            MalObject::Nil => serializer.serialize_unit(),
+            MalObject::Bool(b) => serializer.serialize_bool(*b),
+            MalObject::Integer(i) => serializer.serialize_i64(*i as i64),
+            MalObject::String(s) => serializer.serialize_str(s),
+            MalObject::Char(c) => serializer.serialize_char(*c),
+            MalObject::Instant(i) => serializer.serialize_str(&time::iso_8601(i)),
+            MalObject::Keyword(k) => serializer.collect_str(&format_args!(":{}", k)),
+            MalObject::Symbol(s) => serializer.serialize_str(s.as_ref()),
+            MalObject::List(list) => serializer.collect_seq(&list.payload),
+            MalObject::LazySeq(_) => match lazy::realise_all(self) {
+                Ok(elements) => serializer.collect_seq(&elements),
+                Err(e) => Err(ser::Error::custom(e)),
+            },
+            MalObject::Vector(vec) => serializer.collect_seq(&vec.payload),
+            MalObject::Map(map) => serializer.collect_map(&map.payload),
+            MalObject::Primitive(_)
+            | MalObject::Closure(_)
+            | MalObject::Eval(_)
+            | MalObject::Atom(_)
+            | MalObject::Regex(_)
+            | MalObject::Process(_)
+            | MalObject::Reduced(_) => Err(ser::Error::custom(format!("can't serialize {}", self))),
+        }
+    }
+}
+
+impl Serialize for HashKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            HashKey::String(s) => serializer.serialize_str(s),
+            HashKey::Keyword(k) => serializer.collect_str(&format_args!(":{}", k)),
+        }
+    }
+}
+
+struct MalObjectVisitor;
+
+impl<'de> Visitor<'de> for MalObjectVisitor {
+    type Value = MalObject;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "nil, a boolean, an integer, a string, a sequence or a map"
+        )
+    }
+
+    fn visit_unit<E>(self) -> Result<MalObject, E> {
+        Ok(MalObject::Nil)
+    }
+
+    fn visit_none<E>(self) -> Result<MalObject, E> {
+        Ok(MalObject::Nil)
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, d: D) -> Result<MalObject, D::Error> {
+        MalObject::deserialize(d)
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(self, d: D) -> Result<MalObject, D::Error> {
+        MalObject::deserialize(d)
+    }
+
+    fn visit_bool<E>(self, b: bool) -> Result<MalObject, E> {
+        Ok(MalObject::Bool(b))
+    }
+
+    fn visit_i64<E: de::Error>(self, i: i64) -> Result<MalObject, E> {
+        MalInt::try_from(i)
+            .map(MalObject::Integer)
+            .map_err(|_| E::custom(format!("integer {} out of range", i)))
+    }
+
+    fn visit_u64<E: de::Error>(self, i: u64) -> Result<MalObject, E> {
+        MalInt::try_from(i)
+            .map(MalObject::Integer)
+            .map_err(|_| E::custom(format!("integer {} out of range", i)))
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<MalObject, E> {
+        Ok(match s.strip_prefix(':') {
+            Some(keyword) => MalObject::Keyword(keyword.into()),
+            None => MalObject::String(s.into()),
+        })
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<MalObject, A::Error> {
+        let mut elements = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(element) = seq.next_element()? {
+            elements.push(element);
+        }
+        Ok(MalObject::wrap_vector(elements))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<MalObject, A::Error> {
+        let mut entries = hash_map::HashMap::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some((key, value)) = map.next_entry::<MalObject, MalObject>()? {
+            let key = key
+                .as_hashkey()
+                .map_err(|_| de::Error::custom(format!("{} can't be a map key", key)))?;
+            entries.insert(key, value);
+        }
+        Ok(MalObject::wrap_map(entries))
+    }
+}
+
+impl<'de> Deserialize<'de> for MalObject {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(MalObjectVisitor)
+    }
+}
+
+/// Deserialize a `T` from mal data.
+pub fn from_mal<'de, T: Deserialize<'de>>(obj: &'de MalObject) -> Result<T, Error> {
+    T::deserialize(Deserializer::new(obj))
+}
+
+pub struct Deserializer<'de> {
+    input: &'de MalObject,
+}
+
+impl<'de> Deserializer<'de> {
+    pub fn new(input: &'de MalObject) -> Self {
+        Deserializer { input }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.input {
+            MalObject::Nil => visitor.visit_unit(),
+            MalObject::Bool(b) => visitor.visit_bool(*b),
+            MalObject::Integer(i) => visitor.visit_i64(*i as i64),
+            MalObject::String(s) => visitor.visit_borrowed_str(s),
+            MalObject::Char(c) => visitor.visit_char(*c),
+            MalObject::Keyword(k) => visitor.visit_string(format!(":{}", k)),
+            MalObject::Symbol(s) => visitor.visit_borrowed_str(s.as_ref()),
+            MalObject::List(list) => visitor.visit_seq(Elements(list.payload.iter())),
+            MalObject::Vector(vec) => visitor.visit_seq(Elements(vec.payload.iter())),
+            MalObject::Map(map) => visitor.visit_map(Entries {
+                iter: map.payload.iter(),
+                value: None,
+            }),
+            _ => Err(de::Error::custom(format!(
+                "can't deserialize {}",
+                self.input
+            ))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.input {
+            MalObject::Nil => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    // Where a string is wanted, a keyword stands for its name.
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.input {
+            MalObject::Keyword(k) => visitor.visit_borrowed_str(k),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // Unit variants are written as a string or keyword, and others as a map with a
+    // single entry, e.g. `{:circle 3}`.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.input {
+            MalObject::String(s) | MalObject::Keyword(s) => {
+                visitor.visit_enum(s.as_str().into_deserializer())
+            }
+            MalObject::Map(map) if map.payload.len() == 1 => {
+                let (variant, value) = map.payload.iter().next().unwrap();
+                visitor.visit_enum(Variant { variant, value })
+            }
+            _ => Err(de::Error::custom(format!(
+                "expected an enum variant, found {}",
+                self.input
+            ))),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char bytes byte_buf unit
+        unit_struct seq tuple tuple_struct map struct ignored_any
+    }
+}
+
+struct Elements<'de>(std::slice::Iter<'de, MalObject>);
+
+impl<'de> SeqAccess<'de> for Elements<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        self.0
+            .next()
+            .map(|obj| seed.deserialize(Deserializer::new(obj)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct Entries<'de> {
+    iter: hash_map::Iter<'de, HashKey, MalObject>,
+    value: Option<&'de MalObject>,
+}
+
+impl<'de> MapAccess<'de> for Entries<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(KeyDeserializer(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(Deserializer::new(value)),
+            None => Err(de::Error::custom("map value requested before its key")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct KeyDeserializer<'de>(&'de HashKey);
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            HashKey::String(s) => visitor.visit_borrowed_str(s),
+            HashKey::Keyword(k) => visitor.visit_string(format!(":{}", k)),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            HashKey::String(s) | HashKey::Keyword(s) => visitor.visit_borrowed_str(s),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.0 {
+            HashKey::String(s) | HashKey::Keyword(s) => {
+                visitor.visit_enum(s.as_str().into_deserializer())
+            }
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char bytes byte_buf option unit
+        unit_struct newtype_struct seq tuple tuple_struct map struct ignored_any
+    }
+}
+
+struct Variant<'de> {
+    variant: &'de HashKey,
+    value: &'de MalObject,
+}
+
+impl<'de> EnumAccess<'de> for Variant<'de> {
+    type Error = Error;
+    type Variant = Deserializer<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Deserializer<'de>), Error> {
+        let variant = seed.deserialize(KeyDeserializer(self.variant))?;
+        Ok((variant, Deserializer::new(self.value)))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
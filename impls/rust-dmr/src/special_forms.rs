@@ -1,12 +1,13 @@
 use crate::types::{
-    truthy, Arity, BadArgCount, BadClosureParameters, Closure, ClosureParameters, MalObject,
-    MalSymbol, TypeMismatch,
+    truthy, Arity, BadArgCount, BadClosureParameters, Closure, ClosureParameters, HashKey,
+    MalObject, MalSymbol, TypeMismatch,
 };
 use itertools::Itertools;
 
 use crate::environment::Environment;
 use crate::evaluator::{Error, ErrorDuringCatch, EvalContext, Result, EVAL};
 use crate::special_forms::FnError::{BadVariadic, ParameterNotASymbol};
+use crate::{gc, lazy, namespaces};
 use std::rc::Rc;
 
 #[derive(Debug)]
@@ -20,10 +21,7 @@ pub fn apply_def(args: &[MalObject], env: &Rc<Environment>, make_macro: bool) ->
         2 => Ok((&args[0], &args[1])),
         n => Err(Error::Def(DefError::WrongArgCount(n))),
     }?;
-    let key = match key {
-        MalObject::Symbol(s) => Ok(s),
-        _ => Err(Error::Def(DefError::KeyNotASymbol)),
-    }?;
+    let (key, meta) = def_target(key)?;
     let value = EVAL(value, env)?;
     let value = match make_macro {
         true => match value {
@@ -37,10 +35,43 @@ pub fn apply_def(args: &[MalObject], env: &Rc<Environment>, make_macro: bool) ->
         false => value,
     };
     env.set(key.clone(), value.clone());
+    if has_flag(meta, "private") {
+        namespaces::make_private(env, key);
+    }
+    if has_flag(meta, "dynamic") {
+        namespaces::make_dynamic(env, key);
+    }
     // Shouldn't this return a reference to the object in the map?
     Ok(value)
 }
 
+// `name`, or `^meta name` which the reader turns into `(with-meta name meta)`.
+fn def_target(key: &MalObject) -> Result<(&MalSymbol, Option<&MalObject>)> {
+    match key {
+        MalObject::Symbol(s) => Ok((s, None)),
+        MalObject::List(list) => match &list.payload[..] {
+            [MalObject::Symbol(head), MalObject::Symbol(s), meta]
+                if head.as_str() == "with-meta" =>
+            {
+                Ok((s, Some(meta)))
+            }
+            _ => Err(Error::Def(DefError::KeyNotASymbol)),
+        },
+        _ => Err(Error::Def(DefError::KeyNotASymbol)),
+    }
+}
+
+// Whether metadata such as `:private` or `{:private true}` sets `flag`.
+fn has_flag(meta: Option<&MalObject>, flag: &str) -> bool {
+    match meta {
+        Some(MalObject::Keyword(k)) => k == flag,
+        Some(MalObject::Map(map)) => {
+            matches!(map.payload.get(&HashKey::Keyword(flag.into())), Some(v) if truthy(v))
+        }
+        _ => false,
+    }
+}
+
 #[derive(Debug)]
 pub enum LetError {
     WrongArgCount(usize),
@@ -50,7 +81,9 @@ pub enum LetError {
     BindToNonSymbol,
 }
 
-pub fn apply_let(args: &[MalObject], env: &Rc<Environment>) -> Result {
+// Like `if` and `do`, returns the form in tail position for `EVAL` to carry on
+// with, so that loops written as tail calls don't grow the stack.
+pub fn apply_let(args: &[MalObject], env: &Rc<Environment>) -> Result<EvalContext> {
     let (bindings, obj) = match args.len() {
         2 => Ok((&args[0], &args[1])),
         n => Err(Error::Let(LetError::WrongArgCount(n))),
@@ -59,7 +92,7 @@ pub fn apply_let(args: &[MalObject], env: &Rc<Environment>) -> Result {
         .as_seq()
         .or(Err(Error::Let(LetError::BindingsNotSequence)))?;
     match bindings.len() % 2 == 0 {
-        true => make_let_environment(bindings, env).and_then(|child| EVAL(obj, &child)),
+        true => make_let_environment(bindings, env).map(|child| (obj.clone(), child)),
         false => Err(Error::Let(LetError::BindingsOddLength)),
     }
 }
@@ -89,32 +122,90 @@ fn make_let_environment(
     Ok(child)
 }
 
+#[derive(Debug)]
+pub enum BindingError {
+    WrongArgCount(usize),
+    BindingsNotSequence,
+    BindingsOddLength,
+    BindToNonSymbol,
+}
+
+// (binding [name value ...] body): the values are all evaluated before any of
+// the dynamic variables are rebound.
+pub fn apply_binding(args: &[MalObject], env: &Rc<Environment>) -> Result {
+    let (bindings, body) = match args {
+        [bindings, body] => Ok((bindings, body)),
+        _ => Err(Error::Binding(BindingError::WrongArgCount(args.len()))),
+    }?;
+    let bindings = bindings
+        .as_seq()
+        .or(Err(Error::Binding(BindingError::BindingsNotSequence)))?;
+    if bindings.len() % 2 != 0 {
+        return Err(Error::Binding(BindingError::BindingsOddLength));
+    }
+    let mut values = Vec::new();
+    for (key, value) in bindings.iter().tuples() {
+        let key = key
+            .as_symbol()
+            .or(Err(Error::Binding(BindingError::BindToNonSymbol)))?;
+        values.push((key.clone(), EVAL(value, env)?));
+    }
+    with_bindings(values, env, || EVAL(body, env))
+}
+
+/// Call `body` with each dynamic variable named in `bindings` set to the value
+/// given, then restore the old values whether or not it succeeded.
+pub(crate) fn with_bindings<F>(
+    bindings: Vec<(MalSymbol, MalObject)>,
+    env: &Rc<Environment>,
+    body: F,
+) -> Result
+where
+    F: FnOnce() -> Result,
+{
+    // Find every variable before changing any of them.
+    let mut vars = Vec::new();
+    for (name, value) in bindings {
+        let (owner, name) = namespaces::dynamic_var(env, &name)?;
+        vars.push((owner, name, value));
+    }
+    let saved: Vec<_> = vars
+        .into_iter()
+        .map(|(owner, name, value)| {
+            let old = owner.set(name.clone(), value).unwrap();
+            (owner, name, old)
+        })
+        .collect();
+    let result = body();
+    for (owner, name, old) in saved.into_iter().rev() {
+        owner.set(name, old);
+    }
+    result
+}
+
 #[derive(Debug)]
 pub enum DoError {
     NothingToDo,
 }
 
-pub fn apply_do(args: &[MalObject], env: &Rc<Environment>) -> Result<MalObject> {
-    if args.is_empty() {
-        return Err(Error::Do(DoError::NothingToDo));
+pub fn apply_do(args: &[MalObject], env: &Rc<Environment>) -> Result<EvalContext> {
+    let (last, init) = args.split_last().ok_or(Error::Do(DoError::NothingToDo))?;
+    for obj in init {
+        EVAL(obj, env)?;
     }
-    let result: std::result::Result<Vec<MalObject>, _> =
-        args.iter().map(|obj| EVAL(obj, env)).collect();
-    Ok(result?.pop().unwrap())
+    Ok((last.clone(), env.clone()))
 }
 
-pub fn apply_if(args: &[MalObject], env: &Rc<Environment>) -> Result {
+pub fn apply_if(args: &[MalObject], env: &Rc<Environment>) -> Result<EvalContext> {
     Arity::Between(2..=3)
         .validate_for(args.len(), "if")
         .map_err(Error::BadArgCount)?;
     let condition = EVAL(&args[0], env)?;
-    if truthy(&condition) {
-        EVAL(&args[1], env)
-    } else if args.len() == 3 {
-        EVAL(&args[2], env)
-    } else {
-        Ok(MalObject::Nil)
-    }
+    let branch = match truthy(&condition) {
+        true => args[1].clone(),
+        false => args.get(2).cloned().unwrap_or(MalObject::Nil),
+    };
+    Ok((branch, env.clone()))
 }
 
 #[derive(Debug)]
@@ -146,16 +237,37 @@ pub fn apply_fn(args: &[MalObject], env: &Rc<Environment>) -> Result {
         parameters.iter().map(extract_symbol).collect();
 
     let parameters = parameters.map_err(Error::Fn)?;
+    gc::track_environment(env);
     let closure = Closure {
         parameters: ClosureParameters::new(parameters).map_err(|e| Error::Fn(BadVariadic(e)))?,
         body: body.clone(),
         parent: env.clone(),
         is_macro: false,
         meta: MalObject::Nil,
+        compiled: None,
     };
     Ok(MalObject::Closure(Rc::new(closure)))
 }
 
+// (lazy-seq body...) is (fn* () (do body...)), called when the sequence is
+// first looked at.
+pub(crate) fn lazy_seq_body(args: &[MalObject]) -> MalObject {
+    match args {
+        [] => MalObject::Nil,
+        [form] => form.clone(),
+        forms => {
+            let mut body = vec![MalObject::new_symbol("do")];
+            body.extend_from_slice(forms);
+            MalObject::wrap_list(body)
+        }
+    }
+}
+
+pub fn apply_lazy_seq(args: &[MalObject], env: &Rc<Environment>) -> Result {
+    let thunk = apply_fn(&[MalObject::new_list(), lazy_seq_body(args)], env)?;
+    Ok(lazy::from_fn(thunk))
+}
+
 pub(crate) fn apply_quasiquote(ast: &MalObject) -> std::result::Result<MalObject, BadArgCount> {
     match ast.as_seq().ok() {
         None => Ok(MalObject::wrap_list(vec![
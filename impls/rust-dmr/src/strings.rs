@@ -1,6 +1,7 @@
 // I think Mal keeps things simple and only defines the escapes \n, \" and \\ in
-// a string literal. Let's implement exactly that rather than inheriting rust's
-// string literal behaviour.
+// a string literal. We add \t, \r and \u{...} (as in rust, one to six hex
+// digits) so that any string can be written down, but stop there rather than
+// inheriting all of rust's string literal behaviour.
 
 use bimap::BiMap;
 use std::str::Chars;
@@ -11,6 +12,18 @@ lazy_static! {
         m.insert('\\', '\\');
         m.insert('"', '"');
         m.insert('n', '\n');
+        m.insert('t', '\t');
+        m.insert('r', '\r');
+        m
+    };
+    static ref CHAR_NAMES: BiMap<&'static str, char> = {
+        let mut m = BiMap::new();
+        m.insert("newline", '\n');
+        m.insert("return", '\r');
+        m.insert("space", ' ');
+        m.insert("tab", '\t');
+        m.insert("formfeed", '\u{c}');
+        m.insert("backspace", '\u{8}');
         m
     };
 }
@@ -28,6 +41,27 @@ impl<'a> StringBuilder<'a> {
 pub enum BuildError {
     UnknownEscape(char),
     UnexpectedSingleBackslash,
+    BadUnicodeEscape(String),
+}
+
+impl StringBuilder<'_> {
+    // The part of a `\u{...}` escape after the `u`.
+    fn unicode_escape(&mut self) -> Result<char, BuildError> {
+        let mut escape = String::new();
+        for c in &mut self.chars {
+            escape.push(c);
+            if c == '}' {
+                break;
+            }
+        }
+        escape
+            .strip_prefix('{')
+            .and_then(|rest| rest.strip_suffix('}'))
+            .filter(|hex| (1..=6).contains(&hex.len()))
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .and_then(std::char::from_u32)
+            .ok_or(BuildError::BadUnicodeEscape(escape))
+    }
 }
 
 impl Iterator for StringBuilder<'_> {
@@ -37,6 +71,7 @@ impl Iterator for StringBuilder<'_> {
         let result = match self.chars.next()? {
             '\\' => match self.chars.next() {
                 None => Err(BuildError::UnexpectedSingleBackslash),
+                Some('u') => self.unicode_escape(),
                 Some(c) => ESCAPES
                     .get_by_left(&c)
                     .copied()
@@ -52,40 +87,45 @@ pub(crate) fn build_string(src: &str) -> Result<String, BuildError> {
     StringBuilder::new(src).collect()
 }
 
-struct StringPrinter<'a> {
-    chars: Chars<'a>,
-}
-
-impl<'a> StringPrinter<'a> {
-    fn new(src: &'a str) -> Self {
-        Self { chars: src.chars() }
+// Other control characters are written as `\u{...}` escapes.
+pub(crate) fn string_repr(src: &str) -> String {
+    let mut output = String::new();
+    output.push('"');
+    for c in src.chars() {
+        match ESCAPES.get_by_right(&c) {
+            Some(&escape) => {
+                output.push('\\');
+                output.push(escape);
+            }
+            None if c.is_control() => output.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            None => output.push(c),
+        }
     }
+    output.push('"');
+    output
 }
 
-impl Iterator for StringPrinter<'_> {
-    type Item = (char, Option<char>);
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let next = self.chars.next()?;
-        let charseq = match ESCAPES.get_by_right(&next) {
-            Some(&l) => ('\\', Some(l)),
-            None => (next, None),
-        };
-        Some(charseq)
+// Character literals are a backslash followed by the character itself, its
+// name, or `u` and exactly four hex digits (e.g. `\a`, `\space`, `\u00e9`).
+pub(crate) fn build_char(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => CHAR_NAMES.get_by_left(&name).copied().or_else(|| {
+            name.strip_prefix('u')
+                .filter(|hex| hex.len() == 4)
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .and_then(std::char::from_u32)
+        }),
     }
 }
 
-pub(crate) fn string_repr(src: &str) -> String {
-    let mut output = String::new();
-    output.push('"');
-    for (char1, char2) in StringPrinter::new(src) {
-        output.push(char1);
-        if let Some(char2) = char2 {
-            output.push(char2)
-        };
+pub(crate) fn char_repr(c: char) -> String {
+    match CHAR_NAMES.get_by_right(&c) {
+        Some(name) => format!("\\{}", name),
+        None if c.is_control() => format!("\\u{:04x}", c as u32),
+        None => format!("\\{}", c),
     }
-    output.push('"');
-    output
 }
 
 // TODO should make baby tests to ensure that read_str and print_str_repr are
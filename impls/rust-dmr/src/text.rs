@@ -0,0 +1,385 @@
+// String manipulation and regex primitives.
+//
+// Mal strings are Rust strings, so they're UTF-8 underneath. Every index these
+// functions take or return counts characters rather than bytes, so `(subs "héllo" 2)`
+// is "llo" and never splits a character in half.
+
+use crate::types::{Arity, HashKey, MalInt, MalObject, PrimitiveFn};
+use crate::{evaluator, printer, types};
+use itertools::Itertools;
+use regex::{Captures, Regex};
+use std::convert::TryFrom;
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Debug)]
+pub enum FormatError {
+    UnknownDirective(char),
+    /// A `%` at the end of the format string.
+    UnfinishedDirective,
+    MissingArgument(usize),
+    UnusedArguments(usize),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::UnknownDirective(c) => write!(f, "unknown directive %{}", c),
+            FormatError::UnfinishedDirective => write!(f, "format string ends with %"),
+            FormatError::MissingArgument(n) => write!(f, "no argument for directive {}", n),
+            FormatError::UnusedArguments(n) => write!(f, "{} unused argument(s)", n),
+        }
+    }
+}
+
+fn as_str(obj: &MalObject) -> evaluator::Result<&str> {
+    Ok(obj.as_string()?)
+}
+
+// Convert a character index to a byte offset into `s`. One past the last
+// character is allowed, as it's a valid end of a range.
+fn byte_offset(s: &str, index: MalInt) -> evaluator::Result<usize> {
+    let count = s.chars().count();
+    let offset = usize::try_from(index)
+        .ok()
+        .filter(|&i| i <= count)
+        .map(|i| {
+            s.char_indices()
+                .nth(i)
+                .map_or(s.len(), |(offset, _)| offset)
+        });
+    offset.ok_or(evaluator::Error::BadIndex(index, 0..count + 1))
+}
+
+fn char_index(s: &str, offset: usize) -> MalInt {
+    s[..offset].chars().count() as MalInt
+}
+
+pub(crate) const SUBS: PrimitiveFn = PrimitiveFn {
+    name: "subs",
+    fn_ptr: subs,
+    arity: Arity::Between(2..=3),
+};
+
+fn subs(args: &[MalObject]) -> evaluator::Result {
+    let s = as_str(&args[0])?;
+    let start = args[1].as_int()?;
+    let end = match args.get(2) {
+        Some(end) => end.as_int()?,
+        None => s.chars().count() as MalInt,
+    };
+    let (from, to) = (byte_offset(s, start)?, byte_offset(s, end)?);
+    if end < start {
+        let count = s.chars().count();
+        return Err(evaluator::Error::BadIndex(end, start as usize..count + 1));
+    }
+    Ok(MalObject::String(s[from..to].to_string()))
+}
+
+fn strings(parts: impl Iterator<Item = impl Into<String>>) -> MalObject {
+    MalObject::wrap_vector(parts.map(|s| MalObject::String(s.into())).collect())
+}
+
+pub(crate) const SPLIT: PrimitiveFn = PrimitiveFn {
+    name: "split",
+    fn_ptr: split,
+    arity: Arity::exactly(2),
+};
+
+// The separator is a string or a regex. An empty string separator splits the
+// string into its characters, and so does a regex which matches the empty
+// string, because we drop the empty pieces left by an empty match at either
+// end.
+fn split(args: &[MalObject]) -> evaluator::Result {
+    let s = as_str(&args[0])?;
+    Ok(match &args[1] {
+        MalObject::Regex(re) => {
+            let mut pieces: Vec<&str> = re.split(s).collect();
+            if re.find_iter(s).last().is_some_and(|m| m.start() == s.len()) {
+                pieces.pop();
+            }
+            if re.find(s).is_some_and(|m| m.end() == 0) && !pieces.is_empty() {
+                pieces.remove(0);
+            }
+            strings(pieces.into_iter())
+        }
+        separator => match as_str(separator)? {
+            "" => strings(s.chars().map(String::from)),
+            separator => strings(s.split(separator)),
+        },
+    })
+}
+
+pub(crate) const JOIN: PrimitiveFn = PrimitiveFn {
+    name: "join",
+    fn_ptr: join,
+    arity: Arity::Between(1..=2),
+};
+
+// (join coll) or (join separator coll). Elements are joined as `str` would print them.
+fn join(args: &[MalObject]) -> evaluator::Result {
+    let (separator, coll) = match args {
+        [coll] => ("", coll),
+        [separator, coll] => (as_str(separator)?, coll),
+        _ => unreachable!(),
+    };
+    let elements = match coll {
+        MalObject::Nil => Default::default(),
+        coll => coll.to_seq()?,
+    };
+    let joined = elements
+        .iter()
+        .map(|obj| printer::pr_str(obj, printer::PrintMode::Directly))
+        .join(separator);
+    Ok(MalObject::String(joined))
+}
+
+macro_rules! string_transform {
+    ($NAME:ident, $symbol:expr, $transform:expr) => {
+        pub(crate) const $NAME: PrimitiveFn = PrimitiveFn {
+            name: $symbol,
+            fn_ptr: |args| {
+                let transform: fn(&str) -> String = $transform;
+                Ok(MalObject::String(transform(as_str(&args[0])?)))
+            },
+            arity: Arity::exactly(1),
+        };
+    };
+}
+
+string_transform!(TRIM, "trim", |s| s.trim().to_string());
+string_transform!(UPPER_CASE, "upper-case", str::to_uppercase);
+string_transform!(LOWER_CASE, "lower-case", str::to_lowercase);
+string_transform!(REVERSE, "string/reverse", |s| s.chars().rev().collect());
+
+pub(crate) const STARTS_WITH: PrimitiveFn = PrimitiveFn {
+    name: "starts-with?",
+    fn_ptr: |args| {
+        Ok(MalObject::Bool(
+            as_str(&args[0])?.starts_with(as_str(&args[1])?),
+        ))
+    },
+    arity: Arity::exactly(2),
+};
+
+pub(crate) const ENDS_WITH: PrimitiveFn = PrimitiveFn {
+    name: "ends-with?",
+    fn_ptr: |args| {
+        Ok(MalObject::Bool(
+            as_str(&args[0])?.ends_with(as_str(&args[1])?),
+        ))
+    },
+    arity: Arity::exactly(2),
+};
+
+pub(crate) const INDEX_OF: PrimitiveFn = PrimitiveFn {
+    name: "index-of",
+    fn_ptr: index_of,
+    arity: Arity::Between(2..=3),
+};
+
+// The character index of the first occurrence of a substring, optionally
+// searching from a given index, or nil if there isn't one.
+fn index_of(args: &[MalObject]) -> evaluator::Result {
+    let s = as_str(&args[0])?;
+    let needle = as_str(&args[1])?;
+    let from = match args.get(2) {
+        Some(from) => byte_offset(s, from.as_int()?)?,
+        None => 0,
+    };
+    Ok(match s[from..].find(needle) {
+        Some(offset) => MalObject::Integer(char_index(s, from + offset)),
+        None => MalObject::Nil,
+    })
+}
+
+pub(crate) const REPLACE: PrimitiveFn = PrimitiveFn {
+    name: "replace",
+    fn_ptr: replace,
+    arity: Arity::exactly(3),
+};
+
+// Replace every occurrence of a substring or every match of a regex. With a
+// regex, the replacement may refer to groups as `$1` or `${name}`, or be a
+// function which is passed each match as `re-find` would return it.
+fn replace(args: &[MalObject]) -> evaluator::Result {
+    let s = as_str(&args[0])?;
+    let replaced = match (&args[1], &args[2]) {
+        (MalObject::Regex(re), to) if types::callable(to) => {
+            let mut output = String::with_capacity(s.len());
+            let mut last = 0;
+            for caps in re.captures_iter(s) {
+                let whole = caps.get(0).unwrap();
+                let replacement = evaluator::apply_fully(to, &[match_result(&caps)])?;
+                output.push_str(&s[last..whole.start()]);
+                output.push_str(&printer::pr_str(&replacement, printer::PrintMode::Directly));
+                last = whole.end();
+            }
+            output.push_str(&s[last..]);
+            output
+        }
+        (MalObject::Regex(re), to) => re.replace_all(s, as_str(to)?).into_owned(),
+        (from, to) => s.replace(as_str(from)?, as_str(to)?),
+    };
+    Ok(MalObject::String(replaced))
+}
+
+pub(crate) const FORMAT: PrimitiveFn = PrimitiveFn {
+    name: "format",
+    fn_ptr: format,
+    arity: Arity::at_least(1),
+};
+
+// A small subset of printf: `%s` prints any value as `str` would, `%d` an
+// integer and `%%` a literal percent sign.
+fn format(args: &[MalObject]) -> evaluator::Result {
+    let template = as_str(&args[0])?;
+    let mut values = args[1..].iter();
+    let mut used = 0;
+    let mut output = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+        let directive = chars.next().ok_or(FormatError::UnfinishedDirective)?;
+        if directive == '%' {
+            output.push('%');
+            continue;
+        }
+        if directive != 's' && directive != 'd' {
+            return Err(FormatError::UnknownDirective(directive).into());
+        }
+        used += 1;
+        let value = values.next().ok_or(FormatError::MissingArgument(used))?;
+        match directive {
+            's' => output.push_str(&printer::pr_str(value, printer::PrintMode::Directly)),
+            _ => output.push_str(&value.as_int()?.to_string()),
+        }
+    }
+    match values.len() {
+        0 => Ok(MalObject::String(output)),
+        n => Err(FormatError::UnusedArguments(n).into()),
+    }
+}
+
+// Regexes
+
+pub(crate) const RE_PATTERN: PrimitiveFn = PrimitiveFn {
+    name: "re-pattern",
+    fn_ptr: re_pattern,
+    arity: Arity::exactly(1),
+};
+
+fn re_pattern(args: &[MalObject]) -> evaluator::Result {
+    match &args[0] {
+        MalObject::Regex(_) => Ok(args[0].clone()),
+        pattern => Regex::new(as_str(pattern)?)
+            .map(|re| MalObject::Regex(Rc::new(re)))
+            .map_err(evaluator::Error::Regex),
+    }
+}
+
+// A match is returned as the matched string if the regex has no groups, and
+// otherwise as a vector of the whole match followed by each group. Groups which
+// didn't take part in the match are nil.
+fn match_result(caps: &Captures) -> MalObject {
+    let group = |m: Option<regex::Match>| match m {
+        Some(m) => MalObject::String(m.as_str().to_string()),
+        None => MalObject::Nil,
+    };
+    match caps.len() {
+        1 => group(caps.get(0)),
+        _ => MalObject::wrap_vector(caps.iter().map(group).collect()),
+    }
+}
+
+fn regex_args(args: &[MalObject]) -> evaluator::Result<(&Regex, &str)> {
+    Ok((args[0].as_regex()?, as_str(&args[1])?))
+}
+
+pub(crate) const RE_FIND: PrimitiveFn = PrimitiveFn {
+    name: "re-find",
+    fn_ptr: re_find,
+    arity: Arity::exactly(2),
+};
+
+// The first match anywhere in the string, or nil.
+fn re_find(args: &[MalObject]) -> evaluator::Result {
+    let (re, s) = regex_args(args)?;
+    Ok(re
+        .captures(s)
+        .map_or(MalObject::Nil, |caps| match_result(&caps)))
+}
+
+pub(crate) const RE_MATCHES: PrimitiveFn = PrimitiveFn {
+    name: "re-matches",
+    fn_ptr: re_matches,
+    arity: Arity::exactly(2),
+};
+
+// Like re-find, but the regex has to match the whole string.
+fn re_matches(args: &[MalObject]) -> evaluator::Result {
+    let (re, s) = regex_args(args)?;
+    // Anchoring the pattern, rather than checking where the first match ends,
+    // means alternations like `a|ab` can still match all of "ab".
+    let anchored =
+        Regex::new(&format!(r"\A(?:{})\z", re.as_str())).map_err(evaluator::Error::Regex)?;
+    Ok(anchored
+        .captures(s)
+        .map_or(MalObject::Nil, |caps| match_result(&caps)))
+}
+
+pub(crate) const RE_SEQ: PrimitiveFn = PrimitiveFn {
+    name: "re-seq",
+    fn_ptr: re_seq,
+    arity: Arity::exactly(2),
+};
+
+// A list of every non-overlapping match, or nil if there aren't any.
+fn re_seq(args: &[MalObject]) -> evaluator::Result {
+    let (re, s) = regex_args(args)?;
+    let matches: Vec<_> = re
+        .captures_iter(s)
+        .map(|caps| match_result(&caps))
+        .collect();
+    Ok(match matches.is_empty() {
+        true => MalObject::Nil,
+        false => MalObject::wrap_list(matches),
+    })
+}
+
+pub(crate) const RE_GROUPS: PrimitiveFn = PrimitiveFn {
+    name: "re-groups",
+    fn_ptr: re_groups,
+    arity: Arity::exactly(2),
+};
+
+// The named groups of the first match, as a map from keywords to the captured
+// strings (or nil), or nil if there's no match.
+fn re_groups(args: &[MalObject]) -> evaluator::Result {
+    let (re, s) = regex_args(args)?;
+    let caps = match re.captures(s) {
+        Some(caps) => caps,
+        None => return Ok(MalObject::Nil),
+    };
+    let groups = re
+        .capture_names()
+        .flatten()
+        .map(|name| {
+            let value = match caps.name(name) {
+                Some(m) => MalObject::String(m.as_str().to_string()),
+                None => MalObject::Nil,
+            };
+            (HashKey::Keyword(name.to_string()), value)
+        })
+        .collect();
+    Ok(MalObject::wrap_map(groups))
+}
+
+impl From<FormatError> for evaluator::Error {
+    fn from(e: FormatError) -> Self {
+        evaluator::Error::Format(e)
+    }
+}
@@ -0,0 +1,319 @@
+// Dates, times and the clock.
+//
+// An instant is a point in time together with the UTC offset it's shown in, so
+// that converting to local time doesn't lose the moment it refers to. Instants
+// print as `#inst "2020-07-01T12:30:00Z"`, which both the reader and EDN read
+// back as the same instant. Instants are equal if they refer to the same moment
+// whatever their offsets. Durations are integers of milliseconds, like
+// `time-ms`.
+//
+// The clock can be frozen at a given instant (see `freeze`), after which
+// `now`, `time-ms` and `nano-time` all stay put, and `sleep` moves the clock
+// on instead of waiting. This is per thread, so tests can run in parallel.
+
+use crate::types::{Arity, HashKey, MalInt, MalObject, PrimitiveFn, TypeMismatch};
+use crate::{evaluator, limits};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{
+    DateTime, Datelike, FixedOffset, Local, NaiveDate, NaiveTime, SecondsFormat, TimeDelta,
+    Timelike, Utc,
+};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+pub type MalInstant = DateTime<FixedOffset>;
+
+#[derive(Debug)]
+pub enum Error {
+    BadInstant(String),
+    BadPattern(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BadInstant(s) => write!(f, "can't read an ISO-8601 instant from {:?}", s),
+            Error::BadPattern(s) => write!(f, "bad format pattern {:?}", s),
+        }
+    }
+}
+
+thread_local! {
+    static FROZEN: Cell<Option<DateTime<Utc>>> = const { Cell::new(None) };
+}
+
+lazy_static! {
+    // What `nano-time` counts from.
+    static ref STARTED: Instant = Instant::now();
+}
+
+/// Stop this thread's clock at `at`, or start it again if `at` is `None`.
+pub fn freeze(at: Option<DateTime<Utc>>) {
+    FROZEN.with(|frozen| frozen.set(at));
+}
+
+/// The current time, according to this thread's clock.
+pub fn now() -> DateTime<Utc> {
+    FROZEN.with(Cell::get).unwrap_or_else(Utc::now)
+}
+
+/// ISO-8601 (or rather RFC 3339), with fractional seconds only if needed.
+pub fn iso_8601(instant: &MalInstant) -> String {
+    instant.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// Read an instant written as in RFC 3339, such as `2020-07-01T12:30:00+01:00`.
+/// As in EDN, it may stop after any part, e.g. `2020-07` or `2020-07-01T12:30`:
+/// a missing offset means UTC, and the other missing parts are their earliest.
+pub fn parse_iso_8601(s: &str) -> Result<MalInstant, Error> {
+    DateTime::parse_from_rfc3339(s)
+        .ok()
+        .or_else(|| parse_partial(s))
+        .ok_or_else(|| Error::BadInstant(s.into()))
+}
+
+fn parse_partial(s: &str) -> Option<MalInstant> {
+    let (date, time) = match s.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (s, None),
+    };
+    let date = match date.len() {
+        4 => NaiveDate::parse_from_str(&format!("{}-01-01", date), "%Y-%m-%d"),
+        7 => NaiveDate::parse_from_str(&format!("{}-01", date), "%Y-%m-%d"),
+        _ => NaiveDate::parse_from_str(date, "%Y-%m-%d"),
+    }
+    .ok()?;
+    let (time, offset) = match time {
+        None => (NaiveTime::MIN, "Z"),
+        Some(time) => {
+            let (time, offset) = time.split_at(time.find(['Z', '+', '-']).unwrap_or(time.len()));
+            let time = NaiveTime::parse_from_str(time, "%H:%M:%S%.f")
+                .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+                .ok()?;
+            (time, offset)
+        }
+    };
+    let offset = match offset {
+        "" | "Z" => FixedOffset::east_opt(0)?,
+        offset => offset.parse().ok()?,
+    };
+    date.and_time(time).and_local_timezone(offset).single()
+}
+
+fn as_instant(obj: &MalObject) -> evaluator::Result<&MalInstant> {
+    match obj {
+        MalObject::Instant(instant) => Ok(instant),
+        _ => Err(TypeMismatch::NotAnInstant.into()),
+    }
+}
+
+fn milliseconds(obj: &MalObject) -> evaluator::Result<TimeDelta> {
+    TimeDelta::try_milliseconds(obj.as_int()? as i64).ok_or_else(|| TypeMismatch::OutOfRange.into())
+}
+
+fn from_millis(ms: MalInt) -> evaluator::Result<MalInstant> {
+    DateTime::from_timestamp_millis(ms as i64)
+        .map(|instant| instant.fixed_offset())
+        .ok_or_else(|| TypeMismatch::OutOfRange.into())
+}
+
+pub(crate) const TIME_MS: PrimitiveFn = PrimitiveFn {
+    name: "time-ms",
+    fn_ptr: |_| Ok(MalObject::Integer(now().timestamp_millis() as MalInt)),
+    arity: Arity::exactly(0),
+};
+
+pub(crate) const NANO_TIME: PrimitiveFn = PrimitiveFn {
+    name: "nano-time",
+    fn_ptr: nano_time,
+    arity: Arity::exactly(0),
+};
+
+// Nanoseconds from some arbitrary starting point, for timing things. Unlike
+// `time-ms`, this never goes backwards when the system clock is changed.
+fn nano_time(_args: &[MalObject]) -> evaluator::Result {
+    let nanos = match FROZEN.with(Cell::get) {
+        Some(frozen) => frozen.timestamp_nanos_opt().unwrap_or_default(),
+        None => STARTED.elapsed().as_nanos() as i64,
+    };
+    Ok(MalObject::Integer(nanos as MalInt))
+}
+
+pub(crate) const SLEEP: PrimitiveFn = PrimitiveFn {
+    name: "sleep",
+    fn_ptr: sleep,
+    arity: Arity::exactly(1),
+};
+
+// (sleep ms). Sleeps in short slices so that Ctrl-C and timeouts still work.
+fn sleep(args: &[MalObject]) -> evaluator::Result {
+    let delta = milliseconds(&args[0])?;
+    if let Some(frozen) = FROZEN.with(Cell::get) {
+        let later = frozen.checked_add_signed(delta.max(TimeDelta::zero()));
+        freeze(Some(later.ok_or(TypeMismatch::OutOfRange)?));
+        return Ok(MalObject::Nil);
+    }
+    const SLICE: Duration = Duration::from_millis(10);
+    let wake = Instant::now() + delta.to_std().unwrap_or_default();
+    loop {
+        limits::check()?;
+        let left = wake.saturating_duration_since(Instant::now());
+        if left == Duration::default() {
+            return Ok(MalObject::Nil);
+        }
+        std::thread::sleep(left.min(SLICE));
+    }
+}
+
+pub(crate) const NOW: PrimitiveFn = PrimitiveFn {
+    name: "now",
+    fn_ptr: |_| Ok(MalObject::Instant(now().fixed_offset())),
+    arity: Arity::exactly(0),
+};
+
+pub(crate) const FREEZE_CLOCK: PrimitiveFn = PrimitiveFn {
+    name: "freeze-clock!",
+    fn_ptr: freeze_clock,
+    arity: Arity::exactly(1),
+};
+
+// (freeze-clock! instant) stops the clock; (freeze-clock! nil) restarts it.
+fn freeze_clock(args: &[MalObject]) -> evaluator::Result {
+    match &args[0] {
+        MalObject::Nil => freeze(None),
+        instant => freeze(Some(as_instant(instant)?.with_timezone(&Utc))),
+    }
+    Ok(MalObject::Nil)
+}
+
+pub(crate) const INSTANT_TEST: PrimitiveFn = PrimitiveFn {
+    name: "inst?",
+    fn_ptr: |args| Ok(MalObject::Bool(matches!(args[0], MalObject::Instant(_)))),
+    arity: Arity::exactly(1),
+};
+
+pub(crate) const INSTANT: PrimitiveFn = PrimitiveFn {
+    name: "inst",
+    fn_ptr: instant,
+    arity: Arity::exactly(1),
+};
+
+// An instant from milliseconds since the Unix epoch, or an ISO-8601 string.
+fn instant(args: &[MalObject]) -> evaluator::Result {
+    let instant = match &args[0] {
+        MalObject::Integer(ms) => from_millis(*ms)?,
+        MalObject::Instant(instant) => *instant,
+        other => parse_iso_8601(other.as_string()?).map_err(evaluator::Error::Time)?,
+    };
+    Ok(MalObject::Instant(instant))
+}
+
+pub(crate) const INST_MS: PrimitiveFn = PrimitiveFn {
+    name: "inst-ms",
+    fn_ptr: |args| {
+        let instant = as_instant(&args[0])?;
+        Ok(MalObject::Integer(instant.timestamp_millis() as MalInt))
+    },
+    arity: Arity::exactly(1),
+};
+
+pub(crate) const FORMAT_INST: PrimitiveFn = PrimitiveFn {
+    name: "format-inst",
+    fn_ptr: format_inst,
+    arity: Arity::Between(1..=2),
+};
+
+// ISO-8601 by default, or following a strftime-style pattern such as
+// "%Y-%m-%d %H:%M".
+fn format_inst(args: &[MalObject]) -> evaluator::Result {
+    let instant = as_instant(&args[0])?;
+    let pattern = match args.get(1) {
+        Some(pattern) => pattern.as_string()?,
+        None => return Ok(MalObject::String(iso_8601(instant))),
+    };
+    let items: Vec<Item> = StrftimeItems::new(pattern).collect();
+    if items.contains(&Item::Error) {
+        return Err(evaluator::Error::Time(Error::BadPattern(pattern.into())));
+    }
+    let formatted = instant.format_with_items(items.into_iter()).to_string();
+    Ok(MalObject::String(formatted))
+}
+
+pub(crate) const TO_UTC: PrimitiveFn = PrimitiveFn {
+    name: "to-utc",
+    fn_ptr: |args| {
+        Ok(MalObject::Instant(
+            as_instant(&args[0])?.with_timezone(&Utc).fixed_offset(),
+        ))
+    },
+    arity: Arity::exactly(1),
+};
+
+pub(crate) const TO_LOCAL: PrimitiveFn = PrimitiveFn {
+    name: "to-local",
+    fn_ptr: |args| {
+        let local = as_instant(&args[0])?.with_timezone(&Local);
+        Ok(MalObject::Instant(local.fixed_offset()))
+    },
+    arity: Arity::exactly(1),
+};
+
+pub(crate) const INST_PLUS: PrimitiveFn = PrimitiveFn {
+    name: "inst-plus",
+    fn_ptr: inst_plus,
+    arity: Arity::exactly(2),
+};
+
+// (inst-plus instant ms), keeping the instant's offset.
+fn inst_plus(args: &[MalObject]) -> evaluator::Result {
+    let later = as_instant(&args[0])?.checked_add_signed(milliseconds(&args[1])?);
+    Ok(MalObject::Instant(later.ok_or(TypeMismatch::OutOfRange)?))
+}
+
+pub(crate) const INST_DIFF: PrimitiveFn = PrimitiveFn {
+    name: "inst-diff",
+    fn_ptr: inst_diff,
+    arity: Arity::exactly(2),
+};
+
+// (inst-diff from to) is how many milliseconds `to` is after `from`.
+fn inst_diff(args: &[MalObject]) -> evaluator::Result {
+    let from = as_instant(&args[0])?;
+    let to = as_instant(&args[1])?;
+    let ms = to.signed_duration_since(*from).num_milliseconds();
+    Ok(MalObject::Integer(ms as MalInt))
+}
+
+pub(crate) const INST_FIELDS: PrimitiveFn = PrimitiveFn {
+    name: "inst-fields",
+    fn_ptr: inst_fields,
+    arity: Arity::exactly(1),
+};
+
+// The calendar date and time of day, in the instant's own offset:
+// {:year 2020 :month 7 :day 1 :hour 12 :minute 30 :second 0 :nanosecond 0
+//  :weekday 3 :offset 3600}. Weekdays count from Monday as 1, and the offset is
+// in seconds east of UTC.
+fn inst_fields(args: &[MalObject]) -> evaluator::Result {
+    let instant = as_instant(&args[0])?;
+    let fields = [
+        ("year", instant.year() as i64),
+        ("month", instant.month().into()),
+        ("day", instant.day().into()),
+        ("hour", instant.hour().into()),
+        ("minute", instant.minute().into()),
+        ("second", instant.second().into()),
+        ("nanosecond", instant.nanosecond().into()),
+        ("weekday", instant.weekday().number_from_monday().into()),
+        ("offset", instant.offset().local_minus_utc().into()),
+    ];
+    let mut map = HashMap::new();
+    for (name, value) in fields.iter() {
+        let value = MalInt::try_from(*value).map_err(|_| TypeMismatch::OutOfRange)?;
+        map.insert(HashKey::Keyword((*name).into()), MalObject::Integer(value));
+    }
+    Ok(MalObject::wrap_map(map))
+}
@@ -36,6 +36,10 @@ pub enum Token<'a> {
     StringLiteral(StringLiteral<'a>),
     Comment(&'a str),
     PlainChars(&'a str),
+    /// A character literal like `\a` or `\newline`, without the backslash.
+    Char(&'a str),
+    /// A regex literal like `#"[0-9]+"`. The payload is what's between the quotes.
+    Regex(StringLiteral<'a>),
 }
 
 impl<'a> fmt::Debug for Token<'a> {
@@ -47,6 +51,8 @@ impl<'a> fmt::Debug for Token<'a> {
             Token::UnaryOp(t) => write!(f, "UnaryOp({:?})", t),
             Token::Comment(t) => write!(f, "Comment({:?})", t),
             Token::PlainChars(t) => write!(f, "PlainChars({:?})", t),
+            Token::Char(t) => write!(f, "Char({:?})", t),
+            Token::Regex(s) => write!(f, "Regex(#\"{}\")", s.payload),
         }
     }
 }
@@ -98,15 +104,23 @@ fn create_token(captured: &str) -> Result<Token, TokenizerError> {
         b'^' => Ok(Token::UnaryOp(WithMeta)),
         b'@' => Ok(Token::UnaryOp(Deref)),
         // String literal
-        b'"' => tokenize_string_literal(bytes),
+        b'"' => tokenize_string_literal(bytes).map(Token::StringLiteral),
+        b'#' if bytes.get(1) == Some(&b'"') => {
+            tokenize_string_literal(&bytes[1..]).map(Token::Regex)
+        }
         // Comment. Note that ; is ASCII so safe to slice on bytes even if the rest of the string is
         // non ASCII.
         b';' => Ok(Token::Comment(&captured[1..])),
+        // Likewise for backslash.
+        b'\\' => Ok(Token::Char(&captured[1..])),
+        // A #! line is a comment too, so that scripts can start with a shebang
+        // however they're loaded.
+        b'#' if bytes.get(1) == Some(&b'!') => Ok(Token::Comment(&captured[2..])),
         _ => Ok(Token::PlainChars(&captured)),
     }
 }
 
-fn tokenize_string_literal(bytes: &[u8]) -> Result<Token, TokenizerError> {
+fn tokenize_string_literal(bytes: &[u8]) -> Result<StringLiteral<'_>, TokenizerError> {
     if bytes.len() == 1 || bytes[bytes.len() - 1] != b'"' {
         return Err(TokenizerError::UnbalancedString);
     }
@@ -121,9 +135,9 @@ fn tokenize_string_literal(bytes: &[u8]) -> Result<Token, TokenizerError> {
         return Err(TokenizerError::UnbalancedString);
     }
 
-    Ok(Token::StringLiteral(StringLiteral {
+    Ok(StringLiteral {
         payload: std::str::from_utf8(&bytes[1..bytes.len() - 1]).unwrap(),
-    }))
+    })
 }
 
 pub fn tokenize(input: &str) -> Result<Vec<Token>, TokenizerError> {
@@ -139,7 +153,13 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, TokenizerError> {
                         |[^\\"]              #    anything which isn't a backslash or a quote 
                       )*
                       "?                     #    possibly missing a closing quote
+                    |\#"(?:\\.|[^\\"])*"?   # regex literal, with the same rules as strings
                     |;.*                     # comments
+                    |\#!.*                   # shebang lines, also comments
+                    |\\(?:                   # character literal: a backslash followed by
+                        [^\s\[\]{}()"`,;]+  #    a name or a single plain character
+                        |.                   #    or any single character
+                      )
                     |[^\s\[\]{}('\\"`,;)]*   # zero or more plain characters
                 )
                 [\s,]*                       # whitespace or commas, ignored
@@ -1,10 +1,14 @@
 extern crate derive_more;
+use crate::compiler::Chunk;
 use crate::environment::Environment;
+use crate::interpreter::Engine;
 use crate::strings::BuildError;
 use crate::tokens::StringLiteral;
-use crate::{evaluator, strings};
+use crate::{evaluator, gc, lazy, process, strings, time};
 use derive_more::Deref;
 use itertools::Itertools;
+use regex::Regex;
+use std::borrow::Cow;
 use std::cell::{Ref, RefCell};
 use std::collections::HashMap;
 
@@ -24,7 +28,7 @@ pub struct MalVector {
     pub meta: MalObject,
 }
 
-type MalMapInternal = HashMap<HashKey, MalObject>;
+pub type MalMapInternal = HashMap<HashKey, MalObject>;
 #[derive(Clone, Debug)]
 pub struct MalMap {
     pub payload: MalMapInternal,
@@ -51,7 +55,7 @@ pub enum Arity {
 
 #[derive(Debug)]
 pub struct BadArgCount {
-    name: &'static str,
+    name: String,
     expected: Arity,
     got: usize,
 }
@@ -67,11 +71,11 @@ impl fmt::Display for BadArgCount {
 }
 
 impl Arity {
-    pub(crate) const fn exactly(n: usize) -> Self {
+    pub const fn exactly(n: usize) -> Self {
         Self::Between(n..=n)
     }
 
-    pub(crate) const fn at_least(n: usize) -> Self {
+    pub const fn at_least(n: usize) -> Self {
         Self::AtLeast(n..)
     }
 
@@ -84,11 +88,11 @@ impl Arity {
         }
     }
 
-    pub(crate) fn validate_for(&self, n: usize, name: &'static str) -> Result<(), BadArgCount> {
+    pub(crate) fn validate_for(&self, n: usize, name: &str) -> Result<(), BadArgCount> {
         match self.contains(n) {
             true => Ok(()),
             false => Err(BadArgCount {
-                name,
+                name: name.into(),
                 expected: self.clone(),
                 got: n,
             }),
@@ -125,15 +129,62 @@ impl fmt::Debug for PrimitiveFn {
     }
 }
 
+pub type NativeFn = dyn Fn(&[MalObject]) -> evaluator::Result;
+
+/// A primitive implemented by a Rust closure, which may capture state from the
+/// host application. See `Environment::register`.
+pub struct BoxedPrimitiveFn {
+    pub name: String,
+    pub arity: Arity,
+    pub func: Box<NativeFn>,
+}
+
+impl fmt::Debug for BoxedPrimitiveFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "boxed primitive function #<{}>", self.name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PrimitivePayload {
+    /// One of the built-in functions in `core::CORE`.
+    Static(&'static PrimitiveFn),
+    Boxed(Rc<BoxedPrimitiveFn>),
+}
+
+impl PrimitivePayload {
+    pub fn name(&self) -> &str {
+        match self {
+            PrimitivePayload::Static(func) => func.name,
+            PrimitivePayload::Boxed(func) => &func.name,
+        }
+    }
+
+    pub fn arity(&self) -> &Arity {
+        match self {
+            PrimitivePayload::Static(func) => &func.arity,
+            PrimitivePayload::Boxed(func) => &func.arity,
+        }
+    }
+
+    pub(crate) fn call(&self, args: &[MalObject]) -> evaluator::Result {
+        match self {
+            PrimitivePayload::Static(func) => (func.fn_ptr)(args),
+            PrimitivePayload::Boxed(func) => (func.func)(args),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PrimitiveFnRef {
-    pub payload: &'static PrimitiveFn,
+    pub payload: PrimitivePayload,
     pub meta: Box<MalObject>,
 }
 
 #[derive(Clone)]
 pub struct PrimitiveEval {
     pub env: rc::Weak<Environment>,
+    pub engine: Engine,
 }
 
 impl fmt::Debug for PrimitiveEval {
@@ -211,6 +262,8 @@ pub struct Closure {
     pub parent: Rc<Environment>,
     pub is_macro: bool,
     pub meta: MalObject,
+    // Present if the body has been compiled to bytecode; such closures are run by the `vm`.
+    pub compiled: Option<Rc<Chunk>>,
 }
 
 impl fmt::Debug for Closure {
@@ -227,25 +280,25 @@ impl fmt::Debug for Closure {
 
 #[derive(Debug, Clone)]
 pub struct Atom {
-    payload: Rc<RefCell<MalObject>>,
+    pub(crate) payload: Rc<RefCell<MalObject>>,
 }
 
 impl Atom {
-    pub(crate) fn new(obj: &MalObject) -> Self {
-        Self {
-            payload: Rc::new(RefCell::new(obj.clone())),
-        }
+    pub fn new(obj: &MalObject) -> Self {
+        let payload = Rc::new(RefCell::new(obj.clone()));
+        gc::track_atom(&payload);
+        Self { payload }
     }
 
     pub(crate) fn borrow_payload(&self) -> Ref<MalObject> {
         self.payload.borrow()
     }
 
-    pub(crate) fn clone_payload(&self) -> MalObject {
+    pub fn clone_payload(&self) -> MalObject {
         self.payload.borrow().clone()
     }
 
-    pub(crate) fn replace(&self, obj: &MalObject) {
+    pub fn replace(&self, obj: &MalObject) {
         self.payload.replace(obj.clone());
     }
 }
@@ -256,6 +309,7 @@ pub enum MalObject {
     Integer(MalInt),
     Bool(bool),
     String(String),
+    Char(char),
     Symbol(MalSymbol),
     Keyword(String),
     List(Rc<MalList>),
@@ -265,13 +319,20 @@ pub enum MalObject {
     Closure(Rc<Closure>),
     Eval(PrimitiveEval),
     Atom(Atom),
+    Regex(Rc<Regex>),
+    Process(Rc<process::Process>),
+    Instant(time::MalInstant),
+    LazySeq(Rc<lazy::LazySeq>),
+    /// A value wrapped by `reduced`, to end a reduction early.
+    Reduced(Rc<MalObject>),
 }
 
 pub(crate) fn truthy(obj: &MalObject) -> bool {
     use MalObject::*;
     match obj {
-        List(_) | Vector(_) | Map(_) | Integer(_) | Symbol(_) | String(_) | Keyword(_)
-        | Primitive(_) | Closure(_) | Eval(_) | Atom(_) => true,
+        List(_) | Vector(_) | Map(_) | Integer(_) | Symbol(_) | String(_) | Char(_)
+        | Keyword(_) | Primitive(_) | Closure(_) | Eval(_) | Atom(_) | Regex(_) | Process(_)
+        | Instant(_) | LazySeq(_) | Reduced(_) => true,
         Bool(t) => *t,
         Nil => false,
     }
@@ -285,12 +346,18 @@ pub(crate) fn callable(obj: &MalObject) -> bool {
         Integer(_) => false,
         Bool(_) => false,
         String(_) => false,
+        Char(_) => false,
         Symbol(_) => false,
         Keyword(_) => false,
         List(_) => false,
         Vector(_) => false,
         Map(_) => false,
         Atom(_) => false,
+        Regex(_) => false,
+        Process(_) => false,
+        Instant(_) => false,
+        LazySeq(_) => false,
+        Reduced(_) => false,
     }
 }
 
@@ -301,32 +368,39 @@ pub enum TypeMismatch {
     NotASequence,
     NotASymbol,
     NotAString,
+    NotAChar,
     NotAnAtom,
     NotCallable,
     NotAClosure,
     NotIntoKeyword,
     NotABool,
     NotAMap,
+    NotARegex,
+    NotAProcess,
+    NotAnInstant,
     NotAValidKey,
+    NotComparable,
     CantHoldMetadata,
+    WrongLength { expected: usize, got: usize },
+    OutOfRange,
 }
 
 impl MalObject {
-    pub(crate) fn as_int(&self) -> Result<MalInt, TypeMismatch> {
+    pub fn as_int(&self) -> Result<MalInt, TypeMismatch> {
         match self {
             MalObject::Integer(x) => Ok(*x),
             _ => Err(TypeMismatch::NotAnInt),
         }
     }
 
-    pub(crate) fn as_list(&self) -> Result<&MalList, TypeMismatch> {
+    pub fn as_list(&self) -> Result<&MalList, TypeMismatch> {
         match self {
             MalObject::List(x) => Ok(x),
             _ => Err(TypeMismatch::NotAList),
         }
     }
 
-    pub(crate) fn as_seq(&self) -> Result<&[MalObject], TypeMismatch> {
+    pub fn as_seq(&self) -> Result<&[MalObject], TypeMismatch> {
         match self {
             MalObject::List(x) => Ok(&x.payload),
             MalObject::Vector(x) => Ok(&x.payload),
@@ -334,48 +408,70 @@ impl MalObject {
         }
     }
 
-    pub(crate) fn as_map(&self) -> Result<&MalMapInternal, TypeMismatch> {
+    /// Like `as_seq`, but also accepts lazy sequences, realising them completely.
+    pub fn to_seq(&self) -> evaluator::Result<Cow<'_, [MalObject]>> {
+        match self {
+            MalObject::LazySeq(_) => lazy::realise_all(self).map(Cow::Owned),
+            _ => Ok(Cow::Borrowed(self.as_seq()?)),
+        }
+    }
+
+    pub fn as_map(&self) -> Result<&MalMapInternal, TypeMismatch> {
         match self {
             MalObject::Map(x) => Ok(&x.payload),
             _ => Err(TypeMismatch::NotAMap),
         }
     }
 
-    pub(crate) fn as_symbol(&self) -> Result<&MalSymbol, TypeMismatch> {
+    pub fn as_symbol(&self) -> Result<&MalSymbol, TypeMismatch> {
         match self {
             MalObject::Symbol(s) => Ok(s),
             _ => Err(TypeMismatch::NotASymbol),
         }
     }
 
-    pub(crate) fn as_closure(&self) -> Result<&Closure, TypeMismatch> {
+    pub fn as_closure(&self) -> Result<&Closure, TypeMismatch> {
         match self {
             MalObject::Closure(c) => Ok(c),
             _ => Err(TypeMismatch::NotAClosure),
         }
     }
 
-    pub(crate) fn as_string(&self) -> Result<&str, TypeMismatch> {
+    pub fn as_string(&self) -> Result<&str, TypeMismatch> {
         match self {
             MalObject::String(s) => Ok(s),
             _ => Err(TypeMismatch::NotAString),
         }
     }
 
-    pub(crate) fn as_atom(&self) -> Result<&Atom, TypeMismatch> {
+    pub fn as_char(&self) -> Result<char, TypeMismatch> {
+        match self {
+            MalObject::Char(c) => Ok(*c),
+            _ => Err(TypeMismatch::NotAChar),
+        }
+    }
+
+    pub fn as_regex(&self) -> Result<&Regex, TypeMismatch> {
+        match self {
+            MalObject::Regex(re) => Ok(re),
+            _ => Err(TypeMismatch::NotARegex),
+        }
+    }
+
+    pub fn as_atom(&self) -> Result<&Atom, TypeMismatch> {
         match self {
             MalObject::Atom(a) => Ok(a),
             _ => Err(TypeMismatch::NotAnAtom),
         }
     }
-    pub(crate) fn as_bool(&self) -> Result<bool, TypeMismatch> {
+    pub fn as_bool(&self) -> Result<bool, TypeMismatch> {
         match self {
             MalObject::Bool(b) => Ok(b.clone()),
             _ => Err(TypeMismatch::NotABool),
         }
     }
 
-    pub(crate) fn as_hashkey(&self) -> Result<HashKey, TypeMismatch> {
+    pub fn as_hashkey(&self) -> Result<HashKey, TypeMismatch> {
         match self {
             MalObject::String(s) => Ok(HashKey::String(s.clone())),
             MalObject::Keyword(s) => Ok(HashKey::Keyword(s.clone())),
@@ -383,67 +479,67 @@ impl MalObject {
         }
     }
 
-    pub(crate) fn is_nil(&self) -> bool {
+    pub fn is_nil(&self) -> bool {
         match self {
             MalObject::Nil => true,
             _ => false,
         }
     }
-    pub(crate) fn is_list(&self) -> bool {
+    pub fn is_list(&self) -> bool {
         match self {
             MalObject::List(_) => true,
             _ => false,
         }
     }
-    pub(crate) fn is_vector(&self) -> bool {
+    pub fn is_vector(&self) -> bool {
         match self {
             MalObject::Vector(_) => true,
             _ => false,
         }
     }
-    pub(crate) fn is_seq(&self) -> bool {
+    pub fn is_seq(&self) -> bool {
         match self {
-            MalObject::Vector(_) | MalObject::List(_) => true,
+            MalObject::Vector(_) | MalObject::List(_) | MalObject::LazySeq(_) => true,
             _ => false,
         }
     }
-    pub(crate) fn is_atom(&self) -> bool {
+    pub fn is_atom(&self) -> bool {
         match self {
             MalObject::Atom(_) => true,
             _ => false,
         }
     }
-    pub(crate) fn is_symbol(&self) -> bool {
+    pub fn is_symbol(&self) -> bool {
         match self {
             MalObject::Symbol(_) => true,
             _ => false,
         }
     }
-    pub(crate) fn is_keyword(&self) -> bool {
+    pub fn is_keyword(&self) -> bool {
         match self {
             MalObject::Keyword(_) => true,
             _ => false,
         }
     }
-    pub(crate) fn is_map(&self) -> bool {
+    pub fn is_map(&self) -> bool {
         match self {
             MalObject::Map(_) => true,
             _ => false,
         }
     }
-    pub(crate) fn is_string(&self) -> bool {
+    pub fn is_string(&self) -> bool {
         match self {
             MalObject::String(_) => true,
             _ => false,
         }
     }
-    pub(crate) fn is_number(&self) -> bool {
+    pub fn is_number(&self) -> bool {
         match self {
             MalObject::Integer(_) => true,
             _ => false,
         }
     }
-    pub(crate) fn is_macro(&self) -> bool {
+    pub fn is_macro(&self) -> bool {
         match self {
             MalObject::Closure(f) => f.is_macro,
             _ => false,
@@ -459,7 +555,7 @@ pub enum HashKey {
 }
 
 impl HashKey {
-    pub(crate) fn into_mal_object(&self) -> MalObject {
+    pub fn into_mal_object(&self) -> MalObject {
         match self {
             HashKey::String(x) => MalObject::String(x.clone()),
             HashKey::Keyword(x) => MalObject::Keyword(x.clone()),
@@ -495,32 +591,53 @@ pub(crate) fn build_string(src: &StringLiteral) -> Result<MalObject, BuildError>
     strings::build_string(src.payload).map(MalObject::String)
 }
 
+// Regex literals are passed to the regex crate as written, except that `\"`
+// is needed to put a quote inside one.
+pub(crate) fn build_regex(src: &StringLiteral) -> Result<MalObject, regex::Error> {
+    Regex::new(&src.payload.replace("\\\"", "\"")).map(|re| MalObject::Regex(Rc::new(re)))
+}
+
 impl MalObject {
-    pub(crate) fn new_list() -> Self {
+    pub fn new_list() -> Self {
         Self::wrap_list(Vec::new())
     }
-    pub(crate) fn wrap_list(elements: Vec<MalObject>) -> Self {
+    pub fn wrap_list(elements: Vec<MalObject>) -> Self {
         Self::List(Rc::new(MalList {
             payload: elements,
             meta: MalObject::Nil,
         }))
     }
-    pub(crate) fn wrap_map(map: MalMapInternal) -> Self {
+    pub fn wrap_map(map: MalMapInternal) -> Self {
         Self::Map(Rc::new(MalMap {
             payload: map,
             meta: MalObject::Nil,
         }))
     }
-    pub(crate) fn wrap_vector(elements: Vec<MalObject>) -> Self {
+    pub fn wrap_vector(elements: Vec<MalObject>) -> Self {
         Self::Vector(Rc::new(MalVector {
             payload: elements,
             meta: MalObject::Nil,
         }))
     }
-    pub(crate) fn new_symbol(name: &str) -> Self {
+    /// A primitive implemented by a Rust closure.
+    pub fn native<F>(name: &str, arity: Arity, func: F) -> Self
+    where
+        F: Fn(&[MalObject]) -> evaluator::Result + 'static,
+    {
+        let primitive = BoxedPrimitiveFn {
+            name: name.into(),
+            arity,
+            func: Box::new(func),
+        };
+        Self::Primitive(PrimitiveFnRef {
+            payload: PrimitivePayload::Boxed(Rc::new(primitive)),
+            meta: Box::new(MalObject::Nil),
+        })
+    }
+    pub fn new_symbol(name: &str) -> Self {
         Self::Symbol(MalSymbol(name.into()))
     }
-    pub(crate) fn new_keyword(name: &str) -> Self {
+    pub fn new_keyword(name: &str) -> Self {
         Self::Keyword(name.into())
     }
 }
@@ -531,10 +648,15 @@ impl PartialEq for MalObject {
         if let (Some(x), Some(y)) = (self.as_seq().ok(), other.as_seq().ok()) {
             return equal_sequences(x, &y);
         }
+        if lazy::is_lazy(self) || lazy::is_lazy(other) {
+            return equal_lazy(self, other);
+        }
         match [self, other] {
             [Integer(x), Integer(y)] => x == y,
             [Bool(x), Bool(y)] => x == y,
             [String(x), String(y)] => x == y,
+            [Char(x), Char(y)] => x == y,
+            [Instant(x), Instant(y)] => x == y,
             [Keyword(x), Keyword(y)] => x == y,
             [Symbol(x), Symbol(y)] => x == y,
             [Map(x), Map(y)] => equal_maps(x, y),
@@ -552,6 +674,22 @@ fn equal_sequences(xs: &[MalObject], ys: &[MalObject]) -> bool {
     xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| x == y)
 }
 
+// Lazy sequences are compared as far as they've been realised, and unequal to
+// everything if they haven't been completely. `=` realises them first.
+fn equal_lazy(x: &MalObject, y: &MalObject) -> bool {
+    let realised = |obj: &MalObject| match obj {
+        MalObject::LazySeq(_) => match lazy::realised_elements(obj, None) {
+            (elements, false) => Some(elements),
+            (_, true) => None,
+        },
+        _ => obj.as_seq().ok().map(<[MalObject]>::to_vec),
+    };
+    match (realised(x), realised(y)) {
+        (Some(xs), Some(ys)) => equal_sequences(&xs, &ys),
+        _ => false,
+    }
+}
+
 fn equal_maps(xs: &MalMap, ys: &MalMap) -> bool {
     xs.payload.len() == ys.payload.len()
         && xs
@@ -0,0 +1,270 @@
+// A stack machine which runs the bytecode produced by `compiler`.
+//
+// Calls to compiled closures push a new frame rather than recursing in Rust,
+// and tail calls replace the current frame, so neither deep recursion nor long
+// loops grow the native stack. Everything else (primitives, closures built by
+// the tree-walker, macros) is delegated to `evaluator::apply_fully`.
+
+use crate::compiler::{self, Chunk, Op};
+use crate::environment::Environment;
+use crate::evaluator::{self, Error, ErrorDuringCatch, Result};
+use crate::types::{self, Arity, Closure, MalObject, PrimitiveEval, TypeMismatch};
+use crate::{gc, lazy, limits, namespaces, special_forms};
+use std::rc::Rc;
+
+struct Frame {
+    chunk: Rc<Chunk>,
+    ip: usize,
+    env: Rc<Environment>,
+    // Height of the value stack when this frame was entered.
+    base: usize,
+}
+
+impl Frame {
+    fn new(chunk: Rc<Chunk>, env: Rc<Environment>, base: usize) -> Self {
+        Self {
+            chunk,
+            ip: 0,
+            env,
+            base,
+        }
+    }
+}
+
+/// Compile `ast` and run the result in `env`.
+#[allow(non_snake_case)]
+pub fn EVAL(ast: &MalObject, env: &Rc<Environment>) -> Result {
+    log::trace!("Compile {}", ast);
+    let chunk = compiler::compile(ast);
+    run(Rc::new(chunk), env.clone())
+}
+
+pub(crate) fn call_closure(func: &Closure, args: &[MalObject]) -> Result {
+    let chunk = func
+        .compiled
+        .clone()
+        .expect("call_closure: closure was not compiled");
+    let env = evaluator::make_closure_env(func, args)?;
+    run(chunk, env)
+}
+
+// What a call instruction should do once the callee has been examined.
+enum Callee {
+    Enter(Rc<Chunk>, Rc<Environment>),
+    Value(MalObject),
+}
+
+fn prepare_call(callee: &MalObject, args: &[MalObject]) -> Result<Callee> {
+    match callee {
+        MalObject::Closure(func) => match &func.compiled {
+            Some(chunk) => {
+                let env = evaluator::make_closure_env(func, args)?;
+                Ok(Callee::Enter(chunk.clone(), env))
+            }
+            None => evaluator::apply_fully(callee, args).map(Callee::Value),
+        },
+        MalObject::Eval(PrimitiveEval { env, .. }) => {
+            Arity::exactly(1)
+                .validate_for(args.len(), "eval")
+                .map_err(Error::BadArgCount)?;
+            let env = env.upgrade().expect("eval: env destroyed");
+            let env = namespaces::current(&env);
+            log::info!("Call from mal to EVAL with {}", args[0]);
+            let chunk = compiler::compile(&args[0]);
+            Ok(Callee::Enter(Rc::new(chunk), env))
+        }
+        _ => evaluator::apply_fully(callee, args).map(Callee::Value),
+    }
+}
+
+fn run(chunk: Rc<Chunk>, env: Rc<Environment>) -> Result {
+    let mut nesting = limits::Nesting::enter()?;
+    let mut stack: Vec<MalObject> = Vec::new();
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut frame = Frame::new(chunk, env, 0);
+    loop {
+        limits::step()?;
+        let op = frame.chunk.code[frame.ip];
+        frame.ip += 1;
+        match op {
+            Op::Constant(index) => stack.push(frame.chunk.constants[index].clone()),
+            Op::Lookup(index) => {
+                let symbol = frame.chunk.constants[index].as_symbol()?;
+                let value = frame.env.fetch(symbol).map_err(Error::UnknownSymbol)?;
+                stack.push(value);
+            }
+            Op::Define(index) => {
+                let symbol = frame.chunk.constants[index].as_symbol()?;
+                let value = stack.last().unwrap().clone();
+                log::debug!("define {} as {}", symbol, value);
+                frame.env.set(symbol.clone(), value);
+            }
+            Op::DefineMacro(index) => {
+                let symbol = frame.chunk.constants[index].as_symbol()?;
+                let value = match stack.pop().unwrap() {
+                    MalObject::Closure(c) => {
+                        let mut tweaked_closure = (*c).clone();
+                        tweaked_closure.is_macro = true;
+                        MalObject::Closure(Rc::new(tweaked_closure))
+                    }
+                    _ => return Err(Error::TypeMismatch(TypeMismatch::NotAClosure)),
+                };
+                frame.env.set(symbol.clone(), value.clone());
+                stack.push(value);
+            }
+            Op::Pop => {
+                stack.pop();
+            }
+            Op::Jump(target) => frame.ip = target,
+            Op::JumpIfFalse(target) => {
+                if !types::truthy(&stack.pop().unwrap()) {
+                    frame.ip = target;
+                }
+            }
+            Op::MakeLazySeq => {
+                let thunk = stack.pop().unwrap();
+                stack.push(lazy::from_fn(thunk));
+            }
+            Op::MakeClosure(index) => {
+                let lambda = &frame.chunk.lambdas[index];
+                gc::track_environment(&frame.env);
+                let closure = Closure {
+                    parameters: lambda.parameters.clone(),
+                    body: lambda.body.clone(),
+                    parent: frame.env.clone(),
+                    is_macro: false,
+                    meta: MalObject::Nil,
+                    compiled: Some(lambda.chunk.clone()),
+                };
+                stack.push(MalObject::Closure(Rc::new(closure)));
+            }
+            Op::Call(argc) => {
+                let args = stack.split_off(stack.len() - argc);
+                let callee = stack.pop().unwrap();
+                match prepare_call(&callee, &args)? {
+                    Callee::Enter(chunk, env) => {
+                        nesting.deepen()?;
+                        let caller =
+                            std::mem::replace(&mut frame, Frame::new(chunk, env, stack.len()));
+                        frames.push(caller);
+                    }
+                    Callee::Value(value) => stack.push(value),
+                }
+            }
+            Op::TailCall(argc) => {
+                let args = stack.split_off(stack.len() - argc);
+                let callee = stack.pop().unwrap();
+                match prepare_call(&callee, &args)? {
+                    Callee::Enter(chunk, env) => {
+                        stack.truncate(frame.base);
+                        frame = Frame::new(chunk, env, frame.base);
+                    }
+                    Callee::Value(value) => {
+                        stack.truncate(frame.base);
+                        match frames.pop() {
+                            Some(caller) => {
+                                nesting.shallower();
+                                frame = caller;
+                                stack.push(value);
+                            }
+                            None => return Ok(value),
+                        }
+                    }
+                }
+            }
+            Op::MacroCall(index, end) => {
+                if let Some(chunk) = expand_macro_call(&frame.chunk.constants[index], &frame.env)? {
+                    nesting.deepen()?;
+                    frame.ip = end;
+                    let env = frame.env.clone();
+                    let caller = std::mem::replace(&mut frame, Frame::new(chunk, env, stack.len()));
+                    frames.push(caller);
+                }
+            }
+            Op::TailMacroCall(index) => {
+                if let Some(chunk) = expand_macro_call(&frame.chunk.constants[index], &frame.env)? {
+                    stack.truncate(frame.base);
+                    let env = frame.env.clone();
+                    frame = Frame::new(chunk, env, frame.base);
+                }
+            }
+            Op::Return => {
+                let value = stack.pop().unwrap();
+                stack.truncate(frame.base);
+                match frames.pop() {
+                    Some(caller) => {
+                        nesting.shallower();
+                        frame = caller;
+                        stack.push(value);
+                    }
+                    None => return Ok(value),
+                }
+            }
+            Op::PushEnv => frame.env = Environment::spawn_from(&frame.env),
+            Op::PopEnv => {
+                let parent = frame.env.parent().expect("PopEnv: no parent").clone();
+                frame.env = parent;
+            }
+            Op::MakeVector(n) => {
+                let elements = stack.split_off(stack.len() - n);
+                stack.push(MalObject::wrap_vector(elements));
+            }
+            Op::MakeMap(n) => {
+                let entries = stack.split_off(stack.len() - n);
+                let map = types::build_map(entries)
+                    .map_err(|_| Error::TypeMismatch(TypeMismatch::NotAValidKey))?;
+                stack.push(map);
+            }
+            Op::Try(index) => {
+                let value = run_try(&frame.chunk.try_blocks[index], &frame.env)?;
+                stack.push(value);
+            }
+            Op::Binding(index) => {
+                let block = &frame.chunk.binding_blocks[index];
+                let values = stack.split_off(stack.len() - block.names.len());
+                let bindings = block.names.iter().cloned().zip(values).collect();
+                let value = special_forms::with_bindings(bindings, &frame.env, || {
+                    run(block.body.clone(), frame.env.clone())
+                })?;
+                stack.push(value);
+            }
+            Op::Macroexpand(index) => {
+                let value = evaluator::macroexpand(&frame.chunk.constants[index], &frame.env)?;
+                stack.push(value);
+            }
+            Op::Interpret(index) => {
+                let value = evaluator::EVAL(&frame.chunk.constants[index], &frame.env)?;
+                stack.push(value);
+            }
+        }
+    }
+}
+
+// The compiled expansion of `form`, if it's a call to a macro.
+fn expand_macro_call(form: &MalObject, env: &Rc<Environment>) -> Result<Option<Rc<Chunk>>> {
+    if evaluator::is_macro_call(form, env).is_none() {
+        return Ok(None);
+    }
+    let expanded = evaluator::macroexpand(form, env)?;
+    Ok(Some(Rc::new(compiler::compile(&expanded))))
+}
+
+fn run_try(block: &compiler::TryBlock, env: &Rc<Environment>) -> Result {
+    let original = match run(block.body.clone(), env.clone()) {
+        Ok(value) => return Ok(value),
+        Err(original) => original,
+    };
+    match &block.catch {
+        None => Err(original),
+        Some((exception_name, handler)) => {
+            let exception_env = Environment::spawn_from(env);
+            exception_env.set(exception_name.clone(), MalObject::from(&original));
+            run(handler.clone(), exception_env).map_err(|then| {
+                Error::InCatchHandler(ErrorDuringCatch {
+                    original: Box::new(original),
+                    then: Box::new(then),
+                })
+            })
+        }
+    }
+}
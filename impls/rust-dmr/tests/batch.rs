@@ -0,0 +1,195 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+// Run `script` as a file with the stepA binary, in `dir`.
+fn run_in(dir: &str, name: &str, script: &str) -> Output {
+    let path: PathBuf =
+        std::env::temp_dir().join(format!("mal-batch-{}-{}.mal", name, std::process::id()));
+    std::fs::write(&path, script).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_stepA_mal"))
+        .arg(&path)
+        .arg("an-arg")
+        .current_dir(dir)
+        .env("MAL_TEST_VAR", "from outside")
+        .output()
+        .unwrap();
+    std::fs::remove_file(path).unwrap();
+    output
+}
+
+fn run(name: &str, script: &str) -> Output {
+    run_in(".", name, script)
+}
+
+#[test]
+fn environment_and_working_directory() {
+    let output = run_in(
+        "/",
+        "env",
+        r#"
+        (println (getenv "MAL_TEST_VAR") (getenv "MAL_TEST_UNSET") (first *ARGV*))
+        (setenv "MAL_TEST_VAR" 42)
+        (println (get (getenv) "MAL_TEST_VAR"))
+        (setenv "MAL_TEST_VAR" nil)
+        (println (getenv "MAL_TEST_VAR") (cwd))
+        (chdir "tmp")
+        (println (cwd))
+        (println (try* (chdir "/no/such/dir") (catch* e (get e :kind))))
+        "#,
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout,
+        "from outside nil an-arg\n42\nnil /\n/tmp\n:not-found\n"
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn exit_status() {
+    let output = run(
+        "exit",
+        "(println \"bye\") (exit 7) (println \"unreachable\")",
+    );
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "bye\n");
+    assert_eq!(output.status.code(), Some(7));
+    assert_eq!(run("exit-zero", "(exit)").status.code(), Some(0));
+}
+
+#[test]
+fn failures_are_distinguished() {
+    let uncaught = run("uncaught", "(throw {:oops 1})");
+    assert_eq!(uncaught.status.code(), Some(1));
+    assert!(String::from_utf8(uncaught.stderr)
+        .unwrap()
+        .contains(":oops"));
+    assert_eq!(run("eval", "(undefined-thing)").status.code(), Some(2));
+    assert_eq!(run("read", "(+ 1").status.code(), Some(3));
+    assert_eq!(
+        run("caught", "(try* (throw 1) (catch* e e))").status.code(),
+        Some(0)
+    );
+}
+
+fn mal(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_stepA_mal"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: Output) -> String {
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn expressions() {
+    assert_eq!(
+        stdout(mal(
+            &["-e", "(+ 1 2)", "-e", "(println :hi)", "-e", "\"s\""],
+            ""
+        )),
+        "3\n:hi\n\"s\"\n"
+    );
+    assert_eq!(
+        stdout(mal(
+            &[
+                "-e",
+                "(take 3 (range))",
+                "-e",
+                "(concat (take 2 (range)) [9])",
+                "-e",
+                "[(map (fn* (x) (* x x)) (take 2 (range)))]",
+            ],
+            ""
+        )),
+        "(0 1 2)\n(0 1 9)\n[(0 1)]\n"
+    );
+    assert_eq!(
+        stdout(mal(
+            &["-e", "(def! x 2) (println :x) (* x 3)", "-e", ""],
+            ""
+        )),
+        ":x\n6\n"
+    );
+    let failed = mal(&["-e", "(+ 1"], "");
+    assert_eq!(failed.status.code(), Some(3));
+    assert_eq!(mal(&["-e"], "").status.code(), Some(64));
+}
+
+#[test]
+fn scripts_from_stdin_with_a_shebang() {
+    let output = mal(
+        &["-e", "(def! x 2)", "-", "a", "-e"],
+        "#!/usr/bin/env mal\n(println (* x 21) *ARGV*)\n",
+    );
+    assert_eq!(stdout(output), "2\n42 (a -e)\n");
+    let output = run("shebang", "#!/usr/bin/env stepA_mal\n(println (not false))");
+    assert_eq!(stdout(output), "true\n");
+}
+
+#[test]
+fn options() {
+    let help = mal(&["--help"], "");
+    assert_eq!(help.status.code(), Some(0));
+    assert!(stdout(help).starts_with("usage: mal"));
+
+    let bad = mal(&["--frobnicate"], "");
+    assert_eq!(bad.status.code(), Some(64));
+    assert!(String::from_utf8(bad.stderr)
+        .unwrap()
+        .contains("unknown option --frobnicate"));
+
+    let bare = mal(&["--no-prelude", "-e", "(not true)"], "");
+    assert_eq!(bare.status.code(), Some(2));
+    assert_eq!(
+        stdout(mal(&["--log-level", "off", "-e", "(+ 1 1)"], "")),
+        "2\n"
+    );
+    assert_eq!(mal(&["--log-level", "loud"], "").status.code(), Some(64));
+}
+
+#[test]
+fn random_seed_from_the_environment() {
+    let draw = |seed: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_stepA_mal"))
+            .args(["-e", "[(rand) (shuffle [1 2 3 4 5])]"])
+            .env("MAL_RANDOM_SEED", seed)
+            .output()
+            .unwrap();
+        (output.status.code(), stdout(output))
+    };
+    assert_eq!(draw("7"), draw("7"));
+    assert_ne!(draw("7"), draw("8"));
+    assert_eq!(draw("seven").0, Some(64));
+}
+
+#[test]
+fn load_path() {
+    let dir = std::env::temp_dir().join(format!("mal-batch-lib-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("lib.mal"), "(ns lib)\n(def! answer 42)").unwrap();
+    let dir = dir.to_str().unwrap();
+    let script = ["-e", "(do (require 'lib) lib/answer)"];
+    let flag = mal(&[&["-I", dir][..], &script[..]].concat(), "");
+    assert_eq!(stdout(flag), "42\n");
+    let variable = Command::new(env!("CARGO_BIN_EXE_stepA_mal"))
+        .args(script)
+        .env("MAL_PATH", format!("/no/such/dir:{}", dir))
+        .output()
+        .unwrap();
+    assert_eq!(stdout(variable), "42\n");
+    assert_eq!(mal(&script, "").status.code(), Some(2));
+    std::fs::remove_dir_all(dir).unwrap();
+}
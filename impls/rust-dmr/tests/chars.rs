@@ -0,0 +1,52 @@
+mod common;
+
+use common::eval;
+use rust_dmr_mal::convert::{FromMal, IntoMal};
+use rust_dmr_mal::interpreter::Interpreter;
+
+#[test]
+fn character_literals() {
+    let mal = Interpreter::new().unwrap();
+    assert_eq!(
+        eval(&mal, r"[\a \newline \u00e9 \space \( \u0001]"),
+        Ok(r"[\a \newline \é \space \( \u0001]".into())
+    );
+    assert_eq!(eval(&mal, r"(str \a \space \b)"), Ok(r#""a b""#.into()));
+    assert_eq!(eval(&mal, r"(= \a (char 97))"), Ok("true".into()));
+    assert_eq!(eval(&mal, r"(int \é)"), Ok("233".into()));
+    assert_eq!(eval(&mal, r"(char? \a)"), Ok("true".into()));
+    assert_eq!(eval(&mal, r#"(char? "a")"#), Ok("false".into()));
+    assert!(eval(&mal, r"\bogus")
+        .unwrap_err()
+        .contains("unknown character literal"));
+    assert!(eval(&mal, "(char -1)").is_err());
+    assert!(eval(&mal, "(char 55296)").is_err());
+
+    assert_eq!('x'.into_mal().to_string(), r"\x");
+    assert_eq!(
+        char::from_mal(&mal.eval_str(r"\tab").unwrap()).unwrap(),
+        '\t'
+    );
+}
+
+#[test]
+fn string_escapes() {
+    let mal = Interpreter::new().unwrap();
+    assert_eq!(
+        eval(&mal, r#"(count (seq "a\tb\rc\u{e9}\u{1F600}"))"#),
+        Ok("7".into())
+    );
+    assert_eq!(eval(&mal, r#"(= "\u{e9}" "é")"#), Ok("true".into()));
+    let printed = mal.eval_str(r#"(pr-str "tab\there\r\n\u{7}\u{e9}")"#);
+    assert_eq!(
+        String::from_mal(&printed.unwrap()).unwrap(),
+        r#""tab\there\r\n\u{7}é""#
+    );
+    assert_eq!(
+        eval(&mal, r#"(= "\u{0}\t" (read-string (pr-str "\u{0}\t")))"#),
+        Ok("true".into())
+    );
+    for bad in &[r#""\u{}""#, r#""\u{110000}""#, r#""\u{1234567}""#] {
+        assert!(eval(&mal, bad).unwrap_err().contains("string"), "{}", bad);
+    }
+}
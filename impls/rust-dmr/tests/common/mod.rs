@@ -0,0 +1,31 @@
+// Helpers shared by the integration tests. Not every test uses all of them.
+#![allow(dead_code)]
+
+use rust_dmr_mal::interpreter::{Engine, Interpreter};
+
+// The printed value of the last form in `src`, or the error it raised.
+pub fn eval(mal: &Interpreter, src: &str) -> Result<String, String> {
+    mal.eval_str(src)
+        .map(|obj| obj.to_string())
+        .map_err(|e| e.to_string())
+}
+
+// Evaluate each source with both engines, expecting the printed value given
+// alongside it. `inc` and `odd?` are defined for the cases to use.
+pub fn check(cases: &[(&str, &str)]) {
+    for &engine in &[Engine::TreeWalker, Engine::Bytecode] {
+        let mal = Interpreter::new_using(engine).unwrap();
+        mal.eval_str("(def! inc (fn* (x) (+ x 1)))").unwrap();
+        mal.eval_str("(def! odd? (fn* (x) (= 1 (- x (* 2 (/ x 2))))))")
+            .unwrap();
+        for (src, expected) in cases {
+            assert_eq!(
+                eval(&mal, src),
+                Ok(expected.to_string()),
+                "{} ({:?})",
+                src,
+                engine
+            );
+        }
+    }
+}
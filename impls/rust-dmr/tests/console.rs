@@ -0,0 +1,109 @@
+use rust_dmr_mal::console;
+use rust_dmr_mal::interpreter::Interpreter;
+use rust_dmr_mal::types::Arity;
+use std::cell::RefCell;
+use std::io::{Cursor, Write};
+use std::rc::Rc;
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuffer {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+#[test]
+fn output_and_input_are_redirectable() {
+    let mal = Interpreter::new().unwrap();
+    let out = SharedBuffer::default();
+    mal.set_output(out.clone());
+    mal.set_input(Cursor::new("first line\r\nsecond"));
+
+    mal.eval_str("(prn \"a\" 1) (println \"b\" [2])").unwrap();
+    let lines = mal
+        .eval_str("(list (readline \"1> \") (readline \"2> \") (readline \"3> \"))")
+        .unwrap();
+    assert_eq!(lines.to_string(), "(\"first line\" \"second\" nil)");
+    assert_eq!(out.contents(), "\"a\" 1\nb [2]\n1> 2> 3> ");
+}
+
+#[test]
+fn with_out_str_captures_nested_output() {
+    let mal = Interpreter::new().unwrap();
+    let out = SharedBuffer::default();
+    mal.set_output(out.clone());
+
+    let result = mal
+        .eval_str(
+            "(let* [inner (atom nil)
+                    outer (with-out-str
+                            (println \"outer\")
+                            (reset! inner (with-out-str (prn :inner)))
+                            (println \"again\"))]
+               [outer @inner])",
+        )
+        .unwrap();
+    assert_eq!(result.to_string(), r#"["outer\nagain\n" ":inner\n"]"#);
+    assert_eq!(mal.eval_str("(with-out-str)").unwrap().to_string(), "\"\"");
+
+    // Errors propagate, and output afterwards isn't captured.
+    assert!(mal
+        .eval_str("(with-out-str (println 1) (throw \"oops\"))")
+        .is_err());
+    mal.eval_str("(println \"visible\")").unwrap();
+    assert_eq!(out.contents(), "visible\n");
+}
+
+#[test]
+fn capture_from_rust() {
+    let mal = Interpreter::new().unwrap();
+    let (result, text) = console::capture(mal.environment(), || {
+        mal.eval_str("(do (println \"hi\") 7)")
+    });
+    assert_eq!(result.unwrap().to_string(), "7");
+    assert_eq!(text, "hi\n");
+}
+
+#[test]
+fn each_interpreter_has_its_own_console() {
+    let (first, second) = (Interpreter::new().unwrap(), Interpreter::new().unwrap());
+    let (first_out, second_out) = (SharedBuffer::default(), SharedBuffer::default());
+    first.set_output(first_out.clone());
+    second.set_output(second_out.clone());
+    second.set_input(Cursor::new("typed"));
+
+    first.eval_str("(println 1)").unwrap();
+    second.eval_str("(println 2)").unwrap();
+    // A Rust closure called from one interpreter can run code in another.
+    let other = Rc::new(second);
+    let inner = other.clone();
+    first.register("other", Arity::exactly(0), move |_| {
+        Ok(inner
+            .eval_str("(println :inner) (readline \"> \")")
+            .unwrap())
+    });
+    let read = first
+        .eval_str("(let* [a (do (println :before) (other))] (do (println :after) [a (other)]))");
+    assert_eq!(read.unwrap().to_string(), "[\"typed\" nil]");
+    assert_eq!(first_out.contents(), "1\n:before\n:after\n");
+    assert_eq!(second_out.contents(), "2\n:inner\n> :inner\n> ");
+    assert_eq!(
+        other
+            .eval_str("(with-out-str (println 3))")
+            .unwrap()
+            .to_string(),
+        "\"3\\n\""
+    );
+}
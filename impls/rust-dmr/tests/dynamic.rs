@@ -0,0 +1,97 @@
+mod common;
+
+use common::eval;
+use rust_dmr_mal::interpreter::{Engine, Interpreter};
+
+fn check(cases: &[(&str, Result<&str, &str>)]) {
+    for &engine in &[Engine::TreeWalker, Engine::Bytecode] {
+        let mal = Interpreter::new_using(engine).unwrap();
+        mal.eval_str("(def! ^:dynamic *x* 1)").unwrap();
+        mal.eval_str("(def! ^{:dynamic true} *y* 2)").unwrap();
+        mal.eval_str("(def! show (fn* () [*x* *y*]))").unwrap();
+        mal.eval_str("(def! plain 3)").unwrap();
+        for (src, expected) in cases {
+            let expected = expected.map(String::from).map_err(String::from);
+            assert_eq!(eval(&mal, src), expected, "{}", src);
+        }
+    }
+}
+
+#[test]
+fn binding_lasts_for_the_body() {
+    check(&[
+        ("(binding [*x* 10] (show))", Ok("[10 2]")),
+        ("(show)", Ok("[1 2]")),
+        ("(binding (*x* 10 *y* *x*) (show))", Ok("[10 1]")),
+        ("(binding [*x* 10] (binding [*x* 20] (show)))", Ok("[20 2]")),
+        ("(binding [] 5)", Ok("5")),
+        (
+            "(let* (f (fn* () (binding [*y* 0] (show)))) (f))",
+            Ok("[1 0]"),
+        ),
+        (
+            "(let* (*x* 5) [*x* (binding [*y* 0] (show))])",
+            Ok("[5 [1 0]]"),
+        ),
+    ]);
+}
+
+#[test]
+fn restored_after_exceptions() {
+    check(&[
+        (
+            "(try* (binding [*x* 10] (throw (show))) (catch* e [e (show)]))",
+            Ok("[[10 2] [1 2]]"),
+        ),
+        (
+            "(try* (binding [*x* 10 plain 4] 0) (catch* e (show)))",
+            Ok("[1 2]"),
+        ),
+        (
+            "(binding [*x* 10] (undefined))",
+            Err("'undefined' not found"),
+        ),
+        ("(show)", Ok("[1 2]")),
+    ]);
+}
+
+#[test]
+fn only_dynamic_variables_can_be_bound() {
+    check(&[
+        (
+            "(binding [plain 4] plain)",
+            Err("can't bind plain: it isn't dynamic"),
+        ),
+        ("(binding [missing 4] 0)", Err("'missing' not found")),
+        ("(binding [*x*] 0)", Err("binding: BindingsOddLength")),
+        ("(binding [1 2] 0)", Err("binding: BindToNonSymbol")),
+        ("(binding [*x* 1])", Err("binding: WrongArgCount(1)")),
+    ]);
+}
+
+#[test]
+fn dynamic_variables_in_other_namespaces() {
+    check(&[
+        ("(do (ns lib) nil)", Ok("nil")),
+        ("(def! ^:dynamic *level* :info)", Ok(":info")),
+        ("(def! level (fn* () *level*))", Ok("(fn* () *level*)")),
+        ("(do (in-ns 'user) nil)", Ok("nil")),
+        ("(binding [lib/*level* :debug] (lib/level))", Ok(":debug")),
+        ("(lib/level)", Ok(":info")),
+    ]);
+}
+
+#[test]
+fn print_length_is_dynamic() {
+    check(&[
+        (
+            "(binding [*print-length* 2] (pr-str [1 2 3]))",
+            Ok("\"[1 2 ...]\""),
+        ),
+        ("(pr-str [1 2 3])", Ok("\"[1 2 3]\"")),
+        (
+            "(binding [*print-length* 1] (pr-str (range)))",
+            Ok("\"(0 ...)\""),
+        ),
+    ]);
+}
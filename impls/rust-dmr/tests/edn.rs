@@ -0,0 +1,127 @@
+mod common;
+
+use common::eval;
+use rust_dmr_mal::edn;
+use rust_dmr_mal::interpreter::Interpreter;
+use rust_dmr_mal::types::MalObject;
+
+fn read(src: &str) -> Result<String, String> {
+    edn::read_str(src, &edn::Readers::default())
+        .map(|obj| obj.to_string())
+        .map_err(|e| e.to_string())
+}
+
+#[test]
+fn reads_data_without_evaluating() {
+    assert_eq!(read("(+ 1 2)"), Ok("(+ 1 2)".into()));
+    assert_eq!(
+        read("[a :b/c 42N -7 nil] ignored"),
+        Ok("[a :b/c 42 -7 nil]".into())
+    );
+    assert_eq!(read("; just a comment"), Ok("nil".into()));
+    assert_eq!(read(""), Ok("nil".into()));
+    assert_eq!(
+        read(r"[\a \newline \u00e9 \( \space]"),
+        Ok(r"[\a \newline \é \( \space]".into())
+    );
+    for src in &[
+        "'x", "@x", "^:m x", "`x", "~x", "1.5", "##Inf", r"\bogus", "{[1] 2}",
+    ] {
+        assert!(read(src).is_err(), "{}", src);
+    }
+}
+
+#[test]
+fn discard() {
+    assert_eq!(read("[1 #_2 3 #_ [4 5] #_#_ 6 7 8]"), Ok("[1 3 8]".into()));
+    assert_eq!(read("#_x y"), Ok("y".into()));
+    assert_eq!(read("{:a 1 #_:b #_2}"), Ok("{:a 1}".into()));
+}
+
+#[test]
+fn sets_and_namespaced_maps() {
+    assert_eq!(read("#{1 :two \"three\"}"), Ok("[1 :two \"three\"]".into()));
+    assert!(read("#{1 1}").unwrap_err().contains("duplicate"));
+    let mal = Interpreter::new().unwrap();
+    assert_eq!(
+        eval(&mal, r##"(write-edn (read-edn "#{1 #{2}}"))"##),
+        Ok(r##""#{1 #{2}}""##.into())
+    );
+    assert_eq!(
+        eval(
+            &mal,
+            r##"(= (read-edn "#:person{:name \"x\" :place/city \"y\"}")
+                   {:person/name "x" :place/city "y"})"##
+        ),
+        Ok("true".into())
+    );
+    assert_eq!(
+        eval(
+            &mal,
+            r##"(get (read-edn "#:person{:name 1}") :person/name)"##
+        ),
+        Ok("1".into())
+    );
+}
+
+#[test]
+fn tagged_literals() {
+    assert_eq!(
+        read(r##"#inst "2020-07-01T12:30:00.5Z""##),
+        Ok(r##"#inst "2020-07-01T12:30:00.500Z""##.into())
+    );
+    assert_eq!(
+        read(r##"[#inst "2020" #inst "2020-07-01T12:30+01:00"]"##),
+        Ok(r##"[#inst "2020-01-01T00:00:00Z" #inst "2020-07-01T12:30:00+01:00"]"##.into())
+    );
+    assert_eq!(
+        read(r##"#uuid "f81d4fae-7dec-11d0-a765-00a0c91e6bf6""##),
+        Ok(r##""f81d4fae-7dec-11d0-a765-00a0c91e6bf6""##.into())
+    );
+    assert!(read(r##"#inst "yesterday""##)
+        .unwrap_err()
+        .contains("invalid #inst"));
+    assert!(read(r##"#point [1 2]"##).unwrap_err().contains("#point"));
+
+    let mal = Interpreter::new().unwrap();
+    assert_eq!(
+        eval(
+            &mal,
+            r##"(= (read-edn "[#point [1 2] #other 3]"
+                    {:readers {"point" (fn* (v) {:x (first v) :y (nth v 1)})}
+                     :default (fn* (tag v) [tag v])})
+                   [{:x 1 :y 2} ['other 3]])"##
+        ),
+        Ok("true".into())
+    );
+    assert_eq!(
+        eval(
+            &mal,
+            r##"(try* (read-edn "#boom 1" {:readers {:boom (fn* (v) (throw v))}})
+                     (catch* e e))"##
+        ),
+        Ok("1".into())
+    );
+
+    edn::register_tag("double", |obj| Ok(MalObject::Integer(2 * obj.as_int()?)));
+    assert_eq!(read("#double 21"), Ok("42".into()));
+}
+
+#[test]
+fn write() {
+    let mal = Interpreter::new().unwrap();
+    assert_eq!(
+        eval(&mal, r##"(write-edn '(a "b\"c" [:d nil] {"e" 1}))"##),
+        Ok(r##""(a \"b\\\"c\" [:d nil] {\"e\" 1})""##.into())
+    );
+    assert!(eval(&mal, "(write-edn [+])")
+        .unwrap_err()
+        .contains("can't write +"));
+    assert_eq!(
+        eval(
+            &mal,
+            r##"(= {:a [1 "x"]} (read-edn (write-edn {:a [1 "x"]})))"##
+        ),
+        Ok("true".into())
+    );
+}
@@ -0,0 +1,129 @@
+use rust_dmr_mal::interpreter::{Engine, Interpreter};
+use rust_dmr_mal::namespaces;
+
+// Run each program with both engines, one form at a time, and check that they
+// agree on every value and error.
+fn agree(programs: &[&[&str]]) {
+    for program in programs {
+        let results: Vec<Vec<Result<String, String>>> = [Engine::TreeWalker, Engine::Bytecode]
+            .iter()
+            .map(|&engine| {
+                let mal = Interpreter::new_using(engine).unwrap();
+                program
+                    .iter()
+                    .map(|src| {
+                        mal.eval_str(src)
+                            .map(|obj| obj.to_string())
+                            .map_err(|e| e.to_string())
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(results[0], results[1], "{:?}", program);
+    }
+}
+
+#[test]
+fn macros_defined_after_use() {
+    agree(&[
+        &[
+            "(def! f (fn* () (later 1)))",
+            "(defmacro! later (fn* (x) (list '+ x 100)))",
+            "(f)",
+        ],
+        &["(do (defmacro! m2 (fn* (x) (list 'str x \"!\"))) (m2 5))"],
+        &[
+            "(def! g (fn* (n) (if (= n 0) :done (again n))))",
+            "(defmacro! again (fn* (n) (list 'g (list '- n 1))))",
+            "(g 100)",
+        ],
+        &[
+            "(def! h (fn* (x) [(twice x) :after]))",
+            "(defmacro! twice (fn* (x) (list '* 2 x)))",
+            "(h 4)",
+            "(defmacro! twice (fn* (x) (list '* 3 x)))",
+            "(h 4)",
+        ],
+        &["(def! k (fn* () (not-yet 1)))", "(k)"],
+        &["(let* (cond (fn* (& xs) :fn)) (cond false 1 true 2))"],
+        &[
+            "(defmacro! m (fn* () 1))",
+            "(def! h (fn* () (m)))",
+            "(defmacro! m (fn* () 2))",
+            "(h)",
+        ],
+        &[
+            "(def! g (fn* (x) :fn))",
+            "(def! h (fn* () (g (/ 1 0))))",
+            "(defmacro! g (fn* (x) :macro))",
+            "(h)",
+        ],
+    ]);
+    let mal = Interpreter::new_using(Engine::Bytecode).unwrap();
+    mal.eval_str("(def! f (fn* () (later 1)))").unwrap();
+    mal.eval_str("(defmacro! later (fn* (x) (list '+ x 100)))")
+        .unwrap();
+    assert_eq!(mal.eval_str("(f)").unwrap().to_string(), "101");
+}
+
+#[test]
+fn special_forms_and_calls() {
+    agree(&[
+        &[
+            "(def! fact (fn* (n) (if (= n 0) 1 (* n (fact (- n 1))))))",
+            "(fact 10)",
+        ],
+        &["((fn* (f) (f 2)) (fn* (x) (* x 3)))"],
+        &[
+            "(let* (a 1 b (+ a 1)) [a b])",
+            "(let* (a) a)",
+            "(let* [x 2] x)",
+        ],
+        &[
+            "(do)",
+            "(if)",
+            "(if false 1)",
+            "(fn* (& xs) xs)",
+            "((fn* (& xs) xs) 1 2)",
+        ],
+        &[
+            "(try* (throw {:a 1}) (catch* e (get e :a)))",
+            "(try* (nth [] 1))",
+        ],
+        &["`(1 ~@(list 2 3) ~(+ 2 2))", "(macroexpand (cond true 1))"],
+        &[
+            "(def! ^:dynamic *d* 1)",
+            "(binding [*d* 2] *d*)",
+            "(binding [*d*] 0)",
+        ],
+        &[
+            "(take 3 (lazy-seq (cons 1 nil)))",
+            "(undefined-function 1 2)",
+        ],
+        &["{:a (+ 1 2)}", "[1 (+ 1 1) [3]]", "(eval '(+ 1 2))"],
+    ]);
+}
+
+#[test]
+fn the_chosen_engine_evaluates_everything() {
+    let compiled = |mal: &Interpreter, src: &str| {
+        let obj = mal.eval_str(src).unwrap();
+        obj.as_closure().unwrap().compiled.is_some()
+    };
+    let dir = std::env::temp_dir().join(format!("mal-engines-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("lib.mal"), "(def! f (fn* () 1))").unwrap();
+    for &engine in &[Engine::TreeWalker, Engine::Bytecode] {
+        let bytecode = engine == Engine::Bytecode;
+        let mal = Interpreter::new_using(engine).unwrap();
+        namespaces::add_to_load_path(mal.environment(), &dir);
+        assert_eq!(compiled(&mal, "not"), bytecode);
+        assert_eq!(compiled(&mal, "(eval '(fn* () 1))"), bytecode);
+        mal.eval_str("(require 'lib)").unwrap();
+        assert_eq!(compiled(&mal, "lib/f"), bytecode);
+        // Switching engines afterwards leaves the prelude alone.
+        let switched = Interpreter::new().unwrap().using(engine);
+        assert!(!compiled(&switched, "not"));
+        assert_eq!(compiled(&switched, "(eval '(fn* () 1))"), bytecode);
+    }
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use common::eval;
+use rust_dmr_mal::environment::{Capability, Environment};
+use rust_dmr_mal::interpreter::Interpreter;
+use std::path::PathBuf;
+
+// A fresh directory for each test, bound to `dir` in the interpreter.
+fn scratch(name: &str) -> (Interpreter, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("mal-fs-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let mal = Interpreter::new().unwrap();
+    mal.set_global("dir", dir.to_str().unwrap());
+    (mal, dir)
+}
+
+#[test]
+fn files() {
+    let (mal, dir) = scratch("files");
+    mal.eval_str(r#"(def! f (path-join dir "notes.txt"))"#)
+        .unwrap();
+    assert_eq!(eval(&mal, "(file-exists? f)"), Ok("false".into()));
+    assert_eq!(eval(&mal, r#"(spit f "one\n")"#), Ok("nil".into()));
+    assert_eq!(eval(&mal, r#"(spit f 2 {:append true})"#), Ok("nil".into()));
+    assert_eq!(eval(&mal, "(slurp f)"), Ok(r#""one\n2""#.into()));
+    assert_eq!(eval(&mal, "(read-lines f)"), Ok(r#"("one" "2")"#.into()));
+    assert_eq!(eval(&mal, "(file-size f)"), Ok("5".into()));
+    assert_eq!(eval(&mal, "(dir? f)"), Ok("false".into()));
+
+    mal.eval_str(r#"(rename-file f (path-join dir "renamed.txt"))"#)
+        .unwrap();
+    assert_eq!(eval(&mal, "(file-exists? f)"), Ok("false".into()));
+    assert_eq!(
+        std::fs::read_to_string(dir.join("renamed.txt")).unwrap(),
+        "one\n2"
+    );
+    mal.eval_str(r#"(delete-file (path-join dir "renamed.txt"))"#)
+        .unwrap();
+    assert_eq!(eval(&mal, "(list-dir dir)"), Ok("[]".into()));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn lines_are_read_lazily() {
+    let (mal, dir) = scratch("lines");
+    mal.eval_str(r#"(def! f (path-join dir "lines.txt"))"#)
+        .unwrap();
+    std::fs::write(dir.join("lines.txt"), "one\r\ntwo\n").unwrap();
+    mal.eval_str("(def! lines (read-lines f))").unwrap();
+    assert_eq!(eval(&mal, "(first lines)"), Ok(r#""one""#.into()));
+    assert_eq!(eval(&mal, "(realized? (rest lines))"), Ok("false".into()));
+    // The rest of the file hasn't been read yet, so sees what's added to it.
+    std::fs::write(dir.join("lines.txt"), "one\r\ntwo\nthree").unwrap();
+    assert_eq!(eval(&mal, "(nth lines 2)"), Ok(r#""three""#.into()));
+    assert_eq!(eval(&mal, "(count lines)"), Ok("3".into()));
+    assert!(eval(&mal, r#"(read-lines (path-join dir "missing"))"#).is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn directories() {
+    let (mal, dir) = scratch("directories");
+    mal.eval_str(r#"(mkdir (path-join dir "a" "b"))"#).unwrap();
+    mal.eval_str(r#"(spit (path-join dir "c.txt") "")"#)
+        .unwrap();
+    assert_eq!(eval(&mal, "(list-dir dir)"), Ok(r#"["a" "c.txt"]"#.into()));
+    assert_eq!(
+        eval(&mal, r#"(dir? (path-join dir "a"))"#),
+        Ok("true".into())
+    );
+    assert!(mal
+        .eval_str(r#"(delete-file (path-join dir "a"))"#)
+        .is_err());
+    mal.eval_str(r#"(delete-file (path-join dir "a" "b"))"#)
+        .unwrap();
+    assert_eq!(
+        eval(&mal, r#"(list-dir (path-join dir "a"))"#),
+        Ok("[]".into())
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn errors_are_structured() {
+    let (mal, dir) = scratch("errors");
+    let missing = r#"(path-join dir "missing.txt")"#;
+    let message = eval(&mal, &format!("(slurp {})", missing)).unwrap_err();
+    assert!(message.starts_with("io error: can't read "), "{}", message);
+    assert!(message.contains("missing.txt"), "{}", message);
+    assert_eq!(
+        eval(
+            &mal,
+            &format!("(try* (file-size {}) (catch* e (get e :kind)))", missing)
+        ),
+        Ok(":not-found".into())
+    );
+    assert_eq!(
+        eval(
+            &mal,
+            &format!(
+                "(try* (list-dir {}) (catch* e (= (get e :path) {})))",
+                missing, missing
+            )
+        ),
+        Ok("true".into())
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn paths() {
+    let mal = Interpreter::new().unwrap();
+    assert_eq!(
+        eval(&mal, r#"(path-join "a" "b" "c.txt")"#),
+        Ok(r#""a/b/c.txt""#.into())
+    );
+    assert_eq!(eval(&mal, r#"(path-join "a" "/b")"#), Ok(r#""/b""#.into()));
+    assert_eq!(
+        eval(&mal, r#"(basename "/x/y/z.tar.gz")"#),
+        Ok(r#""z.tar.gz""#.into())
+    );
+    assert_eq!(eval(&mal, r#"(basename "/")"#), Ok("nil".into()));
+    assert_eq!(eval(&mal, r#"(dirname "/x/y/z")"#), Ok(r#""/x/y""#.into()));
+    assert_eq!(eval(&mal, r#"(dirname "/")"#), Ok("nil".into()));
+    assert_eq!(
+        eval(&mal, r#"(abs-path "/x/./y/../z")"#),
+        Ok(r#""/x/z""#.into())
+    );
+    let cwd = std::env::current_dir().unwrap();
+    assert_eq!(
+        eval(&mal, r#"(abs-path "src/../Cargo.toml")"#),
+        Ok(format!("{:?}", cwd.join("Cargo.toml").to_str().unwrap()))
+    );
+}
+
+#[test]
+fn writing_needs_io_write() {
+    let env = Environment::builder()
+        .with(Capability::Pure)
+        .with(Capability::IoRead)
+        .build()
+        .unwrap();
+    let mal = Interpreter::with_environment(env);
+    assert_eq!(
+        eval(&mal, r#"(file-exists? "Cargo.toml")"#),
+        Ok("true".into())
+    );
+    assert_eq!(eval(&mal, r#"(basename "a/b")"#), Ok(r#""b""#.into()));
+    for line in &[r#"(spit "x" 1)"#, r#"(mkdir "x")"#, r#"(delete-file "x")"#] {
+        assert!(
+            eval(&mal, line).unwrap_err().contains("not found"),
+            "{}",
+            line
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use rust_dmr_mal::{environment, gc, interpreter};
+use std::rc::{Rc, Weak};
+
+fn interpreter_with_recursive_function() -> Weak<environment::Environment> {
+    let env = Rc::new(environment::Environment::default());
+    environment::read_prelude(&env).expect("error reading prelude");
+    environment::add_eval(&env);
+    interpreter::rep("(def! f (fn* (n) (if (= n 0) 0 (f (- n 1)))))", &env).unwrap();
+    interpreter::rep("(def! a (atom nil))", &env).unwrap();
+    interpreter::rep("(reset! a (fn* () a))", &env).unwrap();
+    interpreter::rep("(f 10)", &env).unwrap();
+    Rc::downgrade(&env)
+}
+
+#[test]
+fn dropped_interpreters_are_collected() {
+    let environments: Vec<_> = (0..2000)
+        .map(|_| interpreter_with_recursive_function())
+        .collect();
+    let stats = gc::collect();
+    assert!(environments.iter().all(|env| env.upgrade().is_none()));
+    assert_eq!(stats.tracked, 0);
+}
+
+#[test]
+fn live_interpreters_are_not_collected() {
+    let env = Rc::new(environment::Environment::default());
+    environment::read_prelude(&env).expect("error reading prelude");
+    interpreter::rep("(def! f (fn* (n) (if (= n 0) 0 (f (- n 1)))))", &env).unwrap();
+    interpreter::rep("(def! g (let* (x 1) (fn* () x)))", &env).unwrap();
+    gc::collect();
+    let outcome = interpreter::rep("(+ (f 10) (g))", &env).unwrap();
+    match outcome {
+        rust_dmr_mal::printer::Outcome::String(s) => assert_eq!(s, "1"),
+        rust_dmr_mal::printer::Outcome::Empty => panic!("expected a value"),
+    }
+}
+
+#[test]
+fn memory_does_not_grow() {
+    for _ in 0..5 {
+        (0..400).for_each(|_| {
+            interpreter_with_recursive_function();
+        });
+        gc::collect();
+    }
+    assert_eq!(gc::stats().tracked, 0);
+}
@@ -0,0 +1,123 @@
+use rust_dmr_mal::convert::{FromMal, IntoMal, Keyword};
+use rust_dmr_mal::evaluator;
+use rust_dmr_mal::interpreter::{Engine, Error, Interpreter};
+use rust_dmr_mal::types::{Arity, MalObject, TypeMismatch};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[test]
+fn eval_str_returns_last_value() {
+    for &engine in &[Engine::TreeWalker, Engine::Bytecode] {
+        let mal = Interpreter::new_using(engine).unwrap();
+        let result = mal
+            .eval_str("(def! inc (fn* (x) (+ x 1))) ; comment\n(inc 41) ; trailing")
+            .unwrap();
+        assert_eq!(i64::from_mal(&result).unwrap(), 42);
+        assert!(matches!(mal.eval_str("; nothing"), Ok(MalObject::Nil)));
+        assert!(matches!(mal.eval_str("(+ 1"), Err(Error::Read(_))));
+    }
+}
+
+#[test]
+fn globals_round_trip() {
+    let mal = Interpreter::new().unwrap();
+    let mut config = HashMap::new();
+    config.insert(Keyword("port".into()), 8080u16);
+    mal.set_global("config", config.clone());
+    mal.set_global("pair", (true, "x"));
+    mal.set_global("missing", None::<i32>);
+
+    assert_eq!(
+        mal.eval_str("(get config :port)").unwrap().to_string(),
+        "8080"
+    );
+    assert_eq!(
+        mal.get_global::<HashMap<Keyword, u16>>("config").unwrap(),
+        config
+    );
+    assert_eq!(
+        mal.get_global::<(bool, String)>("pair").unwrap(),
+        (true, "x".to_string())
+    );
+    assert_eq!(mal.get_global::<Option<i32>>("missing").unwrap(), None);
+}
+
+#[test]
+fn out_of_range_integers_saturate() {
+    assert_eq!(u64::MAX.into_mal().to_string(), isize::MAX.to_string());
+    assert_eq!(usize::MAX.into_mal().to_string(), isize::MAX.to_string());
+    assert_eq!((-5i64).into_mal().to_string(), "-5");
+    assert_eq!(200u8.into_mal().to_string(), "200");
+}
+
+#[test]
+fn conversion_errors() {
+    let mal = Interpreter::new().unwrap();
+    mal.eval_str("(def! big 300) (def! xs '(1 2 3))").unwrap();
+    assert!(matches!(
+        mal.get_global::<u8>("big"),
+        Err(Error::Eval(evaluator::Error::TypeMismatch(
+            TypeMismatch::OutOfRange
+        )))
+    ));
+    assert!(matches!(
+        mal.get_global::<(i64, i64)>("xs"),
+        Err(Error::Eval(evaluator::Error::TypeMismatch(
+            TypeMismatch::WrongLength {
+                expected: 2,
+                got: 3
+            }
+        )))
+    ));
+    assert!(matches!(
+        mal.get_global::<String>("xs"),
+        Err(Error::Eval(evaluator::Error::TypeMismatch(_)))
+    ));
+    assert!(matches!(
+        mal.get_global::<i64>("nope"),
+        Err(Error::Eval(evaluator::Error::UnknownSymbol(_)))
+    ));
+}
+
+#[test]
+fn call_functions_by_name() {
+    let mal = Interpreter::new().unwrap();
+    mal.eval_str("(def! greet (fn* (name) (str \"hello \" name)))")
+        .unwrap();
+    let greeting = mal.call("greet", &["world".into_mal()]).unwrap();
+    assert_eq!(String::from_mal(&greeting).unwrap(), "hello world");
+    let sum = mal.call("+", &[1.into_mal(), 2.into_mal()]).unwrap();
+    assert_eq!(i32::from_mal(&sum).unwrap(), 3);
+    assert!(mal.call("greet", &[]).is_err());
+}
+
+#[test]
+fn register_stateful_primitives() {
+    let mal = Interpreter::new().unwrap();
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let sink = log.clone();
+    mal.register("record!", Arity::at_least(1), move |args| {
+        sink.borrow_mut()
+            .extend(args.iter().map(|arg| arg.to_string()));
+        Ok(args.len().into_mal())
+    });
+    for &engine in &[Engine::TreeWalker, Engine::Bytecode] {
+        let mal = Interpreter::with_environment(mal.environment().clone()).using(engine);
+        mal.eval_str("(map record! [1 2])").unwrap();
+        assert_eq!(
+            mal.eval_str("(apply record! '(:a))").unwrap().to_string(),
+            "1"
+        );
+    }
+    assert_eq!(*log.borrow(), vec!["1", "2", ":a", "1", "2", ":a"]);
+    assert_eq!(
+        mal.eval_str("(str record!)").unwrap().to_string(),
+        "\"record!\""
+    );
+    assert!(mal
+        .eval_str("(record!)")
+        .unwrap_err()
+        .to_string()
+        .contains("record!"));
+}
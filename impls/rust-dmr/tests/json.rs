@@ -0,0 +1,68 @@
+mod common;
+
+use common::eval;
+use rust_dmr_mal::interpreter::Interpreter;
+
+#[test]
+fn parse() {
+    let mal = Interpreter::new().unwrap();
+    mal.eval_str(
+        r#"(def! doc "{\"name\": \"mal\", \"tags\": [1, 2e2, -3.0, true, null], \"nested\": {}}")"#,
+    )
+    .unwrap();
+    assert_eq!(
+        eval(&mal, "(get (json-parse doc) \"tags\")"),
+        Ok("[1 200 -3 true nil]".into())
+    );
+    assert_eq!(
+        eval(&mal, "(get (json-parse doc {:keywordize-keys true}) :name)"),
+        Ok("\"mal\"".into())
+    );
+    assert_eq!(
+        eval(&mal, "(map? (get (json-parse doc) \"nested\"))"),
+        Ok("true".into())
+    );
+    assert_eq!(
+        eval(&mal, "(json-parse \"\\\"\\\\u00e9\\\"\")"),
+        Ok("\"é\"".into())
+    );
+}
+
+#[test]
+fn parse_errors() {
+    let mal = Interpreter::new().unwrap();
+    let err = eval(&mal, "(json-parse \"{\\\"a\\\": 1,\\n  \\\"b\\\" 2}\")").unwrap_err();
+    assert!(err.contains("line 2 column 7"), "{}", err);
+    let err = eval(&mal, "(json-parse \"[1.5]\")").unwrap_err();
+    assert!(err.contains("1.5 is not an integer"), "{}", err);
+    assert_eq!(
+        eval(&mal, "(try* (json-parse \"[\") (catch* e (string? e)))"),
+        Ok("true".into())
+    );
+}
+
+#[test]
+fn stringify() {
+    let mal = Interpreter::new().unwrap();
+    assert_eq!(
+        eval(
+            &mal,
+            r#"(json-stringify {:b '(1 nil) "a" [:x (quote sym) "q\"uote"]})"#
+        ),
+        Ok(r#""{\"a\":[\"x\",\"sym\",\"q\\\"uote\"],\"b\":[1,null]}""#.into())
+    );
+    assert_eq!(
+        eval(&mal, "(json-stringify {:a [1]} {:pretty true})"),
+        Ok(r#""{\n  \"a\": [\n    1\n  ]\n}""#.into())
+    );
+    assert!(eval(&mal, "(json-stringify [+])")
+        .unwrap_err()
+        .contains("can't encode"));
+    assert_eq!(
+        eval(
+            &mal,
+            "(= {\"k\" [1 2]} (json-parse (json-stringify {:k '(1 2)})))"
+        ),
+        Ok("true".into())
+    );
+}
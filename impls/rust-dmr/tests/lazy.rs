@@ -0,0 +1,163 @@
+mod common;
+
+use common::{check, eval};
+use rust_dmr_mal::convert::FromMal;
+use rust_dmr_mal::interpreter::{Engine, Interpreter};
+
+#[test]
+fn infinite_sequences() {
+    check(&[
+        ("(take 5 (range))", "(0 1 2 3 4)"),
+        ("(take 4 (iterate inc 10))", "(10 11 12 13)"),
+        ("(take 5 (cycle [1 2]))", "(1 2 1 2 1)"),
+        ("(cycle [])", "()"),
+        ("(take 3 (repeat :x))", "(:x :x :x)"),
+        ("(repeat 2 :x)", "(:x :x)"),
+        ("(take 3 (filter odd? (map inc (range))))", "(1 3 5)"),
+        ("(take 2 (remove odd? (drop 5 (range))))", "(6 8)"),
+        ("(take-while (fn* (x) (< x 3)) (range))", "(0 1 2)"),
+        ("(first (drop-while (fn* (x) (< x 3)) (range)))", "3"),
+        ("(take 4 (mapcat (fn* (x) [x x]) (range)))", "(0 0 1 1)"),
+        ("(take 4 (map + (range) [10 20 30]))", "(10 21 32)"),
+        ("(take 4 (concat [:a] (range)))", "(:a 0 1 2)"),
+        ("(nth (range) 5)", "5"),
+        ("(some (fn* (x) (if (> x 10) x)) (range))", "11"),
+        ("(every? odd? (range))", "false"),
+        ("(reduce + (take 5 (range)))", "10"),
+    ]);
+}
+
+#[test]
+fn lazy_seq_special_form() {
+    check(&[
+        (
+            "(do (def! from (fn* (n) (lazy-seq (cons n (from (inc n)))))) (take 3 (from 7)))",
+            "(7 8 9)",
+        ),
+        ("(lazy-seq)", "()"),
+        ("(lazy-seq [1 2])", "(1 2)"),
+        ("(seq (lazy-seq nil))", "nil"),
+        ("(seq (lazy-seq [1]))", "(1)"),
+        ("(empty? (lazy-seq ()))", "true"),
+        ("(count (lazy-seq [1 2 3]))", "3"),
+        ("(first (lazy-seq nil))", "nil"),
+        ("(rest (lazy-seq nil))", "()"),
+        ("(rest (lazy-seq [1 2]))", "(2)"),
+        ("(sequential? (lazy-seq nil))", "true"),
+        ("(= (lazy-seq [1 2]) [1 2])", "true"),
+        ("(= (take 2 (range)) '(0 1))", "true"),
+        ("(apply + (take 4 (range)))", "6"),
+        ("(cons 1 (lazy-seq [2]))", "(1 2)"),
+        ("(conj (lazy-seq [2]) 1 0)", "(0 1 2)"),
+    ]);
+}
+
+#[test]
+fn realised_only_once_and_only_when_needed() {
+    check(&[
+        (
+            "(let* (n (atom 0) xs (lazy-seq (swap! n inc) [1])) [(realized? xs) (first xs) (first xs) @n])",
+            "[false 1 1 1]",
+        ),
+        (
+            "(let* (n (atom 0) xs (map (fn* (x) (swap! n inc)) (range))) (do (nth xs 3) @n))",
+            "4",
+        ),
+    ]);
+}
+
+#[test]
+fn errors_leave_the_sequence_unrealised() {
+    for &engine in &[Engine::TreeWalker, Engine::Bytecode] {
+        let mal = Interpreter::new_using(engine).unwrap();
+        mal.eval_str("(def! n (atom 0))").unwrap();
+        mal.eval_str(
+            "(def! xs (lazy-seq (if (= 0 (swap! n (fn* (x) (+ x 1)))) nil (throw \"no\")) [1]))",
+        )
+        .unwrap();
+        assert!(eval(&mal, "(first xs)").is_err());
+        assert_eq!(eval(&mal, "(realized? xs)"), Ok("false".into()));
+        mal.eval_str("(def! ys (lazy-seq (first ys)))").unwrap();
+        assert_eq!(
+            eval(&mal, "(first ys)"),
+            Err("lazy sequence depends on its own value".into())
+        );
+    }
+}
+
+#[test]
+fn long_chains_do_not_overflow_the_stack() {
+    check(&[
+        ("(nth (iterate (fn* (x) (+ x 1)) 0) 100000)", "100000"),
+        ("(count (take 100000 (map inc (range))))", "100000"),
+        (
+            "(do (def! from (fn* (n) (lazy-seq (cons n (from (+ n 1)))))) (first (drop 100000 (from 0))))",
+            "100000",
+        ),
+        (
+            "(do (def! nest (fn* (n) (if (= n 0) [:end] (lazy-seq (nest (- n 1)))))) (first (nest 100000)))",
+            ":end",
+        ),
+        ("(last (doall (take 200000 (range))))", "199999"),
+    ]);
+}
+
+#[test]
+fn print_length_limits_printing() {
+    check(&[
+        ("(do (def! *print-length* 3) (range))", "(0 1 2 ...)"),
+        ("[1 2 3 4]", "[1 2 3 ...]"),
+        ("{:a 1}", "{:a 1}"),
+        ("(list (repeat :x))", "((:x :x :x ...))"),
+        ("(str (range))", "\"(0 1 2 ...)\""),
+        ("(do (def! *print-length* nil) [1 2 3 4])", "[1 2 3 4]"),
+    ]);
+}
+
+#[test]
+fn print_length_belongs_to_its_interpreter() {
+    let (short, long) = (Interpreter::new().unwrap(), Interpreter::new().unwrap());
+    short.eval_str("(def! *print-length* 2)").unwrap();
+    long.eval_str("(def! *print-length* 4)").unwrap();
+    assert_eq!(eval(&short, "[1 2 3 4 5]"), Ok("[1 2 ...]".into()));
+    assert_eq!(eval(&long, "[1 2 3 4 5]"), Ok("[1 2 3 4 ...]".into()));
+    // A namespace sees the one in user.
+    short.eval_str("(in-ns 'elsewhere)").unwrap();
+    assert_eq!(eval(&short, "[1 2 3]"), Ok("[1 2 ...]".into()));
+}
+
+#[test]
+fn displaying_realises_as_much_as_is_shown() {
+    let mal = Interpreter::new().unwrap();
+    let xs = mal
+        .eval_str("(map (fn* (x) (* x x)) (lazy-seq [1 2 3]))")
+        .unwrap();
+    assert_eq!(xs.to_string(), "(1 4 9)");
+    let nested = mal.eval_str("[(take 2 (range))]").unwrap();
+    assert_eq!(nested.to_string(), "[(0 1)]");
+    mal.eval_str("(def! *print-length* 2)").unwrap();
+    assert_eq!(mal.eval_str("(range)").unwrap().to_string(), "(0 1 ...)");
+    let failing = mal
+        .eval_str("(map (fn* (x) (throw x)) (lazy-seq [1]))")
+        .unwrap();
+    assert_eq!(failing.to_string(), "(...)");
+    mal.eval_str("(def! *print-length* nil)").unwrap();
+}
+
+#[test]
+fn accepted_wherever_sequences_are() {
+    check(&[
+        ("(join \",\" (lazy-seq [1 2 3]))", "\"1,2,3\""),
+        ("(rand-nth (lazy-seq [7]))", "7"),
+        ("(count (shuffle (take 3 (range))))", "3"),
+        ("(sort [(lazy-seq [1 3]) [1 2]])", "([1 2] (1 3))"),
+        ("(into {} [(lazy-seq [:a 1])])", "{:a 1}"),
+    ]);
+    let mal = Interpreter::new().unwrap();
+    let squares = mal
+        .eval_str("(map (fn* (x) (* x x)) (lazy-seq [1 2 3]))")
+        .unwrap();
+    assert_eq!(Vec::<i64>::from_mal(&squares).unwrap(), vec![1, 4, 9]);
+    let pair = mal.eval_str("(take 2 (range))").unwrap();
+    assert_eq!(<(i64, i64)>::from_mal(&pair).unwrap(), (0, 1));
+}
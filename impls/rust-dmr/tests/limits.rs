@@ -0,0 +1,186 @@
+use rust_dmr_mal::interpreter::{Engine, Interpreter};
+use rust_dmr_mal::limits::{self, Limits};
+use rust_dmr_mal::printer::Outcome;
+use rust_dmr_mal::{environment, interpreter};
+use std::rc::Rc;
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// An interrupt goes to whichever thread notices it first, so tests which
+// evaluate mustn't run alongside the one which interrupts.
+fn serial() -> MutexGuard<'static, ()> {
+    static SERIAL: Mutex<()> = Mutex::new(());
+    SERIAL
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn setup(limits: Limits) -> Rc<environment::Environment> {
+    let env = Rc::new(environment::Environment::default());
+    environment::read_prelude(&env).expect("error reading prelude");
+    limits::set_limits(limits);
+    interpreter::rep("(def! loop (fn* (n) (loop (+ n 1))))", &env).unwrap();
+    interpreter::rep("(def! deep (fn* (n) (+ 1 (deep (+ n 1)))))", &env).unwrap();
+    env
+}
+
+fn rep(line: &str, env: &Rc<environment::Environment>) -> Result<String, String> {
+    rep_using(line, env, Engine::TreeWalker)
+}
+
+fn rep_using(
+    line: &str,
+    env: &Rc<environment::Environment>,
+    engine: Engine,
+) -> Result<String, String> {
+    interpreter::rep_using(line, env, engine).map(|outcome| match outcome {
+        Outcome::String(s) => s,
+        Outcome::Empty => String::new(),
+    })
+}
+
+#[test]
+fn step_limit() {
+    let _serial = serial();
+    let env = setup(Limits {
+        max_steps: Some(10_000),
+        ..Limits::default()
+    });
+    let err = rep("(loop 0)", &env).unwrap_err();
+    assert!(err.contains("limit of 10000 steps"), "{}", err);
+    // The budget is reset for each top-level evaluation.
+    assert_eq!(rep("(+ 1 2)", &env), Ok("3".into()));
+}
+
+#[test]
+fn depth_limit() {
+    let _serial = serial();
+    let env = setup(Limits {
+        max_depth: Some(200),
+        ..Limits::default()
+    });
+    let err = rep("(deep 0)", &env).unwrap_err();
+    assert!(err.contains("maximum nesting depth of 200"), "{}", err);
+}
+
+#[test]
+fn timeout() {
+    let _serial = serial();
+    let env = setup(Limits {
+        timeout: Some(Duration::from_millis(50)),
+        ..Limits::default()
+    });
+    let err = rep("(loop 0)", &env).unwrap_err();
+    assert!(err.contains("timed out"), "{}", err);
+    let started = Instant::now();
+    let err = rep("(sleep 10000)", &env).unwrap_err();
+    assert!(err.contains("timed out"), "{}", err);
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn limits_are_catchable() {
+    let _serial = serial();
+    let env = setup(Limits {
+        max_depth: Some(200),
+        ..Limits::default()
+    });
+    assert_eq!(
+        rep("(try* (deep 0) (catch* e \"caught\"))", &env),
+        Ok("\"caught\"".into())
+    );
+}
+
+#[test]
+fn exhausted_budget_cannot_be_escaped() {
+    let _serial = serial();
+    let env = setup(Limits {
+        max_steps: Some(10_000),
+        ..Limits::default()
+    });
+    let err = rep("(try* (loop 0) (catch* e \"caught\"))", &env).unwrap_err();
+    assert!(err.contains("limit of 10000 steps"), "{}", err);
+}
+
+#[test]
+fn interrupts() {
+    let _serial = serial();
+    let env = setup(Limits::default());
+    for &engine in &[Engine::TreeWalker, Engine::Bytecode] {
+        for src in &[
+            "(loop 0)",
+            "(try* (loop 0) (catch* e \"caught\"))",
+            "(sleep 10000)",
+            "(sh \"sleep\" \"10\")",
+            "(proc-read-line (spawn \"sleep\" \"10\"))",
+            "(proc-wait (spawn \"sleep\" \"10\"))",
+        ] {
+            let started = Instant::now();
+            let interrupter = thread::spawn(|| {
+                thread::sleep(Duration::from_millis(50));
+                limits::interrupt();
+            });
+            let err = rep_using(src, &env, engine).unwrap_err();
+            interrupter.join().unwrap();
+            assert!(err.starts_with("Interrupted"), "{}: {}", src, err);
+            assert!(started.elapsed() < Duration::from_secs(5), "{}", src);
+            // Once dealt with, the interrupt doesn't affect the next form.
+            assert_eq!(rep_using("(+ 1 2)", &env, engine), Ok("3".into()));
+        }
+        // Nor does one which arrives between evaluations.
+        limits::interrupt();
+        assert_eq!(rep_using("(+ 1 2)", &env, engine), Ok("3".into()));
+    }
+}
+
+#[test]
+fn default_depth_fits_the_stack() {
+    let _serial = serial();
+    // The size of the main thread's stack, rather than a test thread's.
+    let on_main_sized_stack = thread::Builder::new().stack_size(8 << 20);
+    let results = on_main_sized_stack
+        .spawn(|| {
+            let mut results = Vec::new();
+            for &engine in &[Engine::TreeWalker, Engine::Bytecode] {
+                let mal = Interpreter::new_using(engine).unwrap();
+                for src in &[
+                    "(def! deep (fn* (n) (+ 1 (deep (+ n 1)))))",
+                    "(def! deep (fn* (n) (map deep [(+ n 1)])))",
+                ] {
+                    mal.eval_str(src).unwrap();
+                    let result = mal.eval_str("(try* (deep 0) (catch* e :caught))");
+                    results.push(result.map(|obj| obj.to_string()).map_err(|e| e.to_string()));
+                }
+            }
+            results
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+    for result in results {
+        assert_eq!(result, Ok(":caught".into()));
+    }
+}
+
+#[test]
+fn each_interpreter_has_its_own_limits() {
+    let _serial = serial();
+    limits::set_limits(Limits::default());
+    let limited = Interpreter::new().unwrap();
+    limited.set_limits(Limits {
+        max_steps: Some(10_000),
+        ..Limits::default()
+    });
+    let unlimited = Interpreter::new().unwrap();
+    let count = "(def! count (fn* (n) (if (= n 0) :done (count (- n 1)))))";
+    limited.eval_str(count).unwrap();
+    unlimited.eval_str(count).unwrap();
+    let err = limited.eval_str("(count 100000)").unwrap_err().to_string();
+    assert!(err.contains("limit of 10000 steps"), "{}", err);
+    assert_eq!(
+        unlimited.eval_str("(count 100000)").unwrap().to_string(),
+        ":done"
+    );
+    assert!(limits::limits().max_steps.is_none());
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use common::eval;
+use rust_dmr_mal::interpreter::{Engine, Interpreter};
+use rust_dmr_mal::namespaces;
+use std::path::PathBuf;
+
+// A fresh directory of library files for each test.
+fn library(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mal-ns-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (path, text) in files {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+    dir
+}
+
+fn interpreters(dir: &PathBuf) -> Vec<Interpreter> {
+    [Engine::TreeWalker, Engine::Bytecode]
+        .iter()
+        .map(|&engine| {
+            let mal = Interpreter::new_using(engine).unwrap();
+            namespaces::add_to_load_path(mal.environment(), dir);
+            mal
+        })
+        .collect()
+}
+
+#[test]
+fn in_ns_and_qualified_symbols() {
+    for mal in interpreters(&PathBuf::from(".")) {
+        assert_eq!(eval(&mal, "*ns*"), Ok("user".into()));
+        mal.eval_str("(def! helper 1)").unwrap();
+        mal.eval_str("(in-ns 'a)").unwrap();
+        mal.eval_str("(def! helper 2)").unwrap();
+        assert_eq!(eval(&mal, "*ns*"), Ok("a".into()));
+        assert_eq!(
+            eval(&mal, "[helper user/helper a/helper]"),
+            Ok("[2 1 2]".into())
+        );
+        // Other namespaces see everything in user, including the primitives.
+        assert_eq!(eval(&mal, "(/ (+ 4 2) 3)"), Ok("2".into()));
+        mal.eval_str("(in-ns 'user)").unwrap();
+        assert_eq!(eval(&mal, "[helper a/helper]"), Ok("[1 2]".into()));
+        assert_eq!(eval(&mal, "(eval '*ns*)"), Ok("user".into()));
+        assert_eq!(eval(&mal, "b/helper"), Err("'b/helper' not found".into()));
+    }
+}
+
+#[test]
+fn ns_macro() {
+    for mal in interpreters(&PathBuf::from(".")) {
+        mal.eval_str("(ns a)").unwrap();
+        mal.eval_str("(def! f (fn* (x) (* x 2)))").unwrap();
+        mal.eval_str("(ns b (:require [a :as alias :refer [f]]))")
+            .unwrap();
+        assert_eq!(
+            eval(&mal, "[(f 1) (alias/f 2) (a/f 3)]"),
+            Ok("[2 4 6]".into())
+        );
+        assert!(eval(&mal, "(ns c (:use a))").is_err());
+    }
+}
+
+#[test]
+fn private_definitions() {
+    for mal in interpreters(&PathBuf::from(".")) {
+        mal.eval_str("(ns a)").unwrap();
+        mal.eval_str("(def! ^:private secret 1)").unwrap();
+        mal.eval_str("(def! ^{:private true} hidden 2)").unwrap();
+        mal.eval_str("(def! ^{:private false} shown 3)").unwrap();
+        mal.eval_str("(def! reveal (fn* () secret))").unwrap();
+        assert_eq!(eval(&mal, "[secret a/hidden]"), Ok("[1 2]".into()));
+        mal.eval_str("(ns b)").unwrap();
+        assert_eq!(eval(&mal, "[(a/reveal) a/shown]"), Ok("[1 3]".into()));
+        assert_eq!(eval(&mal, "a/secret"), Err("'a/secret' not found".into()));
+        assert!(eval(&mal, "a/hidden").is_err());
+        assert!(eval(&mal, "(require '[a :refer [secret]])").is_err());
+    }
+}
+
+#[test]
+fn require_loads_each_namespace_once() {
+    let dir = library(
+        "require",
+        &[
+            (
+                "text/shout_it.mal",
+                "(ns text.shout-it)\n(swap! loads (fn* (n) (+ n 1)))\n(def! ^:private helper (fn* (s) (str s \"!\")))\n(def! shout (fn* (s) (helper s)))",
+            ),
+            (
+                "greet.mal",
+                "(ns greet (:require [text.shout-it :as t]))\n(def! helper (fn* (name) (t/shout (str \"hi \" name))))",
+            ),
+        ],
+    );
+    for mal in interpreters(&dir) {
+        mal.eval_str("(def! loads (atom 0))").unwrap();
+        mal.eval_str("(require 'greet '[text.shout-it :refer [shout]])")
+            .unwrap();
+        assert_eq!(
+            eval(&mal, "(greet/helper \"bob\")"),
+            Ok("\"hi bob!\"".into())
+        );
+        assert_eq!(eval(&mal, "(shout \"a\")"), Ok("\"a!\"".into()));
+        assert_eq!(eval(&mal, "@loads"), Ok("1".into()));
+        assert_eq!(eval(&mal, "*ns*"), Ok("user".into()));
+        assert!(eval(&mal, "text.shout-it/helper").is_err());
+        assert!(eval(&mal, "helper").is_err());
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn shebang_lines_are_ignored() {
+    let dir = library(
+        "shebang",
+        &[
+            ("tool.mal", "#!/usr/bin/env mal\n(ns tool)\n(def! x 1)"),
+            ("script.mal", "#!/usr/bin/env -S mal -i\n(def! y 2)"),
+        ],
+    );
+    for mal in interpreters(&dir) {
+        mal.set_global("script", dir.join("script.mal").to_str().unwrap());
+        mal.eval_str("(require 'tool) (load-file script)").unwrap();
+        assert_eq!(eval(&mal, "[tool/x y]"), Ok("[1 2]".into()));
+        assert_eq!(eval(&mal, "(read-string \"#!ignored\n3\")"), Ok("3".into()));
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn require_errors() {
+    let dir = library(
+        "errors",
+        &[
+            ("a.mal", "(ns a (:require b))"),
+            ("b.mal", "(ns b (:require a))"),
+            ("broken.mal", "(ns broken)\n(def! x 1)\n(undefined)"),
+        ],
+    );
+    for mal in interpreters(&dir) {
+        assert_eq!(
+            eval(&mal, "(require 'missing)"),
+            Err("can't find missing: no missing.mal on the load path".into())
+        );
+        assert_eq!(
+            eval(&mal, "(require 'a)"),
+            Err("cyclic require: a is still loading".into())
+        );
+        assert!(eval(&mal, "(require 'broken)").is_err());
+        // Nothing is left half loaded.
+        assert!(eval(&mal, "broken/x").is_err());
+        assert!(eval(&mal, "(require '[a :as])").is_err());
+        assert!(eval(&mal, "(require 1)").is_err());
+        assert_eq!(eval(&mal, "*ns*"), Ok("user".into()));
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::eval;
+use rust_dmr_mal::environment::{Capability, Environment};
+use rust_dmr_mal::interpreter::Interpreter;
+
+#[test]
+fn sh() {
+    let mal = Interpreter::new().unwrap();
+    mal.eval_str(r#"(def! r (sh "sh" "-c" "echo out; echo err >&2; exit 3"))"#)
+        .unwrap();
+    assert_eq!(eval(&mal, "(get r :exit)"), Ok("3".into()));
+    assert_eq!(eval(&mal, "(get r :out)"), Ok(r#""out\n""#.into()));
+    assert_eq!(eval(&mal, "(get r :err)"), Ok(r#""err\n""#.into()));
+    assert_eq!(
+        eval(&mal, r#"(get (sh "tr" "a-z" "A-Z" {:in "shout"}) :out)"#),
+        Ok(r#""SHOUT""#.into())
+    );
+    assert_eq!(
+        eval(&mal, r#"(get (sh "pwd" {:dir "/"}) :out)"#),
+        Ok(r#""/\n""#.into())
+    );
+    assert_eq!(
+        eval(
+            &mal,
+            r#"(get (sh "sh" "-c" "echo $GREETING-$HOME" {:env {"GREETING" "hi" :HOME nil}}) :out)"#
+        ),
+        Ok(r#""hi-\n""#.into())
+    );
+    assert_eq!(
+        eval(
+            &mal,
+            r#"(try* (sh "/no/such/program") (catch* e (get e :kind)))"#
+        ),
+        Ok(":not-found".into())
+    );
+}
+
+#[test]
+fn spawn() {
+    let mal = Interpreter::new().unwrap();
+    mal.eval_str(r#"(def! p (spawn "sh" "-c" "echo one; echo two; exit 4"))"#)
+        .unwrap();
+    assert!(eval(&mal, "p").unwrap().starts_with("#<process "));
+    assert_eq!(eval(&mal, "(proc-read-line p)"), Ok(r#""one""#.into()));
+    assert_eq!(eval(&mal, "(proc-read-line p)"), Ok(r#""two""#.into()));
+    assert_eq!(eval(&mal, "(proc-read-line p)"), Ok("nil".into()));
+    assert_eq!(eval(&mal, "(proc-wait p)"), Ok("4".into()));
+
+    mal.eval_str(r#"(def! p (spawn "cat" {:in "a\nb\n"}))"#)
+        .unwrap();
+    assert_eq!(eval(&mal, "(proc-read-line p)"), Ok(r#""a""#.into()));
+    assert_eq!(eval(&mal, "(proc-wait p)"), Ok("0".into()));
+    assert_eq!(eval(&mal, "(proc-read-line p)"), Ok("nil".into()));
+
+    mal.eval_str(r#"(def! p (spawn "sleep" "10"))"#).unwrap();
+    mal.eval_str("(proc-kill p)").unwrap();
+    assert_eq!(eval(&mal, "(proc-wait p)"), Ok("nil".into()));
+    assert!(eval(&mal, "(proc-wait 1)").is_err());
+}
+
+#[test]
+fn processes_need_a_capability() {
+    let env = Environment::builder()
+        .with(Capability::Pure)
+        .with(Capability::IoRead)
+        .with(Capability::IoWrite)
+        .build()
+        .unwrap();
+    let mal = Interpreter::with_environment(env);
+    assert!(eval(&mal, r#"(sh "true")"#)
+        .unwrap_err()
+        .contains("not found"));
+    assert!(eval(&mal, r#"(spawn "true")"#)
+        .unwrap_err()
+        .contains("not found"));
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use common::eval;
+use rust_dmr_mal::interpreter::Interpreter;
+
+const DRAWS: &str = "[(rand) (rand-int 10) (rand-nth [:a :b :c]) (shuffle [0 1 2 3 4 5 6 7 8 9])]";
+
+#[test]
+fn seeding_makes_results_reproducible() {
+    let mal = Interpreter::new().unwrap();
+    eval(&mal, "(random-seed! 42)").unwrap();
+    let first = eval(&mal, DRAWS).unwrap();
+    let second = eval(&mal, DRAWS).unwrap();
+    assert_ne!(first, second);
+    eval(&mal, "(random-seed! 42)").unwrap();
+    assert_eq!(eval(&mal, DRAWS).unwrap(), first);
+
+    eval(&mal, "(random-seed! 43)").unwrap();
+    assert_ne!(eval(&mal, DRAWS).unwrap(), first);
+    assert_eq!(eval(&mal, "(random-seed!)"), Ok("nil".into()));
+}
+
+#[test]
+fn results_are_in_range() {
+    let mal = Interpreter::new().unwrap();
+    let src = "(let* [n (rand-int 3)] (if (< n 0) false (< n 3)))";
+    for _ in 0..100 {
+        assert_eq!(eval(&mal, src), Ok("true".into()));
+    }
+    assert_eq!(eval(&mal, "(>= (rand) 0)"), Ok("true".into()));
+    assert_eq!(eval(&mal, "(rand-int 1)"), Ok("0".into()));
+    assert_eq!(eval(&mal, "(rand-nth '(:only))"), Ok(":only".into()));
+    assert_eq!(
+        eval(
+            &mal,
+            "(let* [v (shuffle '(1 1 2))] [(count v) (apply + v)])"
+        ),
+        Ok("[3 4]".into())
+    );
+    assert_eq!(eval(&mal, "(shuffle [])"), Ok("[]".into()));
+    assert!(eval(&mal, "(rand-int 0)").is_err());
+    assert!(eval(&mal, "(rand-int -5)").is_err());
+    assert!(eval(&mal, "(rand-nth [])").is_err());
+}
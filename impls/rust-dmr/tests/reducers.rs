@@ -0,0 +1,83 @@
+mod common;
+
+use common::{check, eval};
+use rust_dmr_mal::interpreter::Interpreter;
+
+#[test]
+fn reduce_over_every_collection() {
+    check(&[
+        ("(reduce + 7 [])", "7"),
+        ("(reduce + 7 '(1 2))", "10"),
+        ("(reduce + nil)", "0"),
+        ("(reduce str \"a\" \"bc\")", "\"abc\""),
+        ("(reduce (fn* (n e) (+ n (nth e 1))) 0 {:a 1 :b 2})", "3"),
+        ("(reduce + (take 4 (range)))", "6"),
+        ("(reduce concat [1] [[2] [3]])", "(1 2 3)"),
+    ]);
+}
+
+#[test]
+fn reduced_stops_early() {
+    check(&[
+        ("(reduced? (reduced 1))", "true"),
+        ("(reduced? 1)", "false"),
+        ("@(reduced 1)", "1"),
+        ("(unreduced (reduced 1))", "1"),
+        ("(unreduced 1)", "1"),
+        (
+            "(reduce (fn* (acc x) (if (> x 2) (reduced acc) (+ acc x))) 0 (range))",
+            "3",
+        ),
+        ("(reduce (fn* (acc x) (reduced x)) [:a :b])", ":b"),
+        (
+            "(let* (seen (atom 0)) (do (reduce (fn* (_ x) (do (reset! seen x) (reduced x))) nil [1 2 3]) @seen))",
+            "1",
+        ),
+    ]);
+}
+
+#[test]
+fn transducers() {
+    check(&[
+        ("(transduce (map inc) + [1 2 3])", "9"),
+        ("(transduce (filter odd?) + 100 [1 2 3])", "104"),
+        ("(transduce (remove odd?) conj [1 2 3 4])", "[2 4]"),
+        ("(transduce (take 2) conj (range))", "[0 1]"),
+        (
+            "(transduce (comp (map inc) (filter odd?) (take 3)) + (range))",
+            "9",
+        ),
+        ("(transduce (comp) + [1 2])", "3"),
+        ("(transduce (take 0) + [1 2])", "0"),
+    ]);
+}
+
+#[test]
+fn into_collections() {
+    check(&[
+        ("(into [] '(1 2))", "[1 2]"),
+        ("(into [0] (map inc) [1 2])", "[0 2 3]"),
+        ("(into () [1 2 3])", "(3 2 1)"),
+        ("(into nil [1 2])", "(2 1)"),
+        ("(into [] \"ab\")", "[\"a\" \"b\"]"),
+        ("(get (into {:a 1} [[:b 2]]) :b)", "2"),
+        ("(get (into {} {:a 1}) :a)", "1"),
+        ("(into [] (comp (filter odd?) (take 2)) (range))", "[1 3]"),
+        ("(into [] (take 3) (range))", "[0 1 2]"),
+    ]);
+    let mal = Interpreter::new().unwrap();
+    assert!(eval(&mal, "(into {} [[:a]])").is_err());
+    assert!(eval(&mal, "(into 1 [])").is_err());
+}
+
+#[test]
+fn comp_and_conj() {
+    check(&[
+        ("((comp inc inc) 1)", "3"),
+        ("((comp) 5)", "5"),
+        ("((comp str +) 1 2)", "\"3\""),
+        ("(conj)", "[]"),
+        ("(conj [1])", "[1]"),
+        ("(conj nil 1)", "(1)"),
+    ]);
+}
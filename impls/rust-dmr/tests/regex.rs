@@ -0,0 +1,94 @@
+mod common;
+
+use common::eval;
+use rust_dmr_mal::interpreter::Interpreter;
+
+#[test]
+fn literals_and_printing() {
+    let mal = Interpreter::new().unwrap();
+    assert_eq!(eval(&mal, r##"#"[0-9]+""##), Ok(r##"#"[0-9]+""##.into()));
+    assert_eq!(
+        eval(&mal, r##"(pr-str #"say \"hi\"\d")"##),
+        Ok(r##""#\"say \\\"hi\\\"\\d\"""##.into())
+    );
+    assert_eq!(eval(&mal, r##"(str #"a+")"##), Ok(r#""a+""#.into()));
+    assert_eq!(
+        eval(&mal, r##"(re-find (re-pattern "\\d+") "ab12")"##),
+        Ok(r#""12""#.into())
+    );
+    assert!(eval(&mal, r#"(re-pattern "(")"#)
+        .unwrap_err()
+        .contains("bad regex"));
+    assert!(eval(&mal, r##"#"(""##).unwrap_err().contains("bad regex"));
+}
+
+#[test]
+fn matching() {
+    let mal = Interpreter::new().unwrap();
+    assert_eq!(
+        eval(&mal, r##"(re-find #"\d+" "ab12cd345")"##),
+        Ok(r#""12""#.into())
+    );
+    assert_eq!(
+        eval(&mal, r##"(re-find #"(\w)(\d)?" "x!")"##),
+        Ok(r#"["x" "x" nil]"#.into())
+    );
+    assert_eq!(eval(&mal, r##"(re-find #"z" "abc")"##), Ok("nil".into()));
+    assert_eq!(
+        eval(&mal, r##"(re-matches #"a|ab" "ab")"##),
+        Ok(r#""ab""#.into())
+    );
+    assert_eq!(
+        eval(&mal, r##"(re-matches #"\d+" "12a")"##),
+        Ok("nil".into())
+    );
+    assert_eq!(
+        eval(&mal, r##"(re-seq #"(\w)=(\d)" "a=1, b=2")"##),
+        Ok(r#"(["a=1" "a" "1"] ["b=2" "b" "2"])"#.into())
+    );
+    assert_eq!(eval(&mal, r##"(re-seq #"\d" "abc")"##), Ok("nil".into()));
+    assert_eq!(
+        eval(
+            &mal,
+            r##"(let* [g (re-groups #"(?P<year>\d{4})-(?P<month>\d\d)(-(?P<day>\d\d))?" "on 2020-07")]
+                   [(get g :year) (get g :month) (get g :day) (contains? g :day)])"##
+        ),
+        Ok(r#"["2020" "07" nil true]"#.into())
+    );
+    assert_eq!(
+        eval(&mal, r##"(get (re-groups #"(?P<y>\d+)" "in 1999") :y)"##),
+        Ok(r#""1999""#.into())
+    );
+}
+
+#[test]
+fn replace_and_split() {
+    let mal = Interpreter::new().unwrap();
+    assert_eq!(
+        eval(
+            &mal,
+            r##"(replace "2020-07-01" #"(\d+)-(\d+)-(\d+)" "$3/$2/$1")"##
+        ),
+        Ok(r#""01/07/2020""#.into())
+    );
+    assert_eq!(
+        eval(&mal, r##"(replace "a1b22" #"(\d)+" (fn* (m) (nth m 1)))"##),
+        Ok(r#""a1b2""#.into())
+    );
+    assert_eq!(
+        eval(&mal, r##"(split "a, b,c" #",\s*")"##),
+        Ok(r#"["a" "b" "c"]"#.into())
+    );
+    assert_eq!(
+        eval(&mal, r##"(split ",a," #",")"##),
+        Ok(r#"["" "a" ""]"#.into())
+    );
+    // Like an empty string, a regex matching the empty string splits into characters.
+    assert_eq!(
+        eval(
+            &mal,
+            r##"[(split "abc" #"") (split "abc" #"x*") (split "" #"")]"##
+        ),
+        Ok(r#"[["a" "b" "c"] ["a" "b" "c"] []]"#.into())
+    );
+}
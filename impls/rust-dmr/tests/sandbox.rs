@@ -0,0 +1,59 @@
+use rust_dmr_mal::environment::{Capability, Environment};
+use rust_dmr_mal::interpreter;
+use rust_dmr_mal::printer::Outcome;
+
+fn rep(line: &str, env: &std::rc::Rc<Environment>) -> Result<String, String> {
+    interpreter::rep(line, env).map(|outcome| match outcome {
+        Outcome::String(s) => s,
+        Outcome::Empty => String::new(),
+    })
+}
+
+#[test]
+fn pure_environment_has_no_io() {
+    let env = Environment::builder()
+        .with(Capability::Pure)
+        .with_prelude()
+        .build()
+        .unwrap();
+    assert_eq!(rep("(cond false 1 true (+ 1 2))", &env), Ok("3".into()));
+    for line in &[
+        "(slurp \"Cargo.toml\")",
+        "(load-file \"Cargo.toml\")",
+        "(println 1)",
+        "(readline \"> \")",
+        "(time-ms)",
+        "(-rust-log-level 'off)",
+        "(eval 1)",
+    ] {
+        assert!(
+            rep(line, &env).unwrap_err().contains("not found"),
+            "{}",
+            line
+        );
+    }
+}
+
+#[test]
+fn capabilities_can_be_combined() {
+    let env = Environment::builder()
+        .with(Capability::Pure)
+        .with(Capability::IoRead)
+        .with_eval()
+        .build()
+        .unwrap();
+    assert!(rep("(slurp \"Cargo.toml\")", &env).is_ok());
+    assert_eq!(rep("(eval '(+ 1 2))", &env), Ok("3".into()));
+    assert!(rep("(println 1)", &env).is_err());
+}
+
+#[test]
+fn capabilities_parse_from_names() {
+    for capability in Capability::ALL.iter() {
+        assert_eq!(
+            capability.name().parse::<Capability>().unwrap(),
+            *capability
+        );
+    }
+    assert!("network".parse::<Capability>().is_err());
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use common::{check, eval};
+use rust_dmr_mal::interpreter::Interpreter;
+
+#[test]
+fn mapping_and_filtering() {
+    check(&[
+        ("(map inc [1 2 3])", "(2 3 4)"),
+        ("(map + [1 2 3] '(10 20) [100 200 300])", "(111 222)"),
+        ("(map inc nil)", "()"),
+        ("(map count {:a [1 2]})", "(2)"),
+        ("(mapcat (fn* (x) [x x]) [1 2])", "(1 1 2 2)"),
+        ("(mapcat list [1 2] [3 4])", "(1 3 2 4)"),
+        ("(filter odd? (range 6))", "(1 3 5)"),
+        ("(remove odd? [1 2 3 4])", "(2 4)"),
+        (
+            r#"(filter (fn* (c) (= c "a")) "banana")"#,
+            r#"("a" "a" "a")"#,
+        ),
+        ("(some (fn* (x) (if (> x 2) (* x 10))) [1 2 3 4])", "30"),
+        ("(some odd? [2 4])", "nil"),
+        ("(every? odd? [1 3])", "true"),
+        ("(every? odd? [1 2])", "false"),
+        ("(every? odd? nil)", "true"),
+    ]);
+}
+
+#[test]
+fn reducing() {
+    check(&[
+        ("(reduce + [1 2 3 4])", "10"),
+        ("(reduce + 100 '(1 2 3))", "106"),
+        ("(reduce + [])", "0"),
+        ("(reduce + 5 nil)", "5"),
+        (
+            "(reduce (fn* (acc kv) (+ acc (nth kv 1))) 0 {:a 1 :b 2})",
+            "3",
+        ),
+    ]);
+}
+
+#[test]
+fn slicing() {
+    check(&[
+        ("(take 2 [1 2 3])", "(1 2)"),
+        ("(take 5 '(1 2))", "(1 2)"),
+        ("(take -1 [1])", "()"),
+        ("(drop 2 [1 2 3])", "(3)"),
+        ("(drop 9 [1 2 3])", "()"),
+        ("(take-while odd? [1 3 4 5])", "(1 3)"),
+        ("(drop-while odd? [1 3 4 5])", "(4 5)"),
+        ("(last [1 2 3])", "3"),
+        ("(last nil)", "nil"),
+        (r#"(last "abc")"#, r#""c""#),
+        ("(reverse [1 2 3])", "(3 2 1)"),
+        ("(range 4)", "(0 1 2 3)"),
+        ("(range 2 5)", "(2 3 4)"),
+        ("(range 10 0 -3)", "(10 7 4 1)"),
+        ("(range 0)", "()"),
+    ]);
+}
+
+#[test]
+fn sorting() {
+    check(&[
+        ("(sort [3 1 2])", "(1 2 3)"),
+        (r#"(sort ["b" "a" "c"])"#, r#"("a" "b" "c")"#),
+        ("(sort [[1 2] [1] [0 5]])", "([0 5] [1] [1 2])"),
+        ("(sort > [3 1 2])", "(3 2 1)"),
+        ("(sort - [3 1 2])", "(1 2 3)"),
+        ("(sort-by count [[1 2 3] [] [1]])", "([] [1] [1 2 3])"),
+        (
+            "(sort-by first > [[1 :a] [3 :b] [2 :c]])",
+            "([3 :b] [2 :c] [1 :a])",
+        ),
+        ("(sort-by count [[2] [1] []])", "([] [2] [1])"),
+    ]);
+    let mal = Interpreter::new().unwrap();
+    assert!(eval(&mal, "(sort [1 :a])")
+        .unwrap_err()
+        .contains("NotComparable"));
+    assert!(eval(&mal, "(sort (fn* (a b) (throw :oops)) [1 2])")
+        .unwrap_err()
+        .contains(":oops"));
+}
+
+#[test]
+fn grouping() {
+    check(&[
+        (
+            "(get (group-by (fn* (x) (if (odd? x) :odd :even)) (range 5)) :even)",
+            "[0 2 4]",
+        ),
+        (r#"(get (frequencies "hello") "l")"#, "2"),
+        ("(frequencies [])", "{}"),
+        ("(partition 2 [1 2 3 4 5])", "((1 2) (3 4))"),
+        ("(partition 2 1 [1 2 3])", "((1 2) (2 3))"),
+        ("(partition 3 3 [:x] (range 7))", "((0 1 2) (3 4 5) (6 :x))"),
+        (
+            "(partition 3 1 [:p] [1 2 3 4])",
+            "((1 2 3) (2 3 4) (3 4 :p))",
+        ),
+        ("(interleave [1 2 3] '(:a :b))", "(1 :a 2 :b)"),
+        ("(interleave)", "()"),
+        ("(get (zipmap [:a :b :c] [1 2]) :b)", "2"),
+        ("(count (keys (zipmap [:a :b :c] [1 2])))", "2"),
+    ]);
+    let mal = Interpreter::new().unwrap();
+    assert!(eval(&mal, "(partition 0 [1])").is_err());
+    assert!(eval(&mal, "(frequencies [1 2])").is_err());
+}
@@ -0,0 +1,87 @@
+#![cfg(feature = "serde")]
+
+use rust_dmr_mal::interpreter::Interpreter;
+use rust_dmr_mal::reader::read_str;
+use rust_dmr_mal::serialization::from_mal;
+use rust_dmr_mal::types::MalObject;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[test]
+fn serialize_to_json() {
+    let obj = read_str(r#"[nil true -3 "s" :k sym (1 [2]) {:a 1}]"#).unwrap();
+    let json = serde_json::to_string(&obj).unwrap();
+    assert_eq!(json, r#"[null,true,-3,"s",":k","sym",[1,[2]],{":a":1}]"#);
+
+    let mal = Interpreter::new().unwrap();
+    let closure = mal.eval_str("(fn* (x) x)").unwrap();
+    assert!(serde_json::to_string(&closure).is_err());
+}
+
+#[test]
+fn deserialize_from_json() {
+    let obj: MalObject =
+        serde_json::from_str(r#"{"a": [1, ":b", null], ":c": {"d": false}}"#).unwrap();
+    let expected = read_str(r#"{"a" [1 :b nil] :c {"d" false}}"#).unwrap();
+    assert_eq!(obj, expected);
+    assert!(serde_json::from_str::<MalObject>("1.5").is_err());
+    assert!(serde_json::from_str::<MalObject>("[18446744073709551615]").is_err());
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+struct Config {
+    name: String,
+    max_retries: u8,
+    tags: Vec<String>,
+    limits: HashMap<String, i64>,
+    mode: Mode,
+    shape: Shape,
+    fallback: Option<Box<Config>>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+enum Mode {
+    Fast,
+    Safe,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+enum Shape {
+    Circle(u32),
+    Rect { w: u32, h: u32 },
+}
+
+#[test]
+fn deserialize_struct_from_mal_map() {
+    let obj = read_str(
+        r#"{:name "primary" :max-retries 3 :tags (:a "b") :limits {:cpu 2 "mem" 512}
+            :mode :safe :shape {:rect {:w 2 :h 3}}
+            :fallback {:name "backup" :max-retries 0 :tags [] :limits {}
+                       :mode "fast" :shape {:circle 1} :fallback nil}}"#,
+    )
+    .unwrap();
+    let config: Config = from_mal(&obj).unwrap();
+    assert_eq!(config.name, "primary");
+    assert_eq!(config.tags, vec!["a", "b"]);
+    assert_eq!(config.limits["cpu"], 2);
+    assert_eq!(config.limits["mem"], 512);
+    assert_eq!(config.mode, Mode::Safe);
+    assert_eq!(config.shape, Shape::Rect { w: 2, h: 3 });
+    let fallback = config.fallback.unwrap();
+    assert_eq!(fallback.shape, Shape::Circle(1));
+    assert_eq!(fallback.mode, Mode::Fast);
+    assert!(fallback.fallback.is_none());
+
+    let bad = read_str(r#"{:name "x" :max-retries 300}"#).unwrap();
+    assert!(from_mal::<Config>(&bad).is_err());
+}
+
+#[test]
+fn mal_objects_round_trip_through_from_mal() {
+    let obj = read_str(r#"{:a [1 "x" :y] "b" nil}"#).unwrap();
+    let copy: MalObject = from_mal(&obj).unwrap();
+    assert_eq!(copy, obj);
+}
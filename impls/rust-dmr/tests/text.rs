@@ -0,0 +1,83 @@
+mod common;
+
+use common::eval;
+use rust_dmr_mal::interpreter::Interpreter;
+
+#[test]
+fn substrings_count_characters() {
+    let mal = Interpreter::new().unwrap();
+    assert_eq!(
+        eval(&mal, r#"(subs "héllo wörld" 1 4)"#),
+        Ok(r#""éll""#.into())
+    );
+    assert_eq!(eval(&mal, r#"(subs "héllo" 5)"#), Ok(r#""""#.into()));
+    assert_eq!(
+        eval(&mal, r#"(index-of "naïve café" "café")"#),
+        Ok("6".into())
+    );
+    assert_eq!(eval(&mal, r#"(index-of "abcabc" "b" 2)"#), Ok("4".into()));
+    assert_eq!(eval(&mal, r#"(index-of "abc" "z")"#), Ok("nil".into()));
+    assert_eq!(
+        eval(&mal, r#"(string/reverse "añb😀")"#),
+        Ok(r#""😀bña""#.into())
+    );
+    assert!(eval(&mal, r#"(subs "héllo" 6)"#)
+        .unwrap_err()
+        .contains("bad index"));
+    assert!(eval(&mal, r#"(subs "héllo" 3 2)"#).is_err());
+}
+
+#[test]
+fn split_and_join() {
+    let mal = Interpreter::new().unwrap();
+    assert_eq!(
+        eval(&mal, r#"(split "a,b,,c" ",")"#),
+        Ok(r#"["a" "b" "" "c"]"#.into())
+    );
+    assert_eq!(eval(&mal, r#"(split "dé" "")"#), Ok(r#"["d" "é"]"#.into()));
+    assert_eq!(
+        eval(&mal, r#"(join ", " [1 "b" :c])"#),
+        Ok(r#""1, b, :c""#.into())
+    );
+    assert_eq!(eval(&mal, r#"(join '("x" "y"))"#), Ok(r#""xy""#.into()));
+    assert_eq!(eval(&mal, r#"(join "-" nil)"#), Ok(r#""""#.into()));
+}
+
+#[test]
+fn transformations_and_tests() {
+    let mal = Interpreter::new().unwrap();
+    assert_eq!(eval(&mal, r#"(trim "  hi\n")"#), Ok(r#""hi""#.into()));
+    assert_eq!(
+        eval(&mal, r#"(upper-case "straße")"#),
+        Ok(r#""STRASSE""#.into())
+    );
+    assert_eq!(eval(&mal, r#"(lower-case "ÀB")"#), Ok(r#""àb""#.into()));
+    assert_eq!(
+        eval(&mal, r#"(starts-with? "mal" "ma")"#),
+        Ok("true".into())
+    );
+    assert_eq!(eval(&mal, r#"(ends-with? "mal" "ma")"#), Ok("false".into()));
+    assert_eq!(
+        eval(&mal, r#"(replace "a-b-c" "-" "+")"#),
+        Ok(r#""a+b+c""#.into())
+    );
+}
+
+#[test]
+fn format() {
+    let mal = Interpreter::new().unwrap();
+    assert_eq!(
+        eval(&mal, r#"(format "%s has %d%% of %s" "x" 50 [1 "y"])"#),
+        Ok(r#""x has 50% of [1 y]""#.into())
+    );
+    assert!(eval(&mal, r#"(format "%d" "x")"#).is_err());
+    assert!(eval(&mal, r#"(format "%s %s" 1)"#)
+        .unwrap_err()
+        .contains("no argument"));
+    assert!(eval(&mal, r#"(format "%s" 1 2)"#)
+        .unwrap_err()
+        .contains("unused"));
+    assert!(eval(&mal, r#"(format "%x" 1)"#)
+        .unwrap_err()
+        .contains("unknown directive %x"));
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use common::eval;
+use rust_dmr_mal::interpreter::Interpreter;
+
+#[test]
+fn instants_read_and_print_as_iso_8601() {
+    let mal = Interpreter::new().unwrap();
+    assert_eq!(
+        eval(&mal, r#"(inst "2020-07-01T12:30:00.5+01:00")"#),
+        Ok(r#"#inst "2020-07-01T12:30:00.500+01:00""#.into())
+    );
+    assert_eq!(
+        eval(&mal, r#"(str (inst "2020-07-01"))"#),
+        Ok(r#""2020-07-01T00:00:00Z""#.into())
+    );
+    assert_eq!(
+        eval(&mal, r#"(inst-ms (inst "1970-01-01T00:00:01"))"#),
+        Ok("1000".into())
+    );
+    assert_eq!(
+        eval(&mal, "(inst 86400000)"),
+        Ok(r#"#inst "1970-01-02T00:00:00Z""#.into())
+    );
+    assert_eq!(
+        eval(
+            &mal,
+            r#"(= (inst "2020-07-01T12:30:00+01:00") (inst "2020-07-01T11:30:00Z"))"#
+        ),
+        Ok("true".into())
+    );
+    assert_eq!(
+        eval(&mal, r#"(to-utc (inst "2020-07-01T12:30:00+01:00"))"#),
+        Ok(r#"#inst "2020-07-01T11:30:00Z""#.into())
+    );
+    assert_eq!(eval(&mal, "(inst? (now))"), Ok("true".into()));
+    assert_eq!(eval(&mal, "(inst? 0)"), Ok("false".into()));
+    assert!(eval(&mal, r#"(inst "yesterday")"#)
+        .unwrap_err()
+        .contains("can't read an ISO-8601 instant"));
+}
+
+#[test]
+fn instants_round_trip() {
+    let mal = Interpreter::new().unwrap();
+    mal.eval_str(r#"(def! t (inst "2020-07-01T12:30:00.25+01:00"))"#)
+        .unwrap();
+    assert_eq!(
+        eval(&mal, r#"#inst "2020-07-01T12:30:00.250+01:00""#),
+        eval(&mal, "t")
+    );
+    assert_eq!(
+        eval(&mal, "(= t (read-string (pr-str t)))"),
+        Ok("true".into())
+    );
+    assert_eq!(
+        eval(&mal, "(= t (read-edn (write-edn t)))"),
+        Ok("true".into())
+    );
+    assert_eq!(
+        eval(&mal, "(inst? (first (read-string (pr-str [t]))))"),
+        Ok("true".into())
+    );
+    assert!(eval(&mal, r#"#inst "yesterday""#)
+        .unwrap_err()
+        .contains("bad #inst literal"));
+}
+
+#[test]
+fn formatting_and_fields() {
+    let mal = Interpreter::new().unwrap();
+    mal.eval_str(r#"(def! t (inst "2020-07-01T12:30:05-04:00"))"#)
+        .unwrap();
+    assert_eq!(
+        eval(&mal, r#"(format-inst t "%d/%m/%Y %H:%M")"#),
+        Ok(r#""01/07/2020 12:30""#.into())
+    );
+    assert!(eval(&mal, r#"(format-inst t "%Q")"#)
+        .unwrap_err()
+        .contains("bad format pattern"));
+    assert_eq!(
+        eval(
+            &mal,
+            "(let* [f (inst-fields t)] [(get f :year) (get f :month) (get f :day) (get f :hour) (get f :second) (get f :weekday) (get f :offset)])"
+        ),
+        Ok("[2020 7 1 12 5 3 -14400]".into())
+    );
+    assert!(eval(&mal, "(inst-ms 1)")
+        .unwrap_err()
+        .contains("NotAnInstant"));
+}
+
+#[test]
+fn durations_are_milliseconds() {
+    let mal = Interpreter::new().unwrap();
+    assert_eq!(
+        eval(
+            &mal,
+            r#"(inst-plus (inst "2020-12-31T23:59:59+02:00") 1500)"#
+        ),
+        Ok(r#"#inst "2021-01-01T00:00:00.500+02:00""#.into())
+    );
+    assert_eq!(
+        eval(
+            &mal,
+            r#"(inst-diff (inst "2020-01-01") (inst "2019-12-31T23:00:00Z"))"#
+        ),
+        Ok("-3600000".into())
+    );
+}
+
+#[test]
+fn a_frozen_clock_only_moves_when_slept() {
+    let mal = Interpreter::new().unwrap();
+    mal.eval_str(r#"(freeze-clock! (inst "2020-07-01T12:00:00Z"))"#)
+        .unwrap();
+    assert_eq!(eval(&mal, "(time-ms)"), Ok("1593604800000".into()));
+    let before = eval(&mal, "(nano-time)").unwrap();
+    assert_eq!(eval(&mal, "(nano-time)").unwrap(), before);
+    let started = std::time::Instant::now();
+    mal.eval_str("(sleep 3600000)").unwrap();
+    assert!(started.elapsed().as_secs() < 1);
+    assert_eq!(
+        eval(&mal, "(now)"),
+        Ok(r#"#inst "2020-07-01T13:00:00Z""#.into())
+    );
+    mal.eval_str("(freeze-clock! nil)").unwrap();
+    assert_eq!(eval(&mal, "(< 1593604800000 (time-ms))"), Ok("true".into()));
+}
+
+#[test]
+fn sleeping_and_timing() {
+    let mal = Interpreter::new().unwrap();
+    let src = "(let* [start (nano-time)] (do (sleep 20) (- (nano-time) start)))";
+    let elapsed: i64 = eval(&mal, src).unwrap().parse().unwrap();
+    assert!(elapsed >= 20_000_000);
+    assert!(eval(&mal, "(sleep :forever)").is_err());
+}